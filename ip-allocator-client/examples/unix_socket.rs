@@ -0,0 +1,13 @@
+//! Run with `cargo run --example unix_socket --features unix-socket`
+//! against a server configured with `unix_socket = "/tmp/ip-allocator.sock"`.
+use ip_allocator_client::Client;
+
+#[tokio::main]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let client = Client::with_unix_socket("/tmp/ip-allocator.sock")?;
+
+    let borrow_result = client.handlers_ip_borrow(None, None).await?;
+    println!("✅ Borrowed item over unix socket: {:?}", borrow_result.item);
+
+    Ok(())
+}
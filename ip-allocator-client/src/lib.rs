@@ -22,8 +22,28 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ## Unix domain sockets
+//!
+//! With the `unix-socket` feature, [`Client::with_unix_socket`] connects to a
+//! server started with `unix_socket = "/path/to.sock"` in its config.
 
 #![allow(clippy::all)]
 #![allow(unused_imports, dead_code)]
 
 include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
+
+#[cfg(all(unix, feature = "unix-socket"))]
+impl Client {
+    /// Create a client that sends every request over the unix domain socket at `path`.
+    pub fn with_unix_socket(path: impl Into<std::path::PathBuf>) -> Result<Self, reqwest::Error> {
+        let dur = std::time::Duration::from_secs(15);
+        let client = reqwest::ClientBuilder::new()
+            .unix_socket(path.into())
+            .connect_timeout(dur)
+            .timeout(dur)
+            .build()?;
+        // The host is ignored when connecting over the socket
+        Ok(Self::new_with_client("http://localhost", client))
+    }
+}
@@ -0,0 +1,119 @@
+use rocket_okapi::okapi::schemars::{self, JsonSchema};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::guards::admin_auth::AdminAuth;
+use crate::store::{now_secs, Store};
+use crate::AppState;
+
+/// `log` target of admin audit events
+const AUDIT_TARGET: &str = "admin_audit";
+/// `log` target of borrow, return and submit audit events
+const ITEM_AUDIT_TARGET: &str = "audit_log";
+
+/// One admin mutation, as logged and kept in the `admin_audit` list
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AuditEntry {
+    /// The admin endpoint's action, e.g. `force_return`
+    pub action: String,
+    /// Items the action touched; empty for actions on operations or subscribers
+    #[serde(default)]
+    pub items: Vec<Value>,
+    /// What else the action targeted, e.g. an operation id or subscriber
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// Hash of the admin key used (see [`key_id`]); unset without a configured `admin_key`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// Unix timestamp (seconds)
+    pub at: u64,
+}
+
+/// One borrow, return or submit, as logged and kept in the `audit_log` list
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ItemAuditEntry {
+    /// Unix timestamp (seconds)
+    pub timestamp: u64,
+    /// `borrow`, `return` or `submit`
+    pub action: String,
+    pub item: Value,
+    /// Owner id (or client IP) of the request, when it had one
+    #[serde(default)]
+    pub owner: Option<String>,
+    /// The return or submit operation; unset for borrows, which have none
+    #[serde(default)]
+    pub operation_id: Option<String>,
+}
+
+/// Identify an admin key without revealing it: the first 16 hex digits of its SHA-256
+pub fn key_id(key: &str) -> String {
+    Sha256::digest(key.as_bytes()).iter().take(8).map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Log an admin mutation and, with `admin_audit_redis`, append it to the `admin_audit`
+/// list. A failed append is logged; the mutation itself already happened.
+pub async fn record(
+    app: &AppState,
+    store: &Store,
+    admin: &AdminAuth,
+    action: &str,
+    items: Vec<Value>,
+    detail: Option<String>,
+) {
+    let entry = AuditEntry {
+        action: action.to_string(),
+        items,
+        detail,
+        key_id: admin.key_id().map(str::to_string),
+        at: now_secs(),
+    };
+    let json = serde_json::to_string(&entry).unwrap_or_default();
+    log::info!(target: AUDIT_TARGET, "{}", json);
+    if app.config.admin_audit_redis {
+        if let Err(e) = store.append_audit(&json).await {
+            log::warn!(target: AUDIT_TARGET, "failed to store audit entry: {}", e);
+        }
+    }
+}
+
+/// With `audit_log`, log a borrow, return or submit and append it to the `audit_log`
+/// list. Best effort: a failed append is logged and the request carries on.
+pub async fn record_item(
+    app: &AppState,
+    store: &Store,
+    action: &str,
+    item: &Value,
+    owner: Option<&str>,
+    operation_id: Option<&str>,
+) {
+    if !app.config.audit_log {
+        return;
+    }
+    let entry = ItemAuditEntry {
+        timestamp: now_secs(),
+        action: action.to_string(),
+        item: item.clone(),
+        owner: owner.map(str::to_string),
+        operation_id: operation_id.map(str::to_string),
+    };
+    let json = serde_json::to_string(&entry).unwrap_or_default();
+    log::info!(target: ITEM_AUDIT_TARGET, "{}", json);
+    if let Err(e) = store.append_item_audit(&json).await {
+        log::warn!(target: ITEM_AUDIT_TARGET, "failed to store audit entry: {}", e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_id_is_stable_and_hides_the_key() {
+        let id = key_id("s3cret");
+        assert_eq!(id, key_id("s3cret"));
+        assert_ne!(id, key_id("other"));
+        assert_eq!(id.len(), 16);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()) && !id.contains("s3cret"));
+    }
+}
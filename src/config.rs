@@ -1,20 +1,165 @@
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
 use serde::Deserialize;
 
 #[derive(Debug, Deserialize, Clone, Default)]
+#[allow(non_snake_case)]
 pub struct SubscriberDef {
     pub post: String,
     #[serde(default)]
     pub mustSuceed: bool,
     #[serde(default, rename = "async")]
     pub r#async: bool,
+    /// Custom request body for this subscriber instead of the default event payload.
+    /// String values may use `{{item}}`, `{{ip}}`, `{{event}}` and `{{operation_id}}`;
+    /// a string that is exactly `{{item}}` is replaced by the item itself.
+    #[serde(default)]
+    pub body_template: Option<serde_json::Value>,
+    /// Only notify this subscriber for items matching the condition, e.g. `"hostname"`
+    /// (the field is present) or `"env == \"prod\""` (the field equals a value).
+    /// Skipped subscribers do not count towards must-succeed.
+    #[serde(default)]
+    pub when: Option<SubscriberCondition>,
+    /// Borrow subscribers only: notify this long after the borrow, and not at all if the
+    /// item is returned first. Deferred notifications never hold up or roll back a borrow.
+    #[serde(default)]
+    pub notify_after_secs: Option<u64>,
+    /// Give up on each request to this subscriber after this many seconds; defaults to
+    /// `subscriber_timeout_secs`
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+    /// Retry connection errors, timeouts and 5xx answers this many times; 4xx answers
+    /// are never retried
+    #[serde(default)]
+    pub max_retries: u32,
+    /// Delay before the first retry, doubled for each further one plus jitter (default 200)
+    #[serde(default)]
+    pub base_delay_ms: Option<u64>,
+    /// Follow 3xx answers (up to 10 hops); by default a redirect fails the notification
+    #[serde(default)]
+    pub follow_redirects: bool,
+    /// Sign each request with HMAC-SHA256 under this secret in `X-Signature-256`.
+    /// `${VAR}` is replaced by the environment variable `VAR`.
+    #[serde(default)]
+    pub hmac_secret: Option<String>,
+    /// Path probed by `/readyz` with `readiness_requires_subscribers`, relative to the
+    /// `post` URL (e.g. `/healthz`); the `post` URL itself when unset
+    #[serde(default)]
+    pub health_path: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Clone, Default)]
+impl SubscriberDef {
+    /// Whether this subscriber is notified about `item`
+    pub fn applies_to(&self, item: &serde_json::Value) -> bool {
+        self.when.as_ref().is_none_or(|when| when.matches(item))
+    }
+
+    /// The signing secret with environment variables expanded; fails if one is unset
+    pub fn hmac_secret(&self) -> Result<Option<String>, String> {
+        self.hmac_secret.as_deref().map(expand_env).transpose()
+    }
+
+    pub fn base_delay_ms(&self) -> u64 {
+        self.base_delay_ms.unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS)
+    }
+}
+
+/// A subscriber's `when` predicate over the item's top-level fields:
+/// `<field>` holds when the field is present and not null, `<field> == <value>` when it
+/// equals the value. The value is JSON (`"prod"`, `22`, `true`); a bare word is a string.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String")]
+pub enum SubscriberCondition {
+    Exists(String),
+    Equals(String, serde_json::Value),
+}
+
+impl SubscriberCondition {
+    pub fn matches(&self, item: &serde_json::Value) -> bool {
+        match self {
+            SubscriberCondition::Exists(field) => item.get(field).is_some_and(|v| !v.is_null()),
+            SubscriberCondition::Equals(field, value) => item.get(field) == Some(value),
+        }
+    }
+}
+
+impl std::fmt::Display for SubscriberCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubscriberCondition::Exists(field) => write!(f, "{}", field),
+            SubscriberCondition::Equals(field, value) => write!(f, "{} == {}", field, value),
+        }
+    }
+}
+
+impl TryFrom<String> for SubscriberCondition {
+    type Error = String;
+
+    fn try_from(expr: String) -> Result<Self, Self::Error> {
+        let field_name = |field: &str| {
+            let field = field.trim();
+            if field.is_empty() || field.contains(char::is_whitespace) {
+                Err(format!("invalid `when` condition `{}`: expected `<field>` or `<field> == <value>`", expr))
+            } else {
+                Ok(field.to_string())
+            }
+        };
+        match expr.split_once("==") {
+            Some((field, value)) => {
+                let value = value.trim();
+                if value.is_empty() {
+                    return Err(format!("invalid `when` condition `{}`: missing value after `==`", expr));
+                }
+                let value = serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+                Ok(SubscriberCondition::Equals(field_name(field)?, value))
+            }
+            None => Ok(SubscriberCondition::Exists(field_name(&expr)?)),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
 pub struct OperationSubscribers {
     #[serde(default)]
     pub subscribers: HashMap<String, SubscriberDef>,
+    /// For `return` and `submit`: add the item to the freelist once subscribers succeed.
+    /// When false only the notifications run, e.g. when another system owns the pool.
+    #[serde(default = "default_true")]
+    pub mutate_freelist: bool,
+    /// Publish this kind's freelist events (`borrowed`, `returned`, `submitted`) on this
+    /// Redis channel instead of the pool's notify channel
+    #[serde(default)]
+    pub notify_channel: Option<String>,
+    /// Subscriber payload schema to send; defaults to `LATEST_PAYLOAD_VERSION`.
+    /// 1 is `{item, params}`; 2 adds `version`, `event` and `operation_id`.
+    #[serde(default)]
+    pub payload_version: Option<u32>,
+}
+
+impl Default for OperationSubscribers {
+    fn default() -> Self {
+        Self {
+            subscribers: HashMap::new(),
+            mutate_freelist: true,
+            notify_channel: None,
+            payload_version: None,
+        }
+    }
+}
+
+impl OperationSubscribers {
+    pub fn payload_version(&self) -> u32 {
+        self.payload_version.unwrap_or(LATEST_PAYLOAD_VERSION)
+    }
+}
+
+/// Newest subscriber payload schema, sent unless a section pins `payload_version`
+pub const LATEST_PAYLOAD_VERSION: u32 = 2;
+
+pub(crate) fn default_true() -> bool {
+    true
 }
 
 #[derive(Debug, Deserialize, Clone, Default)]
@@ -25,6 +170,336 @@ pub struct AppConfig {
     pub r#return: OperationSubscribers,
     #[serde(default)]
     pub submit: OperationSubscribers,
+    /// Key required in the `X-Admin-Key` header for admin endpoints.
+    /// When unset, admin endpoints are open.
+    #[serde(default)]
+    pub admin_key: Option<String>,
+    /// Another key granting every admin endpoint, like `admin_key`
+    #[serde(default)]
+    pub admin_write_key: Option<String>,
+    /// Key granting only the GET admin endpoints, e.g. for dashboards. Once set, the
+    /// admin listings and stats require a key too.
+    #[serde(default)]
+    pub admin_read_key: Option<String>,
+    /// Reject returns and submits of items not registered in `known_items`
+    #[serde(default)]
+    pub restrict_to_known_items: bool,
+    /// Treat submit as "introduce a brand-new item": reject items already free or borrowed
+    #[serde(default)]
+    pub submit_strict: bool,
+    #[serde(default)]
+    pub request_logging: RequestLogging,
+    #[serde(default)]
+    pub retry_queue: RetryQueue,
+    /// Most requests handled at once; beyond that requests get a fast 503 `overloaded`.
+    /// Health and metrics endpoints are exempt. Unlimited when unset.
+    #[serde(default)]
+    pub max_in_flight_requests: Option<usize>,
+    /// Run borrow, return and borrow recording as Lua scripts (`EVALSHA`) for
+    /// least-privilege Redis ACLs; see the README for the commands needed
+    #[serde(default)]
+    pub redis_scripts_only: bool,
+    /// Most Redis connections open at once; requests beyond it wait for a free one.
+    /// Defaults to 16. `/borrow?wait` listens on its own connection outside the pool.
+    #[serde(default)]
+    pub redis_pool_size: Option<usize>,
+    /// Seconds a request waits for a free pooled Redis connection before failing with 503.
+    /// Defaults to 5.
+    #[serde(default)]
+    pub redis_pool_wait_secs: Option<u64>,
+    /// Put every Redis key and channel under `<key_prefix>:` so that several allocators
+    /// can share one Redis; empty (the default) keeps the plain key names
+    #[serde(default)]
+    pub key_prefix: String,
+    /// How submitted and returned items are stored in the freelist
+    #[serde(default)]
+    pub item_encoding: ItemEncoding,
+    /// Shape of error responses
+    #[serde(default)]
+    pub error_format: ErrorFormat,
+    /// Shape of operation SSE events
+    #[serde(default)]
+    pub sse_event_format: SseEventFormat,
+    /// Cut SSE events larger than this down to their event name and a `truncated` marker;
+    /// unlimited when unset
+    #[serde(default)]
+    pub sse_max_event_bytes: Option<usize>,
+    /// Two-phase returns: `/return` notifies subscribers but keeps the item borrowed until
+    /// `POST /return/confirm` (or the confirmation timeout) releases it
+    #[serde(default)]
+    pub two_phase_return: bool,
+    /// Seconds a two-phase return waits for confirmation (default 300)
+    #[serde(default)]
+    pub return_confirm_timeout_secs: Option<u64>,
+    /// What happens to a two-phase return that is not confirmed in time
+    #[serde(default)]
+    pub return_timeout_action: ReturnTimeoutAction,
+    /// What `/return` does when the item is already free or held under another token
+    #[serde(default)]
+    pub duplicate_return_policy: DuplicateReturnPolicy,
+    /// Most `/borrow?wait` requests parked at once; further waiters get 503 `wait_queue_full`.
+    /// Unlimited when unset.
+    #[serde(default)]
+    pub max_waiters: Option<usize>,
+    /// Item fields indexed per value (`freelist:<field>:<value>`) so `/borrow?filter=`
+    /// can pop a matching item directly. Only string values are indexed.
+    #[serde(default)]
+    pub indexed_fields: Vec<String>,
+    /// Most idle keep-alive connections kept per subscriber host; reqwest's default when unset
+    #[serde(default)]
+    pub subscriber_pool_max_idle_per_host: Option<usize>,
+    /// Close idle subscriber connections after this many seconds; reqwest's default when unset
+    #[serde(default)]
+    pub subscriber_pool_idle_timeout_secs: Option<u64>,
+    /// Request timeout of subscribers without their own `timeout_secs` (default 30)
+    #[serde(default)]
+    pub subscriber_timeout_secs: Option<u64>,
+    /// Most subscribers notified at once for one borrow, return or submit (default 16)
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,
+    /// List borrow tokens in `/admin/borrowed` as `token_hash` only (default true); raw
+    /// tokens are then only served by `/admin/borrowed/tokens`
+    #[serde(default)]
+    pub redact_tokens_in_admin: Option<bool>,
+    /// Report not ready on `/readyz` while an enabled must-succeed subscriber is unreachable
+    #[serde(default)]
+    pub readiness_requires_subscribers: bool,
+    /// Skip verifying the Redis server certificate of `rediss://` URLs; for self-signed
+    /// development setups only
+    #[serde(default)]
+    pub redis_insecure_skip_verify: bool,
+    /// Find the Redis master through Redis Sentinel; the Redis URL then only supplies the
+    /// scheme, credentials and database
+    #[serde(default)]
+    pub redis_sentinel: Option<crate::store::Sentinel>,
+    /// Exit at startup when Redis is unreachable (the default). When false the server
+    /// starts anyway and `/readyz` reports not ready until Redis answers.
+    #[serde(default)]
+    pub startup_requires_redis: Option<bool>,
+    /// Talk HTTP/2 to subscribers without negotiation (prior knowledge); every subscriber
+    /// must then support it
+    #[serde(default)]
+    pub http2: bool,
+    /// Serve the freelist read-only at `GET /items` without admin credentials
+    #[serde(default)]
+    pub public_item_listing: bool,
+    /// `Cache-Control` header sent with the admin UI page; defaults to `no-cache`
+    #[serde(default)]
+    pub admin_cache_control: Option<String>,
+    /// Also keep admin audit entries in the Redis list `admin_audit`, for `GET /admin/audit`;
+    /// they are only logged when unset
+    #[serde(default)]
+    pub admin_audit_redis: bool,
+    /// Record every borrow, return and submit in the Redis list `audit_log`, for
+    /// `GET /admin/audit/items`
+    #[serde(default)]
+    pub audit_log: bool,
+    /// Also accept connections on this unix domain socket (unix only). Its clients are
+    /// seen as `127.0.0.1`, so they should send `X-Owner-Id`.
+    #[serde(default)]
+    pub unix_socket: Option<PathBuf>,
+    /// Largest `count` honored by batch endpoints such as `/borrow/reserve-batch`;
+    /// larger requests are clamped. Defaults to 100.
+    #[serde(default)]
+    pub max_batch_count: Option<usize>,
+    /// Drop finished operations this many seconds after they complete (default 3600);
+    /// `0` keeps them forever. Also accepted as `operation_retention_secs`.
+    #[serde(default, alias = "operation_retention_secs")]
+    pub operation_ttl_secs: Option<u64>,
+    /// How long an `Idempotency-Key` of a return or submit is remembered (default 3600)
+    #[serde(default)]
+    pub idempotency_key_ttl_secs: Option<u64>,
+    /// Random spread applied to each operation's TTL, in percent of `operation_ttl_secs`
+    /// (default 10), so operations created together do not expire together
+    #[serde(default)]
+    pub ttl_jitter_pct: Option<u8>,
+    /// Borrow lease: an item not returned within this many seconds is reclaimed into the
+    /// freelist. `/borrow?lease=<secs>` overrides it per borrow. Borrows never expire when unset.
+    #[serde(default)]
+    pub lease_ttl_secs: Option<u64>,
+    /// Borrow tokens stop returning their item this many seconds after the borrow, even if
+    /// the item is still out; reservations are capped to it too. Unlimited when unset.
+    #[serde(default)]
+    pub token_max_lifetime_secs: Option<u64>,
+    /// Publish a `lease_expiring` message this many seconds before a batch reservation or
+    /// a borrow lease expires; no warnings when unset
+    #[serde(default)]
+    pub lease_warning_secs: Option<u64>,
+    /// Also POST each `lease_expiring` message to this URL
+    #[serde(default)]
+    pub lease_warning_url: Option<String>,
+    /// TESTING ONLY: borrow the lexicographically smallest freelist member instead of a
+    /// random one, so tests get a predictable order. Never enable in production.
+    #[serde(default)]
+    pub deterministic_borrow: bool,
+}
+
+/// Default cap on the item count of batch requests
+const DEFAULT_MAX_BATCH_COUNT: usize = 100;
+
+/// Default delay before a subscriber's first retry, in milliseconds
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 200;
+
+/// Most retries a subscriber may configure
+const MAX_SUBSCRIBER_RETRIES: u32 = 10;
+
+/// Default request timeout of subscribers, in seconds
+const DEFAULT_SUBSCRIBER_TIMEOUT_SECS: u64 = 30;
+
+/// Default number of subscribers notified at once per operation
+const DEFAULT_MAX_CONCURRENCY: usize = 16;
+
+/// Default wait for a two-phase return's confirmation, in seconds
+const DEFAULT_RETURN_CONFIRM_TIMEOUT_SECS: u64 = 300;
+
+/// Default retention of finished operations, in seconds
+const DEFAULT_OPERATION_TTL_SECS: u64 = 3600;
+
+/// Default spread of operation TTLs, in percent
+const DEFAULT_TTL_JITTER_PCT: u8 = 10;
+
+/// Outcome of a two-phase return that was not confirmed in time
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ReturnTimeoutAction {
+    /// Fail the return; the item stays borrowed by its holder
+    #[default]
+    Rollback,
+    /// Complete the return as if it had been confirmed
+    Confirm,
+}
+
+/// Handling of a return whose borrow token no longer holds the item, e.g. a second
+/// client returning an item after a force return handed it to someone else
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReturnPolicy {
+    /// Fail with 404 (item already free) or 403 (held under another token)
+    #[default]
+    Reject,
+    /// Run the return anyway: the item goes back to the freelist and any current
+    /// borrow of it is closed
+    AcceptLast,
+    /// Succeed without changing anything; the earlier return or current holder wins
+    AcceptFirst,
+}
+
+/// Form in which items are stored as freelist members
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemEncoding {
+    /// Compact JSON with sorted keys; equal items always map to the same member
+    #[default]
+    Canonical,
+    /// The exact text the client sent, returned byte-for-byte by `/borrow`.
+    /// The same item sent with different formatting becomes a separate member.
+    Raw,
+}
+
+/// Shape of error response bodies
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorFormat {
+    /// `{"err": ..., "msg": ...}` plus any machine-readable fields
+    #[default]
+    Simple,
+    /// RFC 7807 `application/problem+json`. Clients can also ask for it per request
+    /// with `Accept: application/problem+json`.
+    Problem,
+}
+
+/// Shape of the events on `/operations/<id>/events`
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SseEventFormat {
+    /// Just the event, e.g. `{"event":"completed"}`
+    #[default]
+    Compact,
+    /// The event plus the operation's `item`, `status` and `subscribers`
+    Verbose,
+}
+
+/// Request logging; bodies are logged only for JSON requests and always redacted
+#[derive(Debug, Deserialize, Clone)]
+pub struct RequestLogging {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Largest body logged, in bytes (at most 512); 0 disables body logging
+    #[serde(default = "default_log_max_body_bytes")]
+    pub max_body_bytes: usize,
+    /// Object keys whose values are replaced before logging, matched as
+    /// case-insensitive substrings of the key
+    #[serde(default = "default_redact_fields")]
+    pub redact_fields: Vec<String>,
+}
+
+impl Default for RequestLogging {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_body_bytes: default_log_max_body_bytes(),
+            redact_fields: default_redact_fields(),
+        }
+    }
+}
+
+/// Background retries of return and submit notifications that must-succeed subscribers
+/// did not accept. Queued notifications are kept in Redis; their operation stays
+/// `in_progress` until every subscriber accepts or `max_age_secs` runs out.
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetryQueue {
+    #[serde(default)]
+    pub enabled: bool,
+    /// How long after the first failure a notification is retried before its operation fails
+    #[serde(default = "default_retry_max_age_secs")]
+    pub max_age_secs: u64,
+    /// Most notifications queued at once; failures beyond that fail their operation right away
+    #[serde(default = "default_retry_max_entries")]
+    pub max_entries: usize,
+    /// Delay before the first retry, doubled for each later one up to `MAX_RETRY_DELAY_SECS`
+    #[serde(default = "default_retry_base_delay_secs")]
+    pub base_delay_secs: u64,
+}
+
+/// Longest delay between two queued retries of a notification
+pub const MAX_RETRY_DELAY_SECS: u64 = 300;
+
+impl Default for RetryQueue {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_age_secs: default_retry_max_age_secs(),
+            max_entries: default_retry_max_entries(),
+            base_delay_secs: default_retry_base_delay_secs(),
+        }
+    }
+}
+
+impl RetryQueue {
+    /// Delay before retry number `attempt` (from 1)
+    pub fn delay_secs(&self, attempt: u32) -> u64 {
+        self.base_delay_secs.saturating_mul(1 << (attempt - 1).min(16)).min(MAX_RETRY_DELAY_SECS)
+    }
+}
+
+fn default_retry_max_age_secs() -> u64 {
+    3600
+}
+
+fn default_retry_max_entries() -> usize {
+    1000
+}
+
+fn default_retry_base_delay_secs() -> u64 {
+    5
+}
+
+fn default_log_max_body_bytes() -> usize {
+    512
+}
+
+fn default_redact_fields() -> Vec<String> {
+    vec!["borrow_token".to_string(), "secret".to_string()]
 }
 
 impl AppConfig {
@@ -37,6 +512,320 @@ impl AppConfig {
         let cfg = toml::from_str::<AppConfig>(&contents)?;
         Ok(cfg)
     }
+
+    /// Check settings that parse but cannot work; returns every problem found
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        for kind in ["borrow", "return", "submit"] {
+            let section = self.subscribers_for(kind).expect("known operation kind");
+            if !(1..=LATEST_PAYLOAD_VERSION).contains(&section.payload_version()) {
+                problems.push(format!(
+                    "{}.payload_version must be between 1 and {}, got {}",
+                    kind,
+                    LATEST_PAYLOAD_VERSION,
+                    section.payload_version()
+                ));
+            }
+            if section.notify_channel.as_deref() == Some("") {
+                problems.push(format!("{}.notify_channel is empty; leave it unset for the shared channel", kind));
+            }
+            let mut names: Vec<&String> = section.subscribers.keys().collect();
+            names.sort();
+            for name in names {
+                let def = &section.subscribers[name];
+                if !is_http_url(&def.post) {
+                    problems.push(format!("{}.subscribers.{}: post is not an http(s) URL: {:?}", kind, name, def.post));
+                }
+                if def.notify_after_secs.is_some() && kind != "borrow" {
+                    problems.push(format!("{}.subscribers.{}: notify_after_secs is only supported for borrow subscribers", kind, name));
+                } else if def.notify_after_secs.is_some() && def.mustSuceed {
+                    problems.push(format!("{}.subscribers.{}: a subscriber with notify_after_secs cannot be mustSuceed", kind, name));
+                }
+                if def.timeout_secs == Some(0) {
+                    problems.push(format!("{}.subscribers.{}: timeout_secs must be at least 1", kind, name));
+                }
+                if let Err(e) = def.hmac_secret() {
+                    problems.push(format!("{}.subscribers.{}: hmac_secret: {}", kind, name, e));
+                }
+                if def.max_retries > MAX_SUBSCRIBER_RETRIES {
+                    problems.push(format!(
+                        "{}.subscribers.{}: max_retries must be at most {}, got {}",
+                        kind, name, MAX_SUBSCRIBER_RETRIES, def.max_retries
+                    ));
+                }
+            }
+        }
+        if let Some(url) = &self.lease_warning_url {
+            if !is_http_url(url) {
+                problems.push(format!("lease_warning_url is not an http(s) URL: {:?}", url));
+            }
+        }
+        for (name, key) in [
+            ("admin_key", &self.admin_key),
+            ("admin_write_key", &self.admin_write_key),
+            ("admin_read_key", &self.admin_read_key),
+        ] {
+            if key.as_deref() == Some("") {
+                problems.push(format!("{} is empty; leave it unset to disable admin auth", name));
+            }
+        }
+        if self.admin_read_key.is_some() {
+            if self.admin_key.is_none() && self.admin_write_key.is_none() {
+                problems.push("admin_read_key is set without admin_key or admin_write_key".to_string());
+            }
+            if self.admin_read_key == self.admin_key || self.admin_read_key == self.admin_write_key {
+                problems.push("admin_read_key must differ from admin_key and admin_write_key".to_string());
+            }
+        }
+        if self.ttl_jitter_pct() > 100 {
+            problems.push(format!("ttl_jitter_pct must be at most 100, got {}", self.ttl_jitter_pct()));
+        }
+        if self.lease_ttl_secs == Some(0) {
+            problems.push("lease_ttl_secs must be at least 1; leave it unset for borrows that never expire".to_string());
+        }
+        if self.token_max_lifetime_secs == Some(0) {
+            problems.push("token_max_lifetime_secs must be at least 1; leave it unset for tokens that never expire".to_string());
+        }
+        if self.max_batch_count == Some(0) {
+            problems.push("max_batch_count must be at least 1".to_string());
+        }
+        if self.subscriber_timeout_secs == Some(0) {
+            problems.push("subscriber_timeout_secs must be at least 1".to_string());
+        }
+        if self.sse_max_event_bytes == Some(0) {
+            problems.push("sse_max_event_bytes must be at least 1".to_string());
+        }
+        if self.max_concurrency == Some(0) {
+            problems.push("max_concurrency must be at least 1".to_string());
+        }
+        if self.redis_pool_size == Some(0) {
+            problems.push("redis_pool_size must be at least 1".to_string());
+        }
+        if self.redis_pool_wait_secs == Some(0) {
+            problems.push("redis_pool_wait_secs must be at least 1".to_string());
+        }
+        if self.max_in_flight_requests == Some(0) {
+            problems.push("max_in_flight_requests must be at least 1".to_string());
+        }
+        for field in &self.indexed_fields {
+            if field.is_empty() || field.contains(':') {
+                problems.push(format!("indexed_fields: {:?} must be non-empty and contain no ':'", field));
+            }
+        }
+        if self.key_prefix.ends_with(':') || self.key_prefix.contains(char::is_whitespace) {
+            problems.push(format!(
+                "key_prefix: {:?} must not contain whitespace or end with ':' (it is joined with ':')",
+                self.key_prefix
+            ));
+        }
+        if let Some(sentinel) = &self.redis_sentinel {
+            if sentinel.addresses.is_empty() {
+                problems.push("redis_sentinel.addresses must list at least one sentinel".to_string());
+            }
+            if sentinel.master_name.is_empty() {
+                problems.push("redis_sentinel.master_name must not be empty".to_string());
+            }
+        }
+        if self.retry_queue.enabled {
+            if self.retry_queue.max_entries == 0 {
+                problems.push("retry_queue.max_entries must be at least 1".to_string());
+            }
+            if self.retry_queue.base_delay_secs == 0 {
+                problems.push("retry_queue.base_delay_secs must be at least 1".to_string());
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    pub fn admin_cache_control(&self) -> &str {
+        self.admin_cache_control.as_deref().unwrap_or("no-cache")
+    }
+
+    pub fn return_confirm_timeout_secs(&self) -> u64 {
+        self.return_confirm_timeout_secs.unwrap_or(DEFAULT_RETURN_CONFIRM_TIMEOUT_SECS)
+    }
+
+    pub fn redact_tokens_in_admin(&self) -> bool {
+        self.redact_tokens_in_admin.unwrap_or(true)
+    }
+
+    /// The URL to connect to Redis with: with `redis_insecure_skip_verify`, a `rediss://`
+    /// URL gets the `#insecure` fragment that turns off certificate verification
+    pub fn redis_connection_url(&self, redis_url: &str) -> String {
+        if self.redis_insecure_skip_verify && redis_url.starts_with("rediss://") && !redis_url.contains('#') {
+            format!("{}#insecure", redis_url)
+        } else {
+            redis_url.to_string()
+        }
+    }
+
+    pub fn startup_requires_redis(&self) -> bool {
+        self.startup_requires_redis.unwrap_or(true)
+    }
+
+    /// Retention of finished operations in seconds, or `None` to keep them forever
+    pub fn operation_ttl_secs(&self) -> Option<u64> {
+        match self.operation_ttl_secs.unwrap_or(DEFAULT_OPERATION_TTL_SECS) {
+            0 => None,
+            secs => Some(secs),
+        }
+    }
+
+    pub fn idempotency_key_ttl_secs(&self) -> u64 {
+        self.idempotency_key_ttl_secs
+            .unwrap_or(crate::ops::DEFAULT_IDEMPOTENCY_KEY_TTL.as_secs())
+    }
+
+    pub fn ttl_jitter_pct(&self) -> u8 {
+        self.ttl_jitter_pct.unwrap_or(DEFAULT_TTL_JITTER_PCT)
+    }
+
+    pub fn redis_pool_size(&self) -> usize {
+        self.redis_pool_size.unwrap_or(crate::store::DEFAULT_POOL_SIZE)
+    }
+
+    pub fn redis_pool_wait(&self) -> Duration {
+        self.redis_pool_wait_secs.map(Duration::from_secs).unwrap_or(crate::store::DEFAULT_POOL_WAIT)
+    }
+
+    pub fn subscriber_timeout_secs(&self) -> u64 {
+        self.subscriber_timeout_secs.unwrap_or(DEFAULT_SUBSCRIBER_TIMEOUT_SECS)
+    }
+
+    pub fn max_concurrency(&self) -> usize {
+        self.max_concurrency.unwrap_or(DEFAULT_MAX_CONCURRENCY)
+    }
+
+    pub fn max_batch_count(&self) -> usize {
+        self.max_batch_count.unwrap_or(DEFAULT_MAX_BATCH_COUNT)
+    }
+
+    /// `key: value` lines of the effective settings for the startup log, without secrets
+    pub fn startup_summary(&self, redis_url: &str, address: &str, port: u16) -> String {
+        let order = if self.deterministic_borrow { "smallest-first (testing only)" } else { "random" };
+        let operation_ttl = match self.operation_ttl_secs() {
+            Some(secs) => format!("{}s (jitter {}%)", secs, self.ttl_jitter_pct()),
+            None => "forever".to_string(),
+        };
+        let lease_warning = self
+            .lease_warning_secs
+            .map_or("off".to_string(), |secs| format!("{}s before expiry", secs));
+        let mut lines = vec![
+            format!("listen: {}:{}", address, port),
+            format!(
+                "redis: {}{}",
+                redact_url(redis_url),
+                match (redis_url.starts_with("rediss://"), self.redis_insecure_skip_verify) {
+                    (false, _) => "",
+                    (true, false) => " (tls)",
+                    (true, true) => " (tls, certificate not verified)",
+                }
+            ),
+            format!(
+                "redis pool: {} connections, {}s wait",
+                self.redis_pool_size(),
+                self.redis_pool_wait().as_secs()
+            ),
+            format!("key prefix: {}", if self.key_prefix.is_empty() { "none" } else { &self.key_prefix }),
+            format!(
+                "redis sentinel: {}",
+                match &self.redis_sentinel {
+                    Some(sentinel) => format!("master {:?} via {}", sentinel.master_name, sentinel.addresses.join(", ")),
+                    None => "off".to_string(),
+                }
+            ),
+            format!("allocation order: {}", order),
+            format!("item encoding: {:?}", self.item_encoding).to_lowercase(),
+            format!(
+                "subscribers: borrow={} return={} submit={}",
+                self.borrow.subscribers.len(),
+                self.r#return.subscribers.len(),
+                self.submit.subscribers.len()
+            ),
+            format!("operation ttl: {}", operation_ttl),
+            format!("idempotency key ttl: {}s", self.idempotency_key_ttl_secs()),
+            format!(
+                "retry queue: {}",
+                match self.retry_queue.enabled {
+                    true => format!("up to {}s", self.retry_queue.max_age_secs),
+                    false => "off".to_string(),
+                }
+            ),
+            format!("lease warning: {}", lease_warning),
+            format!(
+                "admin auth: {}",
+                match (self.admin_auth_enabled(), self.admin_read_key.is_some()) {
+                    (false, _) => "disabled",
+                    (true, false) => "enabled",
+                    (true, true) => "enabled, with a read-only key",
+                }
+            ),
+        ];
+        if let Some(path) = &self.unix_socket {
+            lines.insert(1, format!("unix socket: {}", path.display()));
+        }
+        lines.join("\n")
+    }
+
+    /// Freelist events (`borrowed`, `returned`, `submitted`) mapped to the channel their
+    /// operation kind publishes them on, for kinds with a `notify_channel`
+    pub fn event_channels(&self) -> HashMap<String, String> {
+        [("borrowed", &self.borrow), ("returned", &self.r#return), ("submitted", &self.submit)]
+            .into_iter()
+            .filter_map(|(event, section)| Some((event.to_string(), section.notify_channel.clone()?)))
+            .collect()
+    }
+
+    /// Whether admin endpoints require a key
+    pub fn admin_auth_enabled(&self) -> bool {
+        self.admin_key.is_some() || self.admin_write_key.is_some() || self.admin_read_key.is_some()
+    }
+
+    /// Look up the subscriber section for an operation kind (`borrow`, `return`, `submit`)
+    pub fn subscribers_for(&self, kind: &str) -> Option<&OperationSubscribers> {
+        match kind {
+            "borrow" => Some(&self.borrow),
+            "return" => Some(&self.r#return),
+            "submit" => Some(&self.submit),
+            _ => None,
+        }
+    }
+}
+
+/// Replace each `${VAR}` in `value` with the environment variable `VAR`
+fn expand_env(value: &str) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        let Some(len) = rest[start..].find('}') else { break };
+        let name = &rest[start + 2..start + len];
+        let var = std::env::var(name).map_err(|_| format!("environment variable `{}` is not set", name))?;
+        out.push_str(&rest[..start]);
+        out.push_str(&var);
+        rest = &rest[start + len + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
 }
 
+fn is_http_url(url: &str) -> bool {
+    reqwest::Url::parse(url).is_ok_and(|u| matches!(u.scheme(), "http" | "https") && u.has_host())
+}
 
+/// Replace the password of a connection URL, if any, with `***`
+pub fn redact_url(url: &str) -> String {
+    match reqwest::Url::parse(url) {
+        Ok(mut parsed) if parsed.password().is_some() => {
+            let _ = parsed.set_password(Some("***"));
+            parsed.to_string()
+        }
+        Ok(_) => url.to_string(),
+        Err(_) => "<invalid url>".to_string(),
+    }
+}
@@ -7,6 +7,8 @@ use rocket_okapi::okapi::openapi3::Responses;
 use rocket_okapi::okapi::schemars::{self, Map};
 use rocket_okapi::{gen::OpenApiGenerator, response::OpenApiResponderInner, OpenApiError};
 
+use crate::config::ErrorFormat;
+
 /// Error messages returned to user
 #[derive(Debug, serde::Serialize, schemars::JsonSchema)]
 pub struct Error {
@@ -14,9 +16,15 @@ pub struct Error {
     pub err: String,
     /// The description of the error
     pub msg: Option<String>,
+    /// Additional machine-readable fields merged into the response body
+    #[serde(flatten, skip_serializing_if = "serde_json::Map::is_empty")]
+    pub context: serde_json::Map<String, serde_json::Value>,
     // HTTP Status Code returned
     #[serde(skip)]
     pub http_status_code: u16,
+    /// Seconds sent in a `Retry-After` header, if any
+    #[serde(skip)]
+    pub retry_after: Option<u64>,
 }
 
 impl Error {
@@ -25,9 +33,30 @@ impl Error {
         Self {
             err: err.to_owned(),
             msg: msg.map(|s| s.to_owned()),
+            context: serde_json::Map::new(),
             http_status_code,
+            retry_after: None,
         }
     }
+
+    /// Attach an extra field to the response body
+    pub fn with_context(mut self, key: &str, value: impl Into<serde_json::Value>) -> Self {
+        self.context.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Whether this is the 503 of a borrow that found the freelist empty, as opposed to
+    /// another 503 such as Redis being unavailable
+    pub fn is_freelist_empty(&self) -> bool {
+        self.http_status_code == 503
+            && self.msg.as_deref().is_some_and(|msg| msg.contains("No items available in the freelist"))
+    }
+
+    /// Ask the client to retry after the given number of seconds
+    pub fn with_retry_after(mut self, secs: u64) -> Self {
+        self.retry_after = Some(secs);
+        self
+    }
 }
 
 impl OpenApiResponderInner for Error {
@@ -120,15 +149,73 @@ impl std::fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
+/// Media type of RFC 7807 problem details
+const PROBLEM_JSON: (&str, &str) = ("application", "problem+json");
+
+/// Prefix of the `type` URI of problem details; the error code is appended
+const PROBLEM_TYPE_PREFIX: &str = "urn:ip-allocator:error:";
+
+/// Context fields that carry the error's machine-readable code, in order of preference
+const CODE_FIELDS: [&str; 3] = ["error", "code", "reason"];
+
+impl Error {
+    /// The machine-readable code of the error (e.g. `freelist_empty`), if it has one
+    pub fn code(&self) -> Option<&str> {
+        CODE_FIELDS.iter().find_map(|field| self.context.get(*field).and_then(|v| v.as_str()))
+    }
+
+    /// The error as RFC 7807 problem details. The code becomes the `type` URI
+    /// (`about:blank` without one) and the other context fields extension members.
+    fn to_problem(&self, instance: &str) -> serde_json::Value {
+        let mut problem = serde_json::Map::new();
+        let r#type = match self.code() {
+            Some(code) => format!("{}{}", PROBLEM_TYPE_PREFIX, code),
+            None => "about:blank".to_string(),
+        };
+        problem.insert("type".to_string(), r#type.into());
+        problem.insert("title".to_string(), self.err.clone().into());
+        problem.insert("status".to_string(), self.http_status_code.into());
+        if let Some(msg) = &self.msg {
+            problem.insert("detail".to_string(), msg.clone().into());
+        }
+        problem.insert("instance".to_string(), instance.into());
+        for (key, value) in &self.context {
+            problem.entry(key.clone()).or_insert_with(|| value.clone());
+        }
+        serde_json::Value::Object(problem)
+    }
+}
+
+/// Whether to answer with problem details: configured, or asked for in `Accept`
+fn wants_problem(req: &Request<'_>) -> bool {
+    let configured = req
+        .rocket()
+        .state::<crate::AppState>()
+        .is_some_and(|app| app.config.error_format == ErrorFormat::Problem);
+    configured
+        || req
+            .accept()
+            .is_some_and(|accept| accept.iter().any(|media| media.top() == PROBLEM_JSON.0 && media.sub() == PROBLEM_JSON.1))
+}
+
 impl<'r> Responder<'r, 'static> for Error {
-    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'static> {
         // Convert object to json
-        let body = serde_json::to_string(&self).unwrap();
-        Response::build()
+        let (body, content_type) = if wants_problem(req) {
+            let problem = self.to_problem(&req.uri().path().to_string());
+            (problem.to_string(), ContentType::new(PROBLEM_JSON.0, PROBLEM_JSON.1))
+        } else {
+            (serde_json::to_string(&self).unwrap(), ContentType::JSON)
+        };
+        let mut response = Response::build();
+        response
             .sized_body(body.len(), std::io::Cursor::new(body))
-            .header(ContentType::JSON)
-            .status(Status::new(self.http_status_code))
-            .ok()
+            .header(content_type)
+            .status(Status::new(self.http_status_code));
+        if let Some(secs) = self.retry_after {
+            response.raw_header("Retry-After", secs.to_string());
+        }
+        response.ok()
     }
 }
 
@@ -136,16 +223,8 @@ impl From<rocket::serde::json::Error<'_>> for Error {
     fn from(err: rocket::serde::json::Error) -> Self {
         use rocket::serde::json::Error::*;
         match err {
-            Io(io_error) => Error {
-                err: "IO Error".to_owned(),
-                msg: Some(io_error.to_string()),
-                http_status_code: 422,
-            },
-            Parse(_raw_data, parse_error) => Error {
-                err: "Parse Error".to_owned(),
-                msg: Some(parse_error.to_string()),
-                http_status_code: 422,
-            },
+            Io(io_error) => Error::new("IO Error", Some(&io_error.to_string()), 422),
+            Parse(_raw_data, parse_error) => Error::new("Parse Error", Some(&parse_error.to_string()), 422),
         }
     }
 }
@@ -156,21 +235,31 @@ impl From<redis::RedisError> for Error {
         // which should return 503 Service Unavailable instead of 500
         // Or if it's an invalid borrow token error which should return 403 Forbidden
         let error_msg = err.to_string();
-        let http_status_code = if error_msg.contains("No items available in the freelist") {
+        // A busy connection pool is told apart from an empty freelist by its `error` code below
+        let http_status_code = if error_msg.contains("No items available in the freelist")
+            || error_msg.contains("Redis pool exhausted")
+        {
             503 // Service Unavailable - resource temporarily exhausted
-        } else if error_msg.contains("Invalid borrow token") || error_msg.contains("borrowed by someone else") {
-            403 // Forbidden - invalid token, item is borrowed by someone else
+        } else if error_msg.contains("Invalid borrow token")
+            || error_msg.contains("borrowed by someone else")
+            || error_msg.contains("Owner mismatch")
+        {
+            403 // Forbidden - invalid token or owner, item is borrowed by someone else
+        } else if error_msg.contains("Borrow token expired") {
+            410 // Gone - the token outlived token_max_lifetime_secs
         } else if error_msg.contains("Item not found in borrowed items") {
             404 // Not Found - item was not borrowed or already returned
+        } else if error_msg.contains("Item not in reservation") || error_msg.contains("Item already borrowed") {
+            409 // Conflict - the item is not in a reservation, or held under another borrow token
         } else {
             500 // Internal Server Error - actual Redis failures
         };
 
-        Error {
-            err: "Redis Error".to_owned(),
-            msg: Some(error_msg),
-            http_status_code,
+        let error = Error::new("Redis Error", Some(&error_msg), http_status_code);
+        if error_msg.contains("Redis pool exhausted") {
+            return error.with_context("error", "redis_unavailable").with_retry_after(1);
         }
+        error
     }
 }
 
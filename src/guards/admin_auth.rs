@@ -0,0 +1,151 @@
+use rocket::{Request, http::{Method, Status}, outcome::Outcome};
+use rocket::request::{self, FromRequest};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{Object, SecurityRequirement, SecurityScheme, SecuritySchemeData};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+
+use crate::audit;
+use crate::config::AppConfig;
+use crate::error::Error;
+use crate::AppState;
+
+/// Header carrying the admin key
+pub const ADMIN_KEY_HEADER: &str = "X-Admin-Key";
+
+/// What an admin key grants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdminScope {
+    /// `admin_read_key`: GET admin endpoints only
+    Read,
+    /// `admin_key` or `admin_write_key`: every admin endpoint
+    Write,
+}
+
+/// The scope an `X-Admin-Key` value grants, if it matches a configured key
+pub fn scope_of(cfg: &AppConfig, provided: &str) -> Option<AdminScope> {
+    let matches = |key: &Option<String>| key.as_deref() == Some(provided);
+    if matches(&cfg.admin_key) || matches(&cfg.admin_write_key) {
+        Some(AdminScope::Write)
+    } else if matches(&cfg.admin_read_key) {
+        Some(AdminScope::Read)
+    } else {
+        None
+    }
+}
+
+/// Request guard for admin endpoints.
+///
+/// Succeeds when no admin key is configured, or when the `X-Admin-Key` header matches
+/// one. Fails with 401 otherwise, and with 403 when the read key is used for anything
+/// but a GET.
+pub struct AdminAuth {
+    /// Audit id of the key used; `None` when no admin key is configured
+    key_id: Option<String>,
+    scope: AdminScope,
+}
+
+impl AdminAuth {
+    /// Hashed id of the admin key that authorized the request, for the audit log
+    pub fn key_id(&self) -> Option<&str> {
+        self.key_id.as_deref()
+    }
+
+    /// 403 unless the request was authorized with a write key, for GET endpoints that
+    /// hand out more than a read-only dashboard should see
+    pub fn require_write(&self) -> Result<(), Error> {
+        match self.scope {
+            AdminScope::Write => Ok(()),
+            AdminScope::Read => Err(Error::new("Forbidden", Some("This endpoint requires the admin write key"), 403)),
+        }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminAuth {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let app = match request.rocket().state::<AppState>() {
+            Some(app) => app,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+
+        if !app.config.admin_auth_enabled() {
+            return Outcome::Success(AdminAuth { key_id: None, scope: AdminScope::Write });
+        }
+        let Some(provided) = request.headers().get_one(ADMIN_KEY_HEADER) else {
+            return Outcome::Error((Status::Unauthorized, ()));
+        };
+        match scope_of(&app.config, provided) {
+            None => Outcome::Error((Status::Unauthorized, ())),
+            Some(AdminScope::Read) if !matches!(request.method(), Method::Get | Method::Head) => {
+                Outcome::Error((Status::Forbidden, ()))
+            }
+            Some(scope) => Outcome::Success(AdminAuth { key_id: Some(audit::key_id(provided)), scope }),
+        }
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for AdminAuth {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(admin_key_security())
+    }
+}
+
+/// Request guard for the admin listings and stats.
+///
+/// Open unless `admin_read_key` is configured; then either admin key is required,
+/// as with [`AdminAuth`].
+pub struct AdminReadAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminReadAuth {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let app = match request.rocket().state::<AppState>() {
+            Some(app) => app,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+        if app.config.admin_read_key.is_none() {
+            return Outcome::Success(AdminReadAuth);
+        }
+        match request.headers().get_one(ADMIN_KEY_HEADER).and_then(|provided| scope_of(&app.config, provided)) {
+            Some(_) => Outcome::Success(AdminReadAuth),
+            None => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for AdminReadAuth {
+    fn from_request_input(
+        _gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        Ok(admin_key_security())
+    }
+}
+
+/// The `X-Admin-Key` security scheme of the OpenAPI spec
+fn admin_key_security() -> RequestHeaderInput {
+    let scheme = SecurityScheme {
+        description: Some(
+            "Admin key, required when `admin_key`, `admin_write_key` or `admin_read_key` is configured. \
+             The read key only grants GET endpoints."
+                .to_owned(),
+        ),
+        data: SecuritySchemeData::ApiKey {
+            name: ADMIN_KEY_HEADER.to_owned(),
+            location: "header".to_owned(),
+        },
+        extensions: Object::default(),
+    };
+    let mut requirement = SecurityRequirement::new();
+    requirement.insert("AdminKey".to_owned(), Vec::new());
+    RequestHeaderInput::Security("AdminKey".to_owned(), scheme, requirement)
+}
@@ -0,0 +1,53 @@
+use rocket::request::{self, FromRequest};
+use rocket::{outcome::Outcome, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{Object, Parameter, ParameterValue};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+
+/// Header carrying a borrow token, so it stays out of URLs and access logs
+pub const BORROW_TOKEN_HEADER: &str = "X-Borrow-Token";
+
+/// The request's `X-Borrow-Token`, if it sent a non-empty one
+pub struct BorrowTokenHeader(pub Option<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for BorrowTokenHeader {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let token = request
+            .headers()
+            .get_one(BORROW_TOKEN_HEADER)
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .map(str::to_string);
+        Outcome::Success(BorrowTokenHeader(token))
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for BorrowTokenHeader {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        let schema = gen.json_schema::<String>();
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: BORROW_TOKEN_HEADER.to_owned(),
+            location: "header".to_owned(),
+            description: Some("Borrow token, instead of the `borrow_token` query parameter.".to_owned()),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema,
+                example: None,
+                examples: None,
+            },
+            extensions: Object::default(),
+        }))
+    }
+}
@@ -2,6 +2,7 @@ use rocket::{Request, http::Status, outcome::Outcome};
 use rocket::request::{self, FromRequest};
 
 /// Custom request guard type that checks for the presence of a 'Debug' header.
+#[allow(dead_code)]
 pub struct DebugHeader;
 
 #[rocket::async_trait]
@@ -0,0 +1,65 @@
+use rocket::request::{self, FromRequest};
+use rocket::{outcome::Outcome, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{Object, Parameter, ParameterValue};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+
+/// Header a client sets to make retries of a return or submit safe
+pub const IDEMPOTENCY_KEY_HEADER: &str = "Idempotency-Key";
+
+/// The request's `Idempotency-Key`, if it sent a non-empty one
+pub struct IdempotencyKey(pub Option<String>);
+
+impl IdempotencyKey {
+    /// The key within the operations of one endpoint, so the same key used for a return
+    /// and a submit names two operations
+    pub fn scoped(&self, endpoint: &str) -> Option<String> {
+        self.0.as_ref().map(|key| format!("{}:{}", endpoint, key))
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IdempotencyKey {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let key = request
+            .headers()
+            .get_one(IDEMPOTENCY_KEY_HEADER)
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+            .map(str::to_string);
+        Outcome::Success(IdempotencyKey(key))
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for IdempotencyKey {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        let schema = gen.json_schema::<String>();
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: IDEMPOTENCY_KEY_HEADER.to_owned(),
+            location: "header".to_owned(),
+            description: Some(
+                "Client-chosen key; a repeated request with the same key gets the first request's operation \
+                 instead of starting another."
+                    .to_owned(),
+            ),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema,
+                example: None,
+                examples: None,
+            },
+            extensions: Object::default(),
+        }))
+    }
+}
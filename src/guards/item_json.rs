@@ -0,0 +1,77 @@
+use rocket::data::{self, Data, FromData, ToByteUnit};
+use rocket::http::Status;
+use rocket::outcome::Outcome;
+use rocket::serde::json::Json;
+use rocket::Request;
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::RequestBody;
+use rocket_okapi::okapi::schemars::JsonSchema;
+use rocket_okapi::request::OpenApiFromData;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::value::RawValue;
+
+use crate::error::Error;
+
+/// A JSON body like `Json<T>` that also keeps the exact text of its `item` field,
+/// so the item can be stored byte-for-byte as the client sent it.
+pub struct ItemJson<T> {
+    pub body: T,
+    pub raw_item: String,
+}
+
+impl<T> std::ops::Deref for ItemJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.body
+    }
+}
+
+/// Parse failure detail left in the request cache for the `422` catcher
+struct JsonParseError(Option<String>);
+
+#[derive(Deserialize)]
+struct RawItem<'a> {
+    #[serde(borrow)]
+    item: &'a RawValue,
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for ItemJson<T> {
+    type Error = String;
+
+    async fn from_data(req: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = req.limits().get("json").unwrap_or(1.mebibytes());
+        let text = match data.open(limit).into_string().await {
+            Ok(text) if text.is_complete() => text.into_inner(),
+            Ok(_) => return Outcome::Error((Status::PayloadTooLarge, "body too large".to_string())),
+            Err(e) => return Outcome::Error((Status::BadRequest, e.to_string())),
+        };
+
+        let parsed = serde_json::from_str::<T>(&text)
+            .and_then(|body| serde_json::from_str::<RawItem>(&text).map(|raw| (body, raw.item.get().to_string())));
+        match parsed {
+            Ok((body, raw_item)) => Outcome::Success(ItemJson { body, raw_item }),
+            Err(e) => {
+                let detail = e.to_string();
+                req.local_cache(|| JsonParseError(Some(detail.clone())));
+                Outcome::Error((Status::UnprocessableEntity, detail))
+            }
+        }
+    }
+}
+
+impl<'r, T: JsonSchema + DeserializeOwned> OpenApiFromData<'r> for ItemJson<T> {
+    fn request_body(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<RequestBody> {
+        <Json<T> as OpenApiFromData>::request_body(gen)
+    }
+}
+
+/// Answers unparsable JSON bodies with the crate's error shape instead of Rocket's default page
+#[catch(422)]
+pub fn invalid_json(req: &Request) -> Error {
+    let JsonParseError(detail) = req.local_cache(|| JsonParseError(None));
+    Error::new("Invalid JSON", Some(detail.as_deref().unwrap_or("Request body could not be parsed")), 422)
+        .with_context("code", "invalid_json")
+}
@@ -0,0 +1,21 @@
+use rocket::request::{self, FromRequest};
+use rocket::{outcome::Outcome, Request};
+
+/// Header a reconnecting `EventSource` sends with the id of the last event it received
+pub const LAST_EVENT_ID_HEADER: &str = "Last-Event-ID";
+
+/// The `Last-Event-ID` of a reconnecting SSE client; `None` when absent or not a number
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let id = request
+            .headers()
+            .get_one(LAST_EVENT_ID_HEADER)
+            .and_then(|id| id.trim().parse().ok());
+        Outcome::Success(LastEventId(id))
+    }
+}
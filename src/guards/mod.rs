@@ -1 +1,10 @@
-pub mod debug_header;
\ No newline at end of file
+pub mod debug_header;
+pub mod admin_auth;
+pub mod owner_id;
+pub mod item_json;
+pub mod prefer;
+pub mod skip_subscribers;
+pub mod stream_token;
+pub mod last_event_id;
+pub mod idempotency_key;
+pub mod borrow_token;
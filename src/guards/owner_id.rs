@@ -0,0 +1,70 @@
+use rocket::{Request, http::Status, outcome::Outcome};
+use rocket::request::{self, FromRequest};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{Object, Parameter, ParameterValue};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+
+use crate::store::Owner;
+
+/// Header identifying the client on whose behalf a request is made
+pub const OWNER_ID_HEADER: &str = "X-Owner-Id";
+
+/// Identity of the requester: the `X-Owner-Id` header, or the client IP when absent.
+/// Forwards when neither is available, so handlers usually take `Option<OwnerId>`.
+pub struct OwnerId {
+    /// The owner id, or the client IP
+    pub id: String,
+    /// Whether `id` came from `X-Owner-Id` rather than the client IP
+    pub explicit: bool,
+}
+
+impl OwnerId {
+    /// The owner a borrow is recorded for
+    pub fn as_owner(&self) -> Owner<'_> {
+        Owner { id: &self.id, explicit: self.explicit }
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for OwnerId {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        match request.headers().get_one(OWNER_ID_HEADER) {
+            Some(owner) if !owner.trim().is_empty() => {
+                Outcome::Success(OwnerId { id: owner.trim().to_string(), explicit: true })
+            }
+            _ => match request.client_ip() {
+                Some(ip) => Outcome::Success(OwnerId { id: ip.to_string(), explicit: false }),
+                None => Outcome::Forward(Status::BadRequest),
+            },
+        }
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for OwnerId {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        let schema = gen.json_schema::<String>();
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: OWNER_ID_HEADER.to_owned(),
+            location: "header".to_owned(),
+            description: Some("Identifies the requester; defaults to the client IP.".to_owned()),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema,
+                example: None,
+                examples: None,
+            },
+            extensions: Object::default(),
+        }))
+    }
+}
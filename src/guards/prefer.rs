@@ -0,0 +1,110 @@
+use rocket::http::Header;
+use rocket::request::{self, FromRequest};
+use rocket::response::{self, Responder};
+use rocket::{outcome::Outcome, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{Object, Parameter, ParameterValue, Responses};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+use rocket_okapi::response::OpenApiResponderInner;
+
+/// Request header carrying client preferences (RFC 7240)
+pub const PREFER_HEADER: &str = "Prefer";
+/// Response header echoing the preference that was honored
+pub const PREFERENCE_APPLIED_HEADER: &str = "Preference-Applied";
+
+/// Whether an operation should finish before the response is sent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RespondMode {
+    Async,
+    Sync,
+}
+
+impl RespondMode {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RespondMode::Async => "respond-async",
+            RespondMode::Sync => "respond-sync",
+        }
+    }
+}
+
+/// The `respond-async`/`respond-sync` preference of a request, if it stated one.
+/// Other preferences in the header are ignored; the first response mode wins.
+pub struct Prefer(pub Option<RespondMode>);
+
+impl Prefer {
+    pub fn mode(&self) -> RespondMode {
+        self.0.unwrap_or(RespondMode::Async)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Prefer {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let mode = request
+            .headers()
+            .get(PREFER_HEADER)
+            .flat_map(|value| value.split(','))
+            .filter_map(|pref| pref.split(';').next())
+            .find_map(|token| match token.trim().to_ascii_lowercase().as_str() {
+                "respond-async" => Some(RespondMode::Async),
+                "respond-sync" => Some(RespondMode::Sync),
+                _ => None,
+            });
+        Outcome::Success(Prefer(mode))
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for Prefer {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        let schema = gen.json_schema::<String>();
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: PREFER_HEADER.to_owned(),
+            location: "header".to_owned(),
+            description: Some(
+                "`respond-sync` waits for the operation to finish; `respond-async` (the default) returns at once."
+                    .to_owned(),
+            ),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema,
+                example: None,
+                examples: None,
+            },
+            extensions: Object::default(),
+        }))
+    }
+}
+
+/// Wraps a response, adding `Preference-Applied` when a response mode was requested
+pub struct PreferenceApplied<R> {
+    pub inner: R,
+    pub applied: Option<RespondMode>,
+}
+
+impl<'r, 'o: 'r, R: Responder<'r, 'o>> Responder<'r, 'o> for PreferenceApplied<R> {
+    fn respond_to(self, req: &'r Request<'_>) -> response::Result<'o> {
+        let mut response = self.inner.respond_to(req)?;
+        if let Some(mode) = self.applied {
+            response.set_header(Header::new(PREFERENCE_APPLIED_HEADER, mode.as_str()));
+        }
+        Ok(response)
+    }
+}
+
+impl<R: OpenApiResponderInner> OpenApiResponderInner for PreferenceApplied<R> {
+    fn responses(gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        R::responses(gen)
+    }
+}
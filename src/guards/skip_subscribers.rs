@@ -0,0 +1,67 @@
+use rocket::request::{self, FromRequest};
+use rocket::{outcome::Outcome, Request};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{Object, Parameter, ParameterValue};
+use rocket_okapi::request::{OpenApiFromRequest, RequestHeaderInput};
+
+use crate::guards::admin_auth::{scope_of, AdminScope, ADMIN_KEY_HEADER};
+use crate::AppState;
+
+/// Header naming subscribers to skip for a single operation
+pub const SKIP_SUBSCRIBERS_HEADER: &str = "X-Skip-Subscribers";
+
+/// Subscribers an operator asked to skip via `X-Skip-Subscribers: name1,name2`.
+///
+/// Only honored when the request also carries an admin write key; otherwise (or with no
+/// admin key configured) the header is ignored and the list is empty.
+pub struct SkipSubscribers(pub Vec<String>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for SkipSubscribers {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let Some(header) = request.headers().get_one(SKIP_SUBSCRIBERS_HEADER) else {
+            return Outcome::Success(SkipSubscribers(Vec::new()));
+        };
+        let authorized = match (request.rocket().state::<AppState>(), request.headers().get_one(ADMIN_KEY_HEADER)) {
+            (Some(app), Some(provided)) => scope_of(&app.config, provided) == Some(AdminScope::Write),
+            _ => false,
+        };
+        if !authorized {
+            return Outcome::Success(SkipSubscribers(Vec::new()));
+        }
+        let names = header.split(',').map(str::trim).filter(|name| !name.is_empty()).map(str::to_string).collect();
+        Outcome::Success(SkipSubscribers(names))
+    }
+}
+
+impl<'r> OpenApiFromRequest<'r> for SkipSubscribers {
+    fn from_request_input(
+        gen: &mut OpenApiGenerator,
+        _name: String,
+        _required: bool,
+    ) -> rocket_okapi::Result<RequestHeaderInput> {
+        let schema = gen.json_schema::<String>();
+        Ok(RequestHeaderInput::Parameter(Parameter {
+            name: SKIP_SUBSCRIBERS_HEADER.to_owned(),
+            location: "header".to_owned(),
+            description: Some(
+                "Comma-separated subscribers to skip for this operation; ignored without a valid `X-Admin-Key`."
+                    .to_owned(),
+            ),
+            required: false,
+            deprecated: false,
+            allow_empty_value: false,
+            value: ParameterValue::Schema {
+                style: None,
+                explode: None,
+                allow_reserved: false,
+                schema,
+                example: None,
+                examples: None,
+            },
+            extensions: Object::default(),
+        }))
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use rocket::request::{self, FromRequest};
+use rocket::{http::Status, outcome::Outcome, Request};
+
+use crate::store::now_secs;
+use crate::AppState;
+
+/// How long a stream token authorizes `/admin/events`, in seconds
+pub const STREAM_TOKEN_TTL_SECS: u64 = 60;
+
+/// Short-lived tokens for `/admin/events`, which browsers open with `EventSource` and so
+/// cannot send `X-Admin-Key`. Kept in memory only.
+#[derive(Default)]
+pub struct StreamTokens {
+    /// Token to the unix timestamp (seconds) it expires at
+    tokens: Mutex<HashMap<String, u64>>,
+}
+
+impl StreamTokens {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Issue a token valid for `STREAM_TOKEN_TTL_SECS`; returns it with its expiry
+    pub fn issue(&self) -> (String, u64) {
+        let now = now_secs();
+        let token = uuid::Uuid::new_v4().to_string();
+        let expires_at = now + STREAM_TOKEN_TTL_SECS;
+        let mut tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens.retain(|_, expires_at| *expires_at > now);
+        tokens.insert(token.clone(), expires_at);
+        (token, expires_at)
+    }
+
+    pub fn is_valid(&self, token: &str) -> bool {
+        let tokens = self.tokens.lock().unwrap_or_else(|e| e.into_inner());
+        tokens.get(token).is_some_and(|expires_at| *expires_at > now_secs())
+    }
+}
+
+/// Request guard for `/admin/events`.
+///
+/// Succeeds when no admin key is configured, or when the `token` query parameter is an
+/// unexpired token from `POST /admin/events/token`. Fails with 401 otherwise.
+pub struct StreamToken;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for StreamToken {
+    type Error = ();
+
+    async fn from_request(request: &'r Request<'_>) -> request::Outcome<Self, Self::Error> {
+        let app = match request.rocket().state::<AppState>() {
+            Some(app) => app,
+            None => return Outcome::Error((Status::InternalServerError, ())),
+        };
+        if !app.config.admin_auth_enabled() {
+            return Outcome::Success(StreamToken);
+        }
+        match request.query_value::<&str>("token") {
+            Some(Ok(token)) if app.stream_tokens.is_valid(token) => Outcome::Success(StreamToken),
+            _ => Outcome::Error((Status::Unauthorized, ())),
+        }
+    }
+}
@@ -3,35 +3,110 @@ use rocket::State;
 use rocket_okapi::openapi;
 use rocket_okapi::okapi::schemars::JsonSchema;
 use rocket::serde::{Deserialize, Serialize};
-use rocket::response::content::RawHtml;
+use rocket::http::{ContentType, Header};
+use rocket::response::{self, Responder, Response};
+use rocket::futures::StreamExt;
+use rocket::response::stream::{Event, EventStream};
+use rocket::tokio::time::interval;
+use rocket::Request;
+use std::collections::{BTreeMap, HashSet};
+use std::io::Cursor;
+use std::time::Duration;
 use tokio::sync::Mutex;
+use ipnetwork::IpNetwork;
 use serde_json::Value;
+use sha2::{Digest, Sha256};
 
+use crate::audit::{self, AuditEntry, ItemAuditEntry};
+use crate::config::redact_url;
 use crate::error::{Error, OResult};
+use crate::guards::admin_auth::{AdminAuth, AdminReadAuth};
+use crate::guards::stream_token::StreamToken;
+use crate::handlers::ip::{announce, pool_store};
 use crate::AppState;
-use crate::store::Store;
+use crate::ops::{cap_event, Operation, OperationKind, OperationStatus, OutcomeCounts};
+use crate::store::{is_valid_pool_name, now_secs, Store};
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct ItemsList {
     items: Vec<Value>,
+    /// Number of items in this page
     count: usize,
+    /// Number of items in the freelist
+    total: usize,
+    /// Items skipped before this page
+    offset: usize,
+    /// Page size requested; null when the page runs to the end
+    limit: Option<usize>,
+    /// Items of this page submitted with `available_until`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    expiring: Vec<ExpiringItem>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ExpiringItem {
+    item: Value,
+    /// Unix timestamp (seconds) at which the item leaves the freelist
+    available_until: u64,
+    /// Seconds of availability left
+    remaining_secs: u64,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct BorrowedItem {
     item: Value,
-    borrow_token: String,
+    /// The raw borrow token; left out when `redact_tokens_in_admin` is set, except on
+    /// `/admin/borrowed/tokens`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    borrow_token: Option<String>,
+    /// First 8 hex digits of the token's SHA-256, to correlate borrows without the token
+    token_hash: String,
+    borrow_id: Option<String>,
+    /// Unix timestamp (seconds) at which the item was borrowed
+    borrowed_at: Option<u64>,
+    /// Owner id (or client IP) of the borrowing request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<String>,
+    /// Unix timestamp (seconds) at which the borrow lease runs out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lease_expires_at: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct BorrowedItemsList {
     borrowed: Vec<BorrowedItem>,
+    /// Number of borrowed items in this page
     count: usize,
+    /// Number of borrowed items
+    total: usize,
+    /// Borrowed items skipped before this page
+    offset: usize,
+    /// Page size requested; null when the page runs to the end
+    limit: Option<usize>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct DeleteItemInput {
     item: Value,
+    /// Keep the item's metadata instead of dropping it with the item
+    #[serde(default)]
+    keep_metadata: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SetItemMetadataInput {
+    item: Value,
+    /// JSON object replacing the item's metadata
+    metadata: Value,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ItemMetadataOutput {
+    item: Value,
+    /// The item's metadata, or null if none was set
+    metadata: Option<Value>,
+    /// `free`, `borrowed` or `unknown` (not in the freelist nor borrowed)
+    state: String,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -39,24 +114,158 @@ pub struct ForceReturnInput {
     item: Value,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ForceReturnByTokenInput {
+    borrow_token: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ForceReturnByTokenOutput {
+    success: bool,
+    /// The item that was returned to the freelist
+    item: Value,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+    /// Number of entries in this page
+    count: usize,
+    /// Number of entries kept
+    total: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ItemAuditLog {
+    entries: Vec<ItemAuditEntry>,
+    /// Number of entries in this page
+    count: usize,
+    /// Number of entries kept
+    total: usize,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct SuccessResponse {
     success: bool,
     message: String,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeleteOperationOutput {
+    success: bool,
+    message: String,
+    /// Whether an operation was removed; false only for idempotent deletes of missing ids
+    deleted: bool,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct OperationsList {
     operations: Vec<OperationDetail>,
+    /// Number of operations in this page
     count: usize,
+    /// Number of operations matching the filter
+    total: usize,
+    /// Operations skipped before this page
+    offset: usize,
+    /// Page size requested; null when the page runs to the end
+    limit: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ImportOperationsOutput {
+    /// Operations loaded
+    imported: usize,
+    /// Of those, how many replaced an operation with the same id
+    replaced: usize,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct OperationDetail {
     id: String,
+    /// `return` or `submit`
+    kind: String,
     item: Value,
     status: String,
     message: Option<String>,
+    borrow_id: Option<String>,
+    initiated_by: Option<String>,
+    /// Unix timestamp (seconds) at which the operation was created
+    created_at: u64,
+    /// Unix timestamp (seconds) at which the operation finished, if it has
+    completed_at: Option<u64>,
+}
+
+impl From<Operation> for OperationDetail {
+    fn from(op: Operation) -> Self {
+        Self {
+            id: op.id,
+            kind: op.kind.as_str().to_string(),
+            item: op.item,
+            status: format!("{:?}", op.status),
+            message: op.message,
+            borrow_id: op.borrow_id,
+            initiated_by: op.initiated_by,
+            created_at: op.created_at,
+            completed_at: op.completed_at,
+        }
+    }
+}
+
+/// Terminal status an operator can force an operation into
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum ResolvedStatus {
+    Succeeded,
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ResolveOperationInput {
+    status: ResolvedStatus,
+    message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct OutcomeSummary {
+    succeeded: usize,
+    failed: usize,
+    total: usize,
+    /// `succeeded / total`, or null when nothing finished in the window
+    success_ratio: Option<f64>,
+}
+
+impl From<OutcomeCounts> for OutcomeSummary {
+    fn from(counts: OutcomeCounts) -> Self {
+        let total = counts.succeeded + counts.failed;
+        Self {
+            succeeded: counts.succeeded,
+            failed: counts.failed,
+            total,
+            success_ratio: (total > 0).then(|| counts.succeeded as f64 / total as f64),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct OperationsSummary {
+    window_secs: u64,
+    r#return: OutcomeSummary,
+    submit: OutcomeSummary,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WaiterInfo {
+    /// Owner id (or client IP) of the waiting request
+    owner: Option<String>,
+    /// Unix timestamp (seconds) at which the request started waiting
+    waiting_since: u64,
+    waited_secs: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct WaitersList {
+    waiters: Vec<WaiterInfo>,
+    count: usize,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
@@ -65,143 +274,793 @@ pub struct StatsResponse {
     borrowed_count: usize,
     pending_operations: usize,
     failed_operations: usize,
+    /// Return and submit notifications waiting in the retry queue
+    retry_queue_depth: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SetItemsInput {
+    /// The exact set of items the freelist should hold
+    items: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SetItemsOutput {
+    added: usize,
+    removed: usize,
+    /// Items already free or currently borrowed
+    unchanged: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SeedCidrInput {
+    /// Range to seed, e.g. `10.0.0.0/24`; at most `MAX_SEED_ADDRESSES` addresses
+    cidr: String,
+    /// Leave out the network address (IPv4 ranges of /30 and wider only; default true)
+    #[serde(default = "crate::config::default_true")]
+    skip_network: bool,
+    /// Leave out the broadcast address (IPv4 ranges of /30 and wider only; default true)
+    #[serde(default = "crate::config::default_true")]
+    skip_broadcast: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SeedCidrOutput {
+    /// Usable addresses in the range
+    addresses: usize,
+    /// Of those, how many were added; the rest were already free or are borrowed
+    added: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct KnownItemsInput {
+    items: Vec<Value>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct KnownItemsOutput {
+    /// Number of items that were not registered before
+    added: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SubscriberStatus {
+    kind: String,
+    name: String,
+    post: String,
+    must_succeed: bool,
+    r#async: bool,
+    enabled: bool,
+    /// The subscriber's `when` condition, if it only fires for some items
+    when: Option<String>,
+    /// Grace period of a deferred borrow subscriber
+    notify_after_secs: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct SubscribersList {
+    subscribers: Vec<SubscriberStatus>,
+    count: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MigrateInput {
+    /// Redis to copy the state to, e.g. `redis://new-redis:6379/`
+    target_redis_url: String,
+    /// Only count what would be copied
+    #[serde(default)]
+    dry_run: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct MigrateOutput {
+    dry_run: bool,
+    /// Members copied (or, in a dry run, to copy) per Redis key, for non-empty keys
+    copied: BTreeMap<String, usize>,
+    /// Members copied in total
+    total: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct RebuildIndexesOutput {
+    /// Index entries written, one per free item and indexed field it has
+    indexed: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TransferItemInput {
+    item: Value,
+    /// Source pool; `default` is the main freelist
+    from: String,
+    /// Destination pool
+    to: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct TransferItemOutput {
+    item: Value,
+    from: String,
+    to: String,
+    transferred: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DeadLetterReplayOutput {
+    /// Notifications delivered and removed from the dead-letter queue
+    replayed: usize,
+    /// Notifications whose subscriber failed again; they stay queued
+    failed: usize,
+    /// Notifications left queued because their subscriber is disabled
+    skipped: usize,
+    /// Notifications discarded because their subscriber is no longer configured
+    dropped: usize,
+    /// Notifications still queued after this pass
+    remaining: usize,
+}
+
+/// How long the admin bulk-operation lock survives a holder that never releases it
+const ADMIN_LOCK_TTL: Duration = Duration::from_secs(30);
+
+/// Run a destructive bulk operation under the Redis-backed admin lock, so operators on
+/// different replicas cannot interleave. Answers 423 `admin_locked` while another holds it.
+async fn with_admin_lock<T>(store: &Store, op: impl std::future::Future<Output = Result<T, Error>>) -> Result<T, Error> {
+    let token = uuid::Uuid::new_v4().to_string();
+    if !store.try_admin_lock(&token, ADMIN_LOCK_TTL).await.map_err(Error::from)? {
+        return Err(Error::new("Locked", Some("Another admin bulk operation is in progress"), 423)
+            .with_context("error", "admin_locked")
+            .with_retry_after(1));
+    }
+    let result = op.await;
+    let _ = store.release_admin_lock(&token).await;
+    result
+}
+
+/// Skip `offset` entries and keep at most `limit` of the rest
+pub(crate) fn page<T>(entries: impl IntoIterator<Item = T>, offset: Option<usize>, limit: Option<usize>) -> Vec<T> {
+    entries
+        .into_iter()
+        .skip(offset.unwrap_or(0))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect()
 }
 
 /// List all items in the freelist (Admin)
+///
+/// Optional `offset` and `limit` select a page, echoed in the response; `count` is the
+/// page length and `total` the size of the freelist. The freelist is walked with `SSCAN`
+/// only as far as the page reaches, so pages follow its order rather than any sort.
+/// Items of the page that are only available until
+/// a deadline are also listed under `expiring` with their remaining time.
+/// Optional `pool` lists that named pool instead of the default one.
 #[openapi(tag = "Admin")]
-#[get("/admin/items")]
-pub async fn list_items(store: &State<Mutex<Store>>) -> OResult<ItemsList> {
-    let store = store.lock().await;
-    match store.list_all_items() {
+#[get("/admin/items?<offset>&<limit>&<pool>")]
+pub async fn list_items(
+    _reader: AdminReadAuth,
+    store: &State<Mutex<Store>>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    pool: Option<String>,
+) -> OResult<ItemsList> {
+    let store = pool_store(store, pool.as_deref()).await?;
+    let total = store.free_count().await.map_err(Error::from)?;
+    let deadlines = store.available_until().await.map_err(Error::from)?;
+    match store.scan_items(offset.unwrap_or(0), limit).await {
         Ok(items) => {
             let count = items.len();
-            Ok(Json(ItemsList { items, count }))
+            let now = now_secs();
+            let expiring = items
+                .iter()
+                .filter_map(|item| {
+                    let until = *deadlines.get(&item.to_string())?;
+                    Some(ExpiringItem {
+                        item: item.clone(),
+                        available_until: until,
+                        remaining_secs: until.saturating_sub(now),
+                    })
+                })
+                .collect();
+            let offset = offset.unwrap_or(0);
+            Ok(Json(ItemsList { items, count, total, offset, limit, expiring }))
         }
         Err(e) => Err(Error::from(e)),
     }
 }
 
 /// List all borrowed items (Admin)
+///
+/// Optional `offset` and `limit` select a page, echoed in the response; `count` is the
+/// page length and `total` the number of borrowed items. Optional `pool` lists that named pool instead
+/// of the default one. With `redact_tokens_in_admin` (the default) each borrow token is
+/// only listed as `token_hash`.
 #[openapi(tag = "Admin")]
-#[get("/admin/borrowed")]
-pub async fn list_borrowed(store: &State<Mutex<Store>>) -> OResult<BorrowedItemsList> {
+#[get("/admin/borrowed?<offset>&<limit>&<pool>")]
+pub async fn list_borrowed(
+    _reader: AdminReadAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    pool: Option<String>,
+) -> OResult<BorrowedItemsList> {
+    let reveal = !app.config.redact_tokens_in_admin();
+    borrowed_listing(store, offset, limit, pool.as_deref(), None, reveal).await
+}
+
+/// List the items one owner has borrowed (Admin)
+///
+/// Like `/admin/borrowed`, restricted to borrows made by `owner_id` (the `X-Owner-Id` of
+/// the borrow, or the client IP without it), e.g. to clean up after a decommissioned
+/// service. `total` is the number of borrowed items that owner holds; an owner holding
+/// nothing gets an empty list. Borrows made before owners were recorded are never listed.
+#[openapi(tag = "Admin")]
+#[get("/admin/borrowed/by-owner/<owner_id>?<offset>&<limit>&<pool>")]
+pub async fn list_borrowed_by_owner(
+    _reader: AdminReadAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    owner_id: &str,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    pool: Option<String>,
+) -> OResult<BorrowedItemsList> {
+    let reveal = !app.config.redact_tokens_in_admin();
+    borrowed_listing(store, offset, limit, pool.as_deref(), Some(owner_id), reveal).await
+}
+
+/// List borrowed items with their raw borrow tokens (Admin)
+///
+/// Like `/admin/borrowed`, but always includes `borrow_token`, which lets the caller
+/// return the items. Requires an admin write key and is recorded in the audit log.
+#[openapi(tag = "Admin")]
+#[get("/admin/borrowed/tokens?<offset>&<limit>&<pool>")]
+pub async fn list_borrow_tokens(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    pool: Option<String>,
+) -> OResult<BorrowedItemsList> {
+    admin.require_write()?;
+    let listing = borrowed_listing(store, offset, limit, pool.as_deref(), None, true).await?;
     let store = store.lock().await;
-    match store.list_borrowed_items() {
-        Ok(borrowed_tuples) => {
-            let borrowed: Vec<BorrowedItem> = borrowed_tuples
+    audit::record(app, &store, &admin, "reveal_borrow_tokens", Vec::new(), None).await;
+    Ok(listing)
+}
+
+/// Identify a borrow token without revealing it: the first 8 hex digits of its SHA-256
+fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes()).iter().take(4).map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn borrowed_listing(
+    store: &Mutex<Store>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+    pool: Option<&str>,
+    owner: Option<&str>,
+    reveal: bool,
+) -> OResult<BorrowedItemsList> {
+    let store = pool_store(store, pool).await?;
+    match store.list_borrowed_items().await {
+        Ok(mut borrowed_tuples) => {
+            let total = match owner {
+                Some(owner) => {
+                    borrowed_tuples
+                        .retain(|(_, _, record)| record.as_ref().and_then(|r| r.owner.as_deref()) == Some(owner));
+                    borrowed_tuples.len()
+                }
+                None => store.borrowed_count().await.map_err(Error::from)?,
+            };
+            let borrowed: Vec<BorrowedItem> = page(borrowed_tuples, offset, limit)
                 .into_iter()
-                .map(|(item, borrow_token)| BorrowedItem { item, borrow_token })
+                .map(|(item, borrow_token, record)| BorrowedItem {
+                    item,
+                    token_hash: token_hash(&borrow_token),
+                    borrow_token: reveal.then_some(borrow_token),
+                    borrow_id: record.as_ref().map(|r| r.borrow_id.clone()),
+                    borrowed_at: record.as_ref().map(|r| r.borrowed_at),
+                    owner: record.as_ref().and_then(|r| r.owner.clone()),
+                    lease_expires_at: record.and_then(|r| r.lease_expires_at),
+                })
                 .collect();
             let count = borrowed.len();
-            Ok(Json(BorrowedItemsList { borrowed, count }))
+            let offset = offset.unwrap_or(0);
+            Ok(Json(BorrowedItemsList { borrowed, count, total, offset, limit }))
         }
         Err(e) => Err(Error::from(e)),
     }
 }
 
+/// Replace the freelist with an exact set of items (Admin)
+///
+/// Adds missing items and removes extra ones in one atomic step. Borrowed items are
+/// never touched: listing one keeps it out of the freelist, omitting one does not revoke it.
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
+#[openapi(tag = "Admin")]
+#[put("/admin/items", data = "<input>")]
+pub async fn set_items(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<SetItemsInput>,
+) -> OResult<SetItemsOutput> {
+    let store = store.lock().await;
+    with_admin_lock(&store, async {
+        match store.set_freelist(&input.items).await {
+            Ok((added, removed, unchanged)) => {
+                audit::record(app, &store, &admin, "set_items", input.items.clone(), None).await;
+                Ok(Json(SetItemsOutput { added, removed, unchanged }))
+            }
+            Err(e) => Err(Error::from(e)),
+        }
+    })
+    .await
+}
+
 /// Delete an item from the freelist (Admin)
+///
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
 #[openapi(tag = "Admin")]
 #[delete("/admin/items", data = "<input>")]
 pub async fn delete_item(
+    admin: AdminAuth,
     store: &State<Mutex<Store>>,
+    app: &State<AppState>,
     input: Json<DeleteItemInput>,
 ) -> OResult<SuccessResponse> {
-    let store = store.lock().await;
-    match store.delete_item(&input.item) {
-        Ok(deleted) => {
-            if deleted {
-                Ok(Json(SuccessResponse {
-                    success: true,
-                    message: "Item deleted successfully".to_string(),
-                }))
-            } else {
-                Err(Error::new("Not Found", Some("Item not found in freelist"), 404))
+    let store = store.lock().await.clone();
+    with_admin_lock(&store, async {
+        match store.delete_item(&input.item).await {
+            Ok(deleted) => {
+                if deleted {
+                    audit::record(app, &store, &admin, "delete_item", vec![input.item.clone()], None).await;
+                    announce(&store, "deleted", &input.item).await;
+                    drop_metadata(&store, &input).await;
+                    Ok(Json(SuccessResponse {
+                        success: true,
+                        message: "Item deleted successfully".to_string(),
+                    }))
+                } else {
+                    Err(Error::new("Not Found", Some("Item not found in freelist"), 404))
+                }
             }
+            Err(e) => Err(Error::from(e)),
         }
-        Err(e) => Err(Error::from(e)),
+    })
+    .await
+}
+
+/// Drop a deleted item's metadata unless the caller asked to keep it
+async fn drop_metadata(store: &Store, input: &DeleteItemInput) {
+    if input.keep_metadata {
+        return;
+    }
+    if let Err(e) = store.delete_item_metadata(&input.item).await {
+        log::warn!("failed to drop metadata of deleted item {}: {}", input.item, e);
+    }
+}
+
+/// Set an item's metadata (Admin)
+///
+/// `metadata` must be a JSON object and replaces any earlier metadata. Metadata is
+/// independent of the item's state: it survives borrows and returns, and works for
+/// items not currently in the pool.
+#[openapi(tag = "Admin")]
+#[put("/admin/items/metadata", data = "<input>")]
+pub async fn set_item_metadata(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<SetItemMetadataInput>,
+) -> OResult<SuccessResponse> {
+    if !input.metadata.is_object() {
+        return Err(Error::new("Invalid metadata", Some("metadata must be a JSON object"), 400));
+    }
+    let store = store.lock().await;
+    store.set_item_metadata(&input.item, &input.metadata).await.map_err(Error::from)?;
+    audit::record(app, &store, &admin, "set_item_metadata", vec![input.item.clone()], None).await;
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: "Item metadata set".to_string(),
+    }))
+}
+
+/// Inspect an item (Admin)
+///
+/// `item` is the item as a JSON string. Answers its metadata (null if none was set)
+/// and whether it is `free`, `borrowed` or `unknown`.
+#[openapi(tag = "Admin")]
+#[get("/admin/items/metadata?<item>")]
+pub async fn get_item_metadata(
+    _admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    item: String,
+) -> OResult<ItemMetadataOutput> {
+    let item: Value = serde_json::from_str(&item)
+        .map_err(|e| Error::new("Invalid item", Some(&format!("Failed to parse item JSON: {}", e)), 400))?;
+
+    let store = store.lock().await;
+    let metadata = store.item_metadata(&item).await.map_err(Error::from)?;
+    let state = match store.item_location(&item).await.map_err(Error::from)? {
+        (true, _) => "free",
+        (_, true) => "borrowed",
+        _ => "unknown",
+    };
+    Ok(Json(ItemMetadataOutput { item, metadata, state: state.to_string() }))
+}
+
+/// Copy the live state to another Redis (Admin)
+///
+/// Copies every pool's freelist and borrowed items (with their records, tokens and
+/// leases), known items, item metadata and time-boxed deadlines to `target_redis_url`,
+/// adding to what it holds. The server keeps using its current Redis; cutting over is a
+/// restart with the new URL. With `dry_run` only the counts are reported. Fails with
+/// 400 if the target is the current Redis and 502 if the copy fails.
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
+#[openapi(tag = "Admin")]
+#[post("/admin/migrate", data = "<input>")]
+pub async fn migrate(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<MigrateInput>,
+) -> OResult<MigrateOutput> {
+    let store = store.lock().await.clone();
+    if input.target_redis_url == store.redis_url() {
+        return Err(Error::new("Invalid target", Some("target_redis_url is the Redis this server uses"), 400));
     }
+    with_admin_lock(&store, async {
+        let copied = store
+            .migrate_to(&input.target_redis_url, input.dry_run)
+            .await
+            .map_err(|e| Error::new("Migration failed", Some(&e.to_string()), 502))?;
+        if !input.dry_run {
+            let detail = format!("to {}", redact_url(&input.target_redis_url));
+            audit::record(app, &store, &admin, "migrate", Vec::new(), Some(detail)).await;
+        }
+        let total = copied.values().sum();
+        Ok(Json(MigrateOutput { dry_run: input.dry_run, copied, total }))
+    })
+    .await
+}
+
+/// Rebuild the per-field freelist indexes (Admin)
+///
+/// The freelist is authoritative; this drops every index set and indexes the current
+/// freelist again, e.g. after changing `indexed_fields` or editing Redis by hand.
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
+#[openapi(tag = "Admin")]
+#[post("/admin/indexes/rebuild")]
+pub async fn rebuild_indexes(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+) -> OResult<RebuildIndexesOutput> {
+    let store = store.lock().await.clone();
+    with_admin_lock(&store, async {
+        let indexed = store.rebuild_indexes().await.map_err(Error::from)?;
+        audit::record(app, &store, &admin, "rebuild_indexes", Vec::new(), None).await;
+        Ok(Json(RebuildIndexesOutput { indexed }))
+    })
+    .await
+}
+
+/// Move a free item from one pool to another (Admin)
+///
+/// The item leaves `from` and joins `to` in one atomic step, so it is never in both
+/// or neither. Answers 409 if the item is not free in `from`.
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
+#[openapi(tag = "Admin")]
+#[post("/admin/pools/transfer", data = "<input>")]
+pub async fn transfer_item(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<TransferItemInput>,
+) -> OResult<TransferItemOutput> {
+    let input = input.into_inner();
+    for pool in [&input.from, &input.to] {
+        if !is_valid_pool_name(pool) {
+            return Err(Error::new("Bad Request", Some("Invalid pool name"), 400)
+                .with_context("pool", pool.as_str()));
+        }
+    }
+    if input.from == input.to {
+        return Err(Error::new("Bad Request", Some("Source and destination pools are the same"), 400));
+    }
+
+    let store = store.lock().await.clone();
+    with_admin_lock(&store, async {
+        if !store.transfer_item(&input.item, &input.from, &input.to).await.map_err(Error::from)? {
+            return Err(Error::new("Conflict", Some("Item is not available in the source pool"), 409)
+                .with_context("error", "not_available"));
+        }
+        let detail = format!("{} -> {}", input.from, input.to);
+        audit::record(app, &store, &admin, "transfer_item", vec![input.item.clone()], Some(detail)).await;
+        Ok(Json(TransferItemOutput {
+            item: input.item,
+            from: input.from,
+            to: input.to,
+            transferred: true,
+        }))
+    })
+    .await
 }
 
 /// Force return a borrowed item (Admin)
 #[openapi(tag = "Admin")]
 #[post("/admin/force-return", data = "<input>")]
 pub async fn force_return(
+    admin: AdminAuth,
     store: &State<Mutex<Store>>,
+    app: &State<AppState>,
     input: Json<ForceReturnInput>,
 ) -> OResult<SuccessResponse> {
     let store = store.lock().await;
-    match store.force_return(&input.item) {
-        Ok(_) => Ok(Json(SuccessResponse {
-            success: true,
-            message: "Item force-returned to freelist".to_string(),
-        })),
+    match store.force_return(&input.item).await {
+        Ok(_) => {
+            app.subs.cancel_deferred_borrows_of(&input.item).await;
+            audit::record(app, &store, &admin, "force_return", vec![input.item.clone()], None).await;
+            announce(&store, "returned", &input.item).await;
+            Ok(Json(SuccessResponse {
+                success: true,
+                message: "Item force-returned to freelist".to_string(),
+            }))
+        }
+        Err(e) => Err(Error::from(e)),
+    }
+}
+
+/// Force return a borrowed item identified only by its borrow token (Admin)
+///
+/// Looks the item up via the token index, clears its borrow record and puts it back
+/// in the freelist in one atomic step. Returns 404 if the token is not an active borrow.
+#[openapi(tag = "Admin")]
+#[post("/admin/force-return/token", data = "<input>")]
+pub async fn force_return_by_token(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<ForceReturnByTokenInput>,
+) -> OResult<ForceReturnByTokenOutput> {
+    let store = store.lock().await;
+    match store.force_return_by_token(&input.borrow_token).await {
+        Ok(Some(item)) => {
+            app.subs.cancel_deferred_borrow(&input.borrow_token).await;
+            audit::record(app, &store, &admin, "force_return_by_token", vec![item.clone()], None).await;
+            announce(&store, "returned", &item).await;
+            Ok(Json(ForceReturnByTokenOutput { success: true, item }))
+        }
+        Ok(None) => Err(Error::new("Not Found", Some("Borrow token is not active"), 404)),
         Err(e) => Err(Error::from(e)),
     }
 }
 
 /// Delete a borrowed item without returning it to the freelist (Admin)
+///
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
 #[openapi(tag = "Admin")]
 #[delete("/admin/borrowed", data = "<input>")]
 pub async fn delete_borrowed_item(
+    admin: AdminAuth,
     store: &State<Mutex<Store>>,
+    app: &State<AppState>,
     input: Json<DeleteItemInput>,
 ) -> OResult<SuccessResponse> {
-    let store = store.lock().await;
-    match store.delete_borrowed_item(&input.item) {
-        Ok(deleted) => {
-            if deleted {
-                Ok(Json(SuccessResponse {
-                    success: true,
-                    message: "Borrowed item deleted successfully".to_string(),
-                }))
-            } else {
-                Err(Error::new("Not Found", Some("Item not found in borrowed items"), 404))
+    let store = store.lock().await.clone();
+    with_admin_lock(&store, async {
+        match store.delete_borrowed_item(&input.item).await {
+            Ok(deleted) => {
+                if deleted {
+                    audit::record(app, &store, &admin, "delete_borrowed_item", vec![input.item.clone()], None).await;
+                    drop_metadata(&store, &input).await;
+                    Ok(Json(SuccessResponse {
+                        success: true,
+                        message: "Borrowed item deleted successfully".to_string(),
+                    }))
+                } else {
+                    Err(Error::new("Not Found", Some("Item not found in borrowed items"), 404))
+                }
             }
+            Err(e) => Err(Error::from(e)),
         }
-        Err(e) => Err(Error::from(e)),
-    }
+    })
+    .await
 }
 
 /// List all operations (Admin)
+///
+/// Optional query parameter `initiated_by` restricts the listing to operations
+/// started by that owner id (or client IP). Optional `offset` and `limit` select a
+/// page, echoed in the response; `count` is the page length and `total` the number of
+/// matching operations.
 #[openapi(tag = "Admin")]
-#[get("/admin/operations")]
-pub async fn list_operations(app: &State<AppState>) -> OResult<OperationsList> {
-    let ops = app.ops.get_all().await;
-    let operations: Vec<OperationDetail> = ops
+#[get("/admin/operations?<initiated_by>&<offset>&<limit>")]
+pub async fn list_operations(
+    _reader: AdminReadAuth,
+    app: &State<AppState>,
+    initiated_by: Option<String>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> OResult<OperationsList> {
+    let mut ops: Vec<_> = app
+        .ops
+        .get_all()
+        .await
         .into_iter()
-        .map(|op| OperationDetail {
-            id: op.id,
-            item: op.item,
-            status: format!("{:?}", op.status),
-            message: op.message,
-        })
+        .filter(|op| initiated_by.is_none() || op.initiated_by == initiated_by)
+        .collect();
+    // Oldest first, so pages stay stable as new operations arrive
+    ops.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+    let total = ops.len();
+    let operations: Vec<OperationDetail> = page(ops, offset, limit)
+        .into_iter()
+        .map(OperationDetail::from)
         .collect();
     let count = operations.len();
-    Ok(Json(OperationsList { operations, count }))
+    let offset = offset.unwrap_or(0);
+    Ok(Json(OperationsList { operations, count, total, offset, limit }))
+}
+
+/// Export all operations (Admin)
+///
+/// Returns the full operation records, oldest first, in the shape
+/// `/admin/operations/import` accepts, e.g. to reproduce an issue in another environment.
+#[openapi(tag = "Admin")]
+#[get("/admin/operations/export")]
+pub async fn export_operations(_admin: AdminAuth, app: &State<AppState>) -> OResult<Vec<Operation>> {
+    let mut ops = app.ops.get_all().await;
+    ops.sort_by(|a, b| (a.created_at, &a.id).cmp(&(b.created_at, &b.id)));
+    Ok(Json(ops))
+}
+
+/// Import operations from an export (Admin)
+///
+/// Operations are loaded as they are, replacing any with the same id. Their items are not
+/// touched and no workflow is resumed. The whole import is rejected with 400 if an id is
+/// empty or appears twice. Operations are kept in memory only, so an import does not
+/// survive a restart.
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
+#[openapi(tag = "Admin")]
+#[post("/admin/operations/import", data = "<input>")]
+pub async fn import_operations(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<Vec<Operation>>,
+) -> OResult<ImportOperationsOutput> {
+    let ops = input.into_inner();
+    let mut seen = HashSet::new();
+    for op in &ops {
+        if op.id.is_empty() {
+            return Err(Error::new("Invalid operations", Some("Operation id must not be empty"), 400));
+        }
+        if !seen.insert(op.id.as_str()) {
+            return Err(Error::new("Invalid operations", Some("Duplicate operation id"), 400)
+                .with_context("id", op.id.clone()));
+        }
+    }
+    let imported = ops.len();
+    let store = store.lock().await.clone();
+    with_admin_lock(&store, async {
+        let replaced = app.ops.import(ops).await;
+        let detail = format!("{} operations", imported);
+        audit::record(app, &store, &admin, "import_operations", Vec::new(), Some(detail)).await;
+        Ok(Json(ImportOperationsOutput { imported, replaced }))
+    })
+    .await
+}
+
+/// Summarize operation outcomes over a recent window (Admin)
+///
+/// Counts return and submit operations that reached a terminal state within the last
+/// `window_secs` seconds (default 3600), with the success ratio of each kind.
+#[openapi(tag = "Admin")]
+#[get("/admin/operations/summary?<window_secs>")]
+pub async fn operations_summary(
+    _admin: AdminAuth,
+    app: &State<AppState>,
+    window_secs: Option<u64>,
+) -> OResult<OperationsSummary> {
+    let window_secs = window_secs.unwrap_or(3600);
+    let since = now_secs().saturating_sub(window_secs);
+    Ok(Json(OperationsSummary {
+        window_secs,
+        r#return: app.ops.outcomes_since(OperationKind::Return, since).await.into(),
+        submit: app.ops.outcomes_since(OperationKind::Submit, since).await.into(),
+    }))
 }
 
 /// Delete an operation (Admin)
+///
+/// A missing operation is a 404, unless `idempotent=true` is passed: then the request
+/// succeeds with `deleted: false`, so cleanup scripts can tolerate already-deleted ids.
 #[openapi(tag = "Admin")]
-#[delete("/admin/operations/<id>")]
-pub async fn delete_operation(app: &State<AppState>, id: &str) -> OResult<SuccessResponse> {
+#[delete("/admin/operations/<id>?<idempotent>")]
+pub async fn delete_operation(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    id: &str,
+    idempotent: Option<bool>,
+) -> OResult<DeleteOperationOutput> {
     if app.ops.delete(id).await {
-        Ok(Json(SuccessResponse {
+        let store = store.lock().await.clone();
+        audit::record(app, &store, &admin, "delete_operation", Vec::new(), Some(id.to_string())).await;
+        Ok(Json(DeleteOperationOutput {
             success: true,
             message: "Operation deleted".to_string(),
+            deleted: true,
+        }))
+    } else if idempotent.unwrap_or(false) {
+        Ok(Json(DeleteOperationOutput {
+            success: true,
+            message: "Operation not found".to_string(),
+            deleted: false,
         }))
     } else {
         Err(Error::new("Not Found", Some("Operation not found"), 404))
     }
 }
 
+/// Force an operation into a terminal state (Admin)
+///
+/// For manual cleanup of operations stuck in `pending` or `in_progress`, e.g. after their
+/// background task died. The matching SSE event (`completed` or `failed`) is emitted.
+/// Resolving an operation that already finished is a 409 unless `force=true`.
+#[openapi(tag = "Admin")]
+#[post("/admin/operations/<id>/resolve?<force>", data = "<input>")]
+pub async fn resolve_operation(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    id: &str,
+    force: Option<bool>,
+    input: Json<ResolveOperationInput>,
+) -> OResult<OperationDetail> {
+    let Some(op) = app.ops.get(id).await else {
+        return Err(Error::new("Not Found", Some("Operation not found"), 404));
+    };
+    if op.status.is_terminal() && !force.unwrap_or(false) {
+        return Err(Error::new("Conflict", Some("Operation already finished"), 409)
+            .with_context("status", format!("{:?}", op.status).to_lowercase()));
+    }
+
+    let input = input.into_inner();
+    let (status, event) = match input.status {
+        ResolvedStatus::Succeeded => (OperationStatus::Succeeded, serde_json::json!({"event":"completed"})),
+        ResolvedStatus::Failed => (
+            OperationStatus::Failed,
+            serde_json::json!({"event":"failed","reason":input.message.clone().unwrap_or_default()}),
+        ),
+    };
+    log::warn!("operation {} manually resolved as {:?}", id, status);
+    let detail = format!("{} as {}", id, format!("{:?}", status).to_lowercase());
+    app.ops.update_message(id, input.message).await;
+    app.ops.set_status(id, status).await;
+    app.sse.notify(id, event.to_string()).await;
+    let store = store.lock().await.clone();
+    audit::record(app, &store, &admin, "resolve_operation", vec![op.item.clone()], Some(detail)).await;
+
+    match app.ops.get(id).await {
+        Some(op) => Ok(Json(OperationDetail::from(op))),
+        None => Err(Error::new("Not Found", Some("Operation not found"), 404)),
+    }
+}
+
 /// Get system statistics (Admin)
 #[openapi(tag = "Admin")]
 #[get("/admin/stats")]
-pub async fn get_stats(store: &State<Mutex<Store>>, app: &State<AppState>) -> OResult<StatsResponse> {
+pub async fn get_stats(
+    _reader: AdminReadAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+) -> OResult<StatsResponse> {
     let store = store.lock().await;
 
-    let free_count = store.list_all_items().unwrap_or_default().len();
-    let borrowed_count = store.list_borrowed_items().unwrap_or_default().len();
+    let free_count = store.list_all_items().await.unwrap_or_default().len();
+    let borrowed_count = store.list_borrowed_items().await.unwrap_or_default().len();
 
     let ops = app.ops.get_all().await;
     let pending_operations = ops.iter().filter(|op| {
@@ -210,23 +1069,374 @@ pub async fn get_stats(store: &State<Mutex<Store>>, app: &State<AppState>) -> OR
     let failed_operations = ops.iter().filter(|op| {
         matches!(op.status, crate::ops::OperationStatus::Failed)
     }).count();
+    let retry_queue_depth = store.retry_queue_depth().await.unwrap_or_default();
 
     Ok(Json(StatsResponse {
         free_count,
         borrowed_count,
         pending_operations,
         failed_operations,
+        retry_queue_depth,
     }))
 }
 
+/// Register items as legitimate members of this pool (Admin)
+///
+/// Used together with `restrict_to_known_items` to make the pool a closed set.
+/// Optional `pool=<name>` registers them in that named pool instead of the default one;
+/// an item known in one pool is still unknown to the others.
+#[openapi(tag = "Admin")]
+#[post("/admin/known-items?<pool>", data = "<input>")]
+pub async fn add_known_items(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<KnownItemsInput>,
+    pool: Option<String>,
+) -> OResult<KnownItemsOutput> {
+    let store = pool_store(store, pool.as_deref()).await?;
+    match store.add_known_items(&input.items).await {
+        Ok(added) => {
+            audit::record(app, &store, &admin, "add_known_items", input.items.clone(), None).await;
+            Ok(Json(KnownItemsOutput { added }))
+        }
+        Err(e) => Err(Error::from(e)),
+    }
+}
+
+/// Most addresses `/admin/seed-cidr` expands a range to: a /16 of IPv4 or a /112 of IPv6
+const MAX_SEED_ADDRESSES: u32 = 1 << 16;
+
+/// Seed the freelist from a CIDR range (Admin)
+///
+/// Adds every usable address of `cidr` as `{"ip": "<address>"}`, e.g. `{"cidr":
+/// "10.0.0.0/24"}` adds `10.0.0.1` to `10.0.0.254`. The network and broadcast addresses
+/// are left out unless `skip_network`/`skip_broadcast` are false; /31 and /32 ranges have
+/// neither, and IPv6 has no broadcast address. Addresses already free or currently borrowed
+/// are left alone, so seeding is safe to repeat. With `restrict_to_known_items` the
+/// addresses are also registered as known items of the seeded pool. Ranges of more than 65536 addresses (wider
+/// than a /16 of IPv4) are refused with 400, as is an address with host bits set.
+/// Optional `pool=<name>` seeds that named pool instead of the default one.
+/// Runs under the admin bulk-operation lock: 423 `admin_locked` while another holds it.
+#[openapi(tag = "Admin")]
+#[post("/admin/seed-cidr?<pool>", data = "<input>")]
+pub async fn seed_cidr(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<SeedCidrInput>,
+    pool: Option<String>,
+) -> OResult<SeedCidrOutput> {
+    let items = cidr_items(&input)?;
+    let store = pool_store(store, pool.as_deref()).await?;
+    with_admin_lock(&store, async {
+        let added = store.seed_items(&items).await.map_err(Error::from)?;
+        if app.config.restrict_to_known_items {
+            store.add_known_items(&items).await.map_err(Error::from)?;
+        }
+        audit::record(app, &store, &admin, "seed_cidr", Vec::new(), Some(input.cidr.clone())).await;
+        Ok(Json(SeedCidrOutput { addresses: items.len(), added }))
+    })
+    .await
+}
+
+/// The usable addresses of a seed range, as items
+fn cidr_items(input: &SeedCidrInput) -> Result<Vec<Value>, Error> {
+    let network: IpNetwork = input
+        .cidr
+        .parse()
+        .map_err(|e: ipnetwork::IpNetworkError| Error::new("Invalid CIDR", Some(&e.to_string()), 400))?;
+    if network.ip() != network.network() {
+        return Err(Error::new("Invalid CIDR", Some("Address has host bits set"), 400)
+            .with_context("network", format!("{}/{}", network.network(), network.prefix())));
+    }
+    let max_prefix = if network.is_ipv4() { 32 } else { 128 };
+    let host_bits = u32::from(max_prefix - network.prefix());
+    if host_bits > MAX_SEED_ADDRESSES.trailing_zeros() {
+        return Err(Error::new("Invalid CIDR", Some("Range is too large"), 400)
+            .with_context("max_addresses", MAX_SEED_ADDRESSES));
+    }
+    // /31 and /32 (RFC 3021) have no network or broadcast address to skip
+    let (skip_network, skip_broadcast) = match network {
+        IpNetwork::V4(_) if host_bits >= 2 => (input.skip_network, input.skip_broadcast),
+        IpNetwork::V6(_) if host_bits >= 2 => (input.skip_network, false),
+        _ => (false, false),
+    };
+    Ok(network
+        .iter()
+        .filter(|ip| !(skip_network && *ip == network.network()))
+        .filter(|ip| !(skip_broadcast && *ip == network.broadcast()))
+        .map(|ip| serde_json::json!({ "ip": ip.to_string() }))
+        .collect())
+}
+
+/// List the clients parked in `/borrow?wait` (Admin)
+///
+/// Longest waiting first, to help diagnose pool starvation.
+#[openapi(tag = "Admin")]
+#[get("/admin/waiters")]
+pub async fn list_waiters(_admin: AdminAuth, app: &State<AppState>) -> OResult<WaitersList> {
+    let now = now_secs();
+    let waiters: Vec<WaiterInfo> = app
+        .waiters
+        .snapshot()
+        .into_iter()
+        .map(|w| WaiterInfo {
+            owner: w.owner,
+            waiting_since: w.since,
+            waited_secs: now.saturating_sub(w.since),
+        })
+        .collect();
+    let count = waiters.len();
+    Ok(Json(WaitersList { waiters, count }))
+}
+
+/// List configured subscribers and whether they are enabled (Admin)
+#[openapi(tag = "Admin")]
+#[get("/admin/subscribers")]
+pub async fn list_subscribers(_admin: AdminAuth, app: &State<AppState>) -> OResult<SubscribersList> {
+    let mut subscribers = Vec::new();
+    for kind in ["borrow", "return", "submit"] {
+        let section = app.config.subscribers_for(kind).expect("known operation kind");
+        for (name, def) in &section.subscribers {
+            subscribers.push(SubscriberStatus {
+                kind: kind.to_string(),
+                name: name.clone(),
+                post: def.post.clone(),
+                must_succeed: def.mustSuceed,
+                r#async: def.r#async,
+                enabled: app.subs.is_enabled(kind, name).await,
+                when: def.when.as_ref().map(|when| when.to_string()),
+                notify_after_secs: def.notify_after_secs,
+            });
+        }
+    }
+    subscribers.sort_by(|a, b| (&a.kind, &a.name).cmp(&(&b.kind, &b.name)));
+    let count = subscribers.len();
+    Ok(Json(SubscribersList { subscribers, count }))
+}
+
+/// Disable a subscriber until re-enabled or restart (Admin)
+///
+/// Disabled subscribers are skipped during dispatch, including must-succeed ones.
+/// The override lives in memory only and is meant as an emergency lever.
+#[openapi(tag = "Admin")]
+#[post("/admin/subscribers/<kind>/<name>/disable")]
+pub async fn disable_subscriber(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    kind: &str,
+    name: &str,
+) -> OResult<SuccessResponse> {
+    set_subscriber_enabled(app, store, &admin, kind, name, false).await
+}
+
+/// Re-enable a previously disabled subscriber (Admin)
+#[openapi(tag = "Admin")]
+#[post("/admin/subscribers/<kind>/<name>/enable")]
+pub async fn enable_subscriber(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    kind: &str,
+    name: &str,
+) -> OResult<SuccessResponse> {
+    set_subscriber_enabled(app, store, &admin, kind, name, true).await
+}
+
+async fn set_subscriber_enabled(
+    app: &AppState,
+    store: &Mutex<Store>,
+    admin: &AdminAuth,
+    kind: &str,
+    name: &str,
+    enabled: bool,
+) -> OResult<SuccessResponse> {
+    let known = app
+        .config
+        .subscribers_for(kind)
+        .map(|section| section.subscribers.contains_key(name))
+        .unwrap_or(false);
+    if !known {
+        return Err(Error::new("Not Found", Some("Subscriber not found"), 404));
+    }
+
+    app.subs.set_enabled(kind, name, enabled).await;
+    let state = if enabled { "enabled" } else { "disabled" };
+    let store = store.lock().await.clone();
+    let action = if enabled { "enable_subscriber" } else { "disable_subscriber" };
+    audit::record(app, &store, admin, action, Vec::new(), Some(format!("{}/{}", kind, name))).await;
+    Ok(Json(SuccessResponse {
+        success: true,
+        message: format!("Subscriber `{}` ({}) {}", name, kind, state),
+    }))
+}
+
+/// Re-send dead-lettered subscriber notifications (Admin)
+///
+/// Optional subscribers that fail a notification get it dead-lettered in memory.
+/// This replays up to `limit` of them (all by default), oldest first.
+#[openapi(tag = "Admin")]
+#[post("/admin/deadletter/replay?<limit>")]
+pub async fn replay_dead_letters(
+    admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    limit: Option<usize>,
+) -> OResult<DeadLetterReplayOutput> {
+    let summary = app.subs.replay_dead_letters(&app.config, limit.unwrap_or(usize::MAX)).await;
+    let store = store.lock().await.clone();
+    let detail = format!("{} replayed", summary.replayed);
+    audit::record(app, &store, &admin, "replay_dead_letters", Vec::new(), Some(detail)).await;
+    Ok(Json(DeadLetterReplayOutput {
+        replayed: summary.replayed,
+        failed: summary.failed,
+        skipped: summary.skipped,
+        dropped: summary.dropped,
+        remaining: summary.remaining,
+    }))
+}
+
+/// Review the admin audit log (Admin)
+///
+/// Admin mutations, newest first, as kept in Redis with `admin_audit_redis`; empty when
+/// it is off (entries are then only logged). Each entry names the action, the items it
+/// touched and the hashed id of the admin key used, never the key itself. Optional
+/// `offset` and `limit` select a page; `total` is the number of entries kept.
+#[openapi(tag = "Admin")]
+#[get("/admin/audit?<offset>&<limit>")]
+pub async fn list_audit(
+    _admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> OResult<AuditLog> {
+    let store = store.lock().await.clone();
+    let (entries, total) = store
+        .audit_entries(offset.unwrap_or(0), limit.unwrap_or(usize::MAX))
+        .await
+        .map_err(Error::from)?;
+    let entries: Vec<AuditEntry> = entries.iter().filter_map(|entry| serde_json::from_str(entry).ok()).collect();
+    let count = entries.len();
+    Ok(Json(AuditLog { entries, count, total }))
+}
+
+/// Review the borrow, return and submit audit log (Admin)
+///
+/// Borrows, returns and submits, newest first, as kept in Redis with `audit_log`; empty
+/// when it is off. Each entry has the `action`, the `item`, the `owner` (owner id or client
+/// IP) and, for returns and submits, the `operation_id`. Optional `offset` and `limit`
+/// select a page; `total` is the number of entries kept.
+#[openapi(tag = "Admin")]
+#[get("/admin/audit/items?<offset>&<limit>")]
+pub async fn list_item_audit(
+    _admin: AdminAuth,
+    store: &State<Mutex<Store>>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> OResult<ItemAuditLog> {
+    let store = store.lock().await.clone();
+    let (entries, total) = store
+        .item_audit_entries(offset.unwrap_or(0), limit.unwrap_or(usize::MAX))
+        .await
+        .map_err(Error::from)?;
+    let entries: Vec<ItemAuditEntry> = entries.iter().filter_map(|entry| serde_json::from_str(entry).ok()).collect();
+    let count = entries.len();
+    Ok(Json(ItemAuditLog { entries, count, total }))
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct StreamTokenOutput {
+    /// Pass as `/admin/events?token=<token>`
+    token: String,
+    /// Unix timestamp (seconds) after which the token no longer opens a stream
+    expires_at: u64,
+}
+
+/// Issue a token for the admin event stream (Admin)
+///
+/// `EventSource` cannot send `X-Admin-Key`, so `/admin/events` takes this token as its
+/// `token` query parameter instead. Tokens are valid for 60 seconds; a stream opened in
+/// time stays open past that.
+#[openapi(tag = "Admin")]
+#[post("/admin/events/token")]
+pub async fn issue_stream_token(_admin: AdminAuth, app: &State<AppState>) -> OResult<StreamTokenOutput> {
+    let (token, expires_at) = app.stream_tokens.issue();
+    Ok(Json(StreamTokenOutput { token, expires_at }))
+}
+
+/// Stream freelist mutations as Server-Sent Events (Admin)
+///
+/// Each `borrowed`, `returned`, `submitted` or `deleted` event carries the item and the
+/// pool's `free` and `borrowed` counts after it. Needs `token=<stream token>` when an
+/// admin key is configured; `pool=<name>` watches a named pool instead of the default.
+#[get("/admin/events?<pool>")]
+pub async fn stream_admin_events(
+    _token: StreamToken,
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    pool: Option<String>,
+) -> Result<EventStream![], Error> {
+    let max_bytes = app.config.sse_max_event_bytes;
+    let store = pool_store(store, pool.as_deref()).await?;
+    let mut messages = store.subscribe_freelist_events().await.map_err(Error::from)?.into_on_message();
+    Ok(EventStream! {
+        let mut ping = interval(Duration::from_secs(15));
+        loop {
+            tokio::select! {
+                message = messages.next() => {
+                    let Some(message) = message else { break };
+                    let payload: String = message.get_payload().unwrap_or_default();
+                    if store.is_own_freelist_event(&payload) {
+                        yield Event::data(cap_event(payload, max_bytes));
+                    }
+                }
+                _ = ping.tick() => yield Event::data("ping"),
+            }
+        }
+    })
+}
+
+/// Cache lifetime of the admin favicon; it only changes with a new release
+const FAVICON_CACHE_CONTROL: &str = "public, max-age=604800";
+
+/// An embedded static file served with an explicit content type and cache policy
+pub struct StaticAsset {
+    content_type: ContentType,
+    cache_control: String,
+    body: &'static str,
+}
+
+impl<'r> Responder<'r, 'static> for StaticAsset {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        Response::build()
+            .header(self.content_type)
+            .header(Header::new("Cache-Control", self.cache_control))
+            .sized_body(self.body.len(), Cursor::new(self.body))
+            .ok()
+    }
+}
+
 /// Serve the admin UI HTML page
+///
+/// Sent as `text/html; charset=utf-8` with the configured `admin_cache_control`
+/// (default `no-cache`, so UI updates show up immediately).
 #[get("/admin")]
-pub async fn admin_ui() -> RawHtml<&'static str> {
-    RawHtml(include_str!("../../static/admin.html"))
+pub async fn admin_ui(app: &State<AppState>) -> StaticAsset {
+    StaticAsset {
+        content_type: ContentType::new("text", "html").with_params(("charset", "utf-8")),
+        cache_control: app.config.admin_cache_control().to_string(),
+        body: include_str!("../../static/admin.html"),
+    }
 }
 
 /// Serve the admin favicon SVG
 #[get("/static/admin-favicon.svg")]
-pub async fn admin_favicon() -> (rocket::http::ContentType, &'static str) {
-    (rocket::http::ContentType::SVG, include_str!("../../static/admin-favicon.svg"))
+pub async fn admin_favicon() -> StaticAsset {
+    StaticAsset {
+        content_type: ContentType::SVG,
+        cache_control: FAVICON_CACHE_CONTROL.to_string(),
+        body: include_str!("../../static/admin-favicon.svg"),
+    }
 }
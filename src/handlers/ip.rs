@@ -1,16 +1,40 @@
+use rocket::http::Status;
+use rocket::response::{self, Responder, Response};
 use rocket::serde::json::Json;
-use rocket::State;
+use rocket::{Request, State};
+use rocket_okapi::gen::OpenApiGenerator;
+use rocket_okapi::okapi::openapi3::{RefOr, Response as OpenApiResponse, Responses};
 use rocket_okapi::openapi;
+use rocket_okapi::response::OpenApiResponderInner;
 use rocket_okapi::okapi::schemars::JsonSchema;
 use rocket::serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 use tokio::sync::Mutex;
 
+use crate::audit;
 use crate::error::{Error, OResult};
+use crate::config::{DuplicateReturnPolicy, ItemEncoding, ReturnTimeoutAction, SseEventFormat};
+use crate::guards::admin_auth::AdminReadAuth;
+use crate::guards::borrow_token::BorrowTokenHeader;
+use crate::guards::idempotency_key::IdempotencyKey;
+use crate::guards::item_json::ItemJson;
+use crate::guards::last_event_id::LastEventId;
+use crate::guards::owner_id::OwnerId;
+use crate::guards::prefer::{Prefer, PreferenceApplied, RespondMode};
+use crate::guards::skip_subscribers::SkipSubscribers;
+use crate::metrics::Metrics;
 use crate::AppState;
-use crate::store::Store;
-use crate::ops::OperationStatus;
+use crate::store::{is_valid_pool_name, now_secs, BorrowRecord, Owner, Store};
+use crate::ops::{
+    cap_event, with_operation, Broadcasters, Operation, OperationKind, OperationStatus, OperationStore, PendingRelease, PendingReleases,
+    RetryEntry,
+};
+use crate::subscribers::{Subscribers, Undelivered};
+use crate::waiters::WaitQueue;
+use crate::config::{AppConfig, SubscriberDef};
 use rocket::response::stream::{Event, EventStream};
 use rocket::tokio::time::{interval, Duration};
+use serde_json::value::RawValue;
 use serde_json::Value;
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone)]
@@ -21,12 +45,76 @@ pub struct ReturnInput {
     params: Option<Value>,
 }
 
+/// One entry of a `/return/batch` body; `item` is kept as sent, like `/return` does
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ReturnBatchEntry {
+    #[schemars(with = "Value")]
+    item: Box<RawValue>,
+    borrow_token: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    params: Option<Value>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ReturnBatchResult {
+    item: Value,
+    /// HTTP status `/return` would have answered for this item alone
+    status: u16,
+    /// The item's own return operation, when the return was accepted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    operation_id: Option<String>,
+    /// Why the return was rejected
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Error>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ReturnBatchOutput {
+    /// Operation covering every accepted return; it succeeds once all of them have
+    operation_id: String,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    /// Number of returns accepted
+    accepted: usize,
+    /// Number of returns rejected
+    rejected: usize,
+    /// One result per entry, in request order
+    results: Vec<ReturnBatchResult>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PeekOutput {
+    /// Free items, in no particular order
+    items: Vec<Value>,
+    /// Number of items returned; less than `count` when fewer are free
+    count: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct PublicItemsList {
+    items: Vec<Value>,
+    /// Number of items in this page
+    count: usize,
+    /// Number of items in the freelist
+    total: usize,
+    /// Items skipped before this page
+    offset: usize,
+    /// Page size requested; null when the page runs to the end
+    limit: Option<usize>,
+}
+
 #[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct SubmitInput {
     item: Value,
+    /// Unix timestamp (seconds) after which the item is removed from the freelist
+    /// unless it is borrowed at that point
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    available_until: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone)]
+#[allow(dead_code)]
 pub struct ReturnIPOutput {
     success: bool,
     message: String,
@@ -34,23 +122,134 @@ pub struct ReturnIPOutput {
 
 #[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct BorrowOutput {
-    item: Value,
+    #[schemars(with = "Value")]
+    item: Box<RawValue>,
     borrow_token: String,
+    /// Identifies this borrow; the eventual return operation references it
+    borrow_id: String,
+    /// Unix timestamp (seconds) at which the borrow lease runs out, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    lease_expires_at: Option<u64>,
+    /// Unix timestamp (seconds) after which `borrow_token` can no longer return the item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    token_expires_at: Option<u64>,
+    /// With `context=true`: items left in the freelist after this borrow
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remaining: Option<usize>,
+    /// With `context=true`: up to five of the remaining items, picked at random
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sample: Option<Vec<Value>>,
 }
 
+/// Remaining items sampled by `/borrow?context=true`
+const BORROW_CONTEXT_SAMPLE: usize = 5;
+
 // listing is intentionally removed for generic store
 
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct BorrowBatchOutput {
+    /// Number of items asked for
+    requested: usize,
+    /// Number of items borrowed; less than `requested` when the freelist ran out or
+    /// `count` was clamped to `max_batch_count`
+    granted: usize,
+    items: Vec<BorrowOutput>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ReserveBatchOutput {
+    reservation_id: String,
+    items: Vec<Value>,
+    /// Number of items reserved; less than requested when clamped to `max_batch_count`
+    count: usize,
+    /// Unix timestamp (seconds) after which uncommitted items return to the freelist
+    expires_at: u64,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct CommitBatchInput {
+    reservation_id: String,
+    /// Subset of the reserved items to commit; all of them when omitted.
+    /// Items left in the reservation stay reserved until committed, aborted or expired.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    items: Option<Vec<Value>>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct CommitBatchOutput {
+    items: Vec<BorrowOutput>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct AbortBatchInput {
+    reservation_id: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct AbortBatchOutput {
+    /// Number of items returned to the freelist
+    released: usize,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct VerifyBorrowOutput {
+    valid: bool,
+    /// Unix timestamp (seconds) at which the borrow expires, if it does
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+    /// Why the token is not valid: `not_borrowed`, `token_mismatch` or `token_expired`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+/// Default lifetime of a batch reservation, in seconds
+const DEFAULT_RESERVATION_TTL_SECS: u64 = 60;
+
 #[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct OperationRef {
     operation_id: String,
     status: String,
+    /// Failure reason, for operations that already finished
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
 }
 
+/// An operation reference, echoing the honored `Prefer` response mode
+type PreferredResult = Result<PreferenceApplied<Json<OperationRef>>, Error>;
+
 #[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct OperationStatusOutput {
     operation_id: String,
     status: String,
     message: Option<String>,
+    /// Id of the originating borrow, for return operations
+    borrow_id: Option<String>,
+    /// Owner id (or client IP) of the request that started the operation
+    initiated_by: Option<String>,
+    /// The item the operation acts on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    item: Option<Value>,
+    /// For submits that reached the freelist: whether the item was not already in it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    newly_added: Option<bool>,
+    /// Set by the long-poll endpoint: true when it gave up before a terminal state
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timed_out: Option<bool>,
+}
+
+impl From<Operation> for OperationStatusOutput {
+    fn from(op: Operation) -> Self {
+        Self {
+            operation_id: op.id,
+            status: format!("{:?}", op.status).to_lowercase(),
+            message: op.message,
+            borrow_id: op.borrow_id,
+            initiated_by: op.initiated_by,
+            item: Some(op.item),
+            newly_added: op.newly_added,
+            timed_out: None,
+        }
+    }
 }
 
 /// Borrow an item from the freelist
@@ -60,196 +259,1819 @@ pub struct OperationStatusOutput {
 /// for an item to become available. If not specified, returns immediately.
 /// If specified, the request will block until an item becomes available or the timeout is reached.
 /// Optional query parameter `params` accepts a JSON string that will be passed to subscribers.
+///
+/// At most `max_waiters` waiting borrows are parked at once; beyond that the request is
+/// rejected with 503, `error: "wait_queue_full"` and a `Retry-After` header.
+///
+/// Optional `filter=<field>:<value>` borrows only an item whose field has that value.
+/// The field must be listed in `indexed_fields`; the item is then popped straight from
+/// that value's index instead of scanning the freelist. It cannot be combined with `wait`.
+///
+/// Optional `context=true` also reports, from the same atomic step as the pop, how many
+/// items are left (`remaining`) and a random `sample` of up to five of them, e.g. for
+/// schedulers planning their next borrow. It cannot be combined with `wait` or `filter`.
+///
+/// If the popped item turns out to be recorded under another borrow token (e.g. a stale
+/// freelist entry, possibly popped by another replica), the borrow fails with 409, the
+/// existing borrow is left untouched and the stale entry is dropped from the freelist.
+///
+/// Optional `lease=<secs>` overrides the configured `lease_ttl_secs`: if the item is not
+/// returned in time it is reclaimed into the freelist. `lease_expires_at` reports the deadline.
+///
+/// When no item is available the 503 body carries `error: "freelist_empty"` together with
+/// the `borrowed` and `total` item counts, so a fully allocated pool can be told apart from an empty one.
+///
+/// Optional `pool=<name>` borrows from that named pool instead of the default one; `wait`
+/// then only wakes on returns to it. Named pools are not indexed, so it cannot be combined
+/// with `filter`.
 #[openapi]
-#[get("/borrow?<wait>&<params>")]
+#[get("/borrow?<wait>&<params>&<filter>&<context>&<lease>&<pool>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn borrow(
     store: &State<Mutex<Store>>,
     app: &State<AppState>,
+    owner: Option<OwnerId>,
     wait: Option<u64>,
     params: Option<String>,
+    filter: Option<String>,
+    context: Option<bool>,
+    lease: Option<u64>,
+    pool: Option<String>,
 ) -> OResult<BorrowOutput> {
-    // Parse params JSON string if provided
-    let params_value: Option<Value> = match &params {
-        Some(p) => {
-            match serde_json::from_str(p) {
-                Ok(v) => Some(v),
-                Err(e) => return Err(Error::new("Invalid params", Some(&format!("Failed to parse params JSON: {}", e)), 400)),
+    let params_value = parse_params(params.as_deref())?;
+
+    let filter = match &filter {
+        Some(filter) => {
+            let Some((field, value)) = filter.split_once(':') else {
+                return Err(Error::new("Invalid filter", Some("Expected filter=<field>:<value>"), 400));
+            };
+            if !app.config.indexed_fields.iter().any(|f| f == field) {
+                return Err(Error::new("Invalid filter", Some("Field is not indexed"), 400)
+                    .with_context("error", "field_not_indexed"));
             }
+            if wait.is_some() {
+                return Err(Error::new("Invalid filter", Some("filter cannot be combined with wait"), 400));
+            }
+            if pool.is_some() {
+                return Err(Error::new("Invalid filter", Some("filter cannot be combined with pool"), 400));
+            }
+            Some((field, value))
         }
         None => None,
     };
 
-    let store = store.lock().await;
+    let context = context.unwrap_or(false);
+    if context && (wait.is_some() || filter.is_some()) {
+        return Err(Error::new("Invalid context", Some("context cannot be combined with wait or filter"), 400));
+    }
+
+    if lease == Some(0) {
+        return Err(Error::new("Invalid lease", Some("lease must be at least 1 second"), 400));
+    }
+    let lease = lease.or(app.config.lease_ttl_secs).map(Duration::from_secs);
+    let owner = owner.as_ref().map(OwnerId::as_owner);
+
+    // Waiting borrows take a slot in the bounded wait queue for the whole request
+    let _wait_slot = match wait {
+        Some(_) => Some(WaitSlot::acquire(app, owner.map(|o| o.id.to_string()))?),
+        None => None,
+    };
+
+    // Work on a handle of the store, so a waiting borrow does not hold the lock
+    let store = pool_store(store, pool.as_deref()).await?;
+    let started = std::time::Instant::now();
 
     // Determine whether to use blocking or non-blocking borrow
-    let result = if let Some(wait_secs) = wait {
+    let mut borrow_context = None;
+    let mut waited = false;
+    let result = if context {
+        store.borrow_with_context(BORROW_CONTEXT_SAMPLE).await.map(|(member, remaining, sample)| {
+            borrow_context = Some((remaining, sample));
+            member
+        })
+    } else if let Some((field, value)) = filter {
+        store.borrow_indexed(field, value).await
+    } else if let Some(wait_secs) = wait {
         // Use blocking borrow with timeout
         use std::time::Duration;
-        store.borrow_blocking(Duration::from_secs(wait_secs))
+        waited = true;
+        store.borrow_blocking_raw(Duration::from_secs(wait_secs)).await
     } else {
-        // Use non-blocking borrow (original behavior)
-        store.borrow()
+        // Pop and record in one step, so a crash in between cannot lose the item
+        return match borrow_recorded(app, &store, params_value.as_ref(), owner, lease, started).await {
+            Ok(output) => Ok(Json(output)),
+            Err(err) if err.is_freelist_empty() => Err(freelist_empty(&store, err).await),
+            Err(err) => Err(err),
+        };
     };
 
     match result {
-        Ok(item) => {
-            if let Err((msg, _must)) = app.subs.notify_borrow(&app.config, &item, params_value.as_ref()).await {
-                // On subscriber failure for must-succeed, return item to freelist as rollback
-                let _ = store.return_item(&item);
-                return Err(Error::new("Subscriber Error", Some(&msg), 502));
+        Ok(member) => {
+            let mut output =
+                complete_borrow(app, &store, &member, params_value.as_ref(), owner, lease, started, true)
+                    .await?;
+            if let Some((remaining, sample)) = borrow_context {
+                // Members are JSON, checked when they were submitted or returned
+                output.remaining = Some(remaining);
+                output.sample = Some(sample.iter().filter_map(|member| serde_json::from_str(member).ok()).collect());
+            }
+            Ok(Json(output))
+        }
+        Err(e) => {
+            let err = Error::from(e);
+            if !err.is_freelist_empty() {
+                return Err(err);
+            }
+            if waited {
+                app.metrics.inc_borrow_timeout(store.pool_name());
+            }
+            Err(freelist_empty(&store, err).await)
+        }
+    }
+}
+
+/// Parse the `params` JSON query parameter passed to subscribers
+fn parse_params(params: Option<&str>) -> Result<Option<Value>, Error> {
+    params
+        .map(serde_json::from_str)
+        .transpose()
+        .map_err(|e| Error::new("Invalid params", Some(&format!("Failed to parse params JSON: {}", e)), 400))
+}
+
+/// Borrow several items at once
+///
+/// Pops up to `count` items, each one atomically together with its borrow record exactly
+/// like `/borrow`: every item gets its own `borrow_token` and borrow subscribers are
+/// notified per item. If the freelist runs out first, the items borrowed so far are
+/// returned; `requested` and `granted` tell a partial grant apart. Answers 503 with
+/// `error: "freelist_empty"` only when not a single item was free.
+///
+/// Any other failure (a must-succeed subscriber failing, an item that cannot be recorded,
+/// Redis being unavailable, ...) puts every item borrowed by this call back in the freelist
+/// and returns the error, so a batch never half-fails. `count` is clamped to the configured `max_batch_count` (default 100);
+/// `params`, `lease` and `pool` work as for `/borrow`.
+#[openapi]
+#[get("/borrow/batch?<count>&<params>&<lease>&<pool>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn borrow_batch(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    owner: Option<OwnerId>,
+    count: usize,
+    params: Option<String>,
+    lease: Option<u64>,
+    pool: Option<String>,
+) -> OResult<BorrowBatchOutput> {
+    if count == 0 {
+        return Err(Error::new("Invalid count", Some("count must be at least 1"), 400));
+    }
+    let params_value = parse_params(params.as_deref())?;
+    if lease == Some(0) {
+        return Err(Error::new("Invalid lease", Some("lease must be at least 1 second"), 400));
+    }
+    let lease = lease.or(app.config.lease_ttl_secs).map(Duration::from_secs);
+    let owner = owner.as_ref().map(OwnerId::as_owner);
+    let store = pool_store(store, pool.as_deref()).await?;
+
+    let mut items: Vec<BorrowOutput> = Vec::new();
+    while items.len() < count.min(app.config.max_batch_count()) {
+        let started = std::time::Instant::now();
+        match borrow_recorded(app, &store, params_value.as_ref(), owner, lease, started).await {
+            Ok(output) => items.push(output),
+            Err(err) if err.is_freelist_empty() => break,
+            Err(err) => {
+                // All or nothing on failures: put back what this call already borrowed
+                for output in &items {
+                    app.subs.cancel_deferred_borrow(&output.borrow_token).await;
+                    if let Ok(item) = serde_json::from_str::<Value>(output.item.get()) {
+                        let _ = store.remove_borrowed_record(&item).await;
+                    }
+                    let _ = store.return_raw(output.item.get()).await;
+                    rollback_borrow(app, store.pool_name(), "batch_failed", output.item.get(), &err.to_string());
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    if items.is_empty() {
+        let err = Error::new("Service Unavailable", Some("No items available in the freelist"), 503)
+            .with_context("requested", count)
+            .with_context("granted", 0);
+        return Err(freelist_empty(&store, err).await);
+    }
+    Ok(Json(BorrowBatchOutput { requested: count, granted: items.len(), items }))
+}
+
+/// Add the `borrowed` and `total` item counts to a failed borrow's 503, telling
+/// "fully allocated" apart from "truly empty pool"
+async fn freelist_empty(store: &Store, err: Error) -> Error {
+    let borrowed = store.borrowed_count().await.unwrap_or_default();
+    let free = store.free_count().await.unwrap_or_default();
+    err.with_context("error", "freelist_empty")
+        .with_context("borrowed", borrowed)
+        .with_context("total", borrowed + free)
+}
+
+/// Borrow with [`Store::borrow_and_record`], then notify borrow subscribers. The item is
+/// recorded before anyone hears of it, so only a subscriber failure needs undoing: its
+/// record is dropped and the member goes back to the freelist.
+async fn borrow_recorded(
+    app: &AppState,
+    store: &Store,
+    params: Option<&Value>,
+    owner: Option<Owner<'_>>,
+    lease: Option<Duration>,
+    started: std::time::Instant,
+) -> Result<BorrowOutput, Error> {
+    let borrow_token = uuid::Uuid::new_v4().to_string();
+    let (member, item, record) =
+        store.borrow_and_record(&borrow_token, owner, lease).await.map_err(Error::from)?;
+    notify_recorded(app, store, &member, &item, record, borrow_token, params, started).await
+}
+
+/// Notify borrow subscribers of a borrow already recorded in Redis. A subscriber failure
+/// drops the record and puts the member back in the freelist.
+#[allow(clippy::too_many_arguments)]
+async fn notify_recorded(
+    app: &AppState,
+    store: &Store,
+    member: &str,
+    item: &Value,
+    record: BorrowRecord,
+    borrow_token: String,
+    params: Option<&Value>,
+    started: std::time::Instant,
+) -> Result<BorrowOutput, Error> {
+    if let Err((msg, _must)) = app.subs.notify_borrow(&app.config, item, params).await {
+        let _ = store.remove_borrowed_record(item).await;
+        let _ = store.return_raw(member).await;
+        rollback_borrow(app, store.pool_name(), "subscriber_failed", member, &msg);
+        return Err(Error::new("Subscriber Error", Some(&msg), 502));
+    }
+
+    app.subs.defer_borrow(&app.config, item, params, &borrow_token).await;
+    app.metrics.observe_borrow(store.pool_name(), started.elapsed());
+    app.metrics.inc_borrow(store.pool_name());
+    announce(store, "borrowed", item).await;
+    audit::record_item(app, store, "borrow", item, record.owner.as_deref(), None).await;
+    Ok(BorrowOutput {
+        item: item_output(app, item, member)?,
+        borrow_token,
+        borrow_id: record.borrow_id,
+        lease_expires_at: record.lease_expires_at,
+        token_expires_at: record.token_expires_at,
+        remaining: None,
+        sample: None,
+    })
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct BorrowSpecificInput {
+    /// The free item to borrow
+    item: Value,
+    /// Passed to the borrow subscribers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    params: Option<Value>,
+}
+
+/// Borrow one particular item
+///
+/// Borrows `item` itself instead of a random free item, e.g. to reclaim a known address.
+/// Checking that it is free (`SISMEMBER`), taking it out of the freelist (`SREM`) and
+/// recording the borrow run as one Lua script, so of two clients claiming the same item
+/// only one gets it. Answers 404 `not_free` when the item is not in the freelist (it is
+/// borrowed, reserved or unknown). Otherwise the response and the subscriber
+/// notifications match `/borrow`; optional `lease` and `pool` work as they do there.
+#[openapi]
+#[post("/borrow/specific?<lease>&<pool>", data = "<input>")]
+pub async fn borrow_specific(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    owner: Option<OwnerId>,
+    input: ItemJson<BorrowSpecificInput>,
+    lease: Option<u64>,
+    pool: Option<String>,
+) -> OResult<BorrowOutput> {
+    if lease == Some(0) {
+        return Err(Error::new("Invalid lease", Some("lease must be at least 1 second"), 400));
+    }
+    let lease = lease.or(app.config.lease_ttl_secs).map(Duration::from_secs);
+    let owner = owner.as_ref().map(OwnerId::as_owner);
+    let store = pool_store(store, pool.as_deref()).await?;
+    let started = std::time::Instant::now();
+
+    let borrow_token = uuid::Uuid::new_v4().to_string();
+    let raw_item = raw_member(app, &input.raw_item);
+    let taken = store
+        .borrow_specific(&input.item, raw_item.as_deref(), &borrow_token, owner, lease)
+        .await
+        .map_err(Error::from)?;
+    let Some((member, record)) = taken else {
+        return Err(Error::new("Not Found", Some("Item is not in the freelist"), 404).with_context("reason", "not_free"));
+    };
+    let output =
+        notify_recorded(app, &store, &member, &input.item, record, borrow_token, input.params.as_ref(), started)
+            .await?;
+    Ok(Json(output))
+}
+
+/// Header of `HEAD /borrow` with the number of free items
+pub const FREELIST_REMAINING_HEADER: &str = "X-Freelist-Remaining";
+
+/// Reply of `HEAD /borrow`: 200 while items are free, 503 otherwise, without a body
+pub struct Availability {
+    remaining: usize,
+}
+
+impl<'r> Responder<'r, 'static> for Availability {
+    fn respond_to(self, _: &'r Request<'_>) -> response::Result<'static> {
+        let status = if self.remaining > 0 { Status::Ok } else { Status::ServiceUnavailable };
+        Response::build()
+            .status(status)
+            .raw_header(FREELIST_REMAINING_HEADER, self.remaining.to_string())
+            .ok()
+    }
+}
+
+impl OpenApiResponderInner for Availability {
+    fn responses(_gen: &mut OpenApiGenerator) -> rocket_okapi::Result<Responses> {
+        let mut responses = Responses::default();
+        for (status, description) in [("200", "Items are available"), ("503", "The freelist is empty")] {
+            let response = OpenApiResponse { description: description.to_string(), ..Default::default() };
+            responses.responses.insert(status.to_string(), RefOr::Object(response));
+        }
+        Ok(responses)
+    }
+}
+
+/// Check whether an item is available without borrowing it
+///
+/// Answers 200 when the freelist has items and 503 when it is empty, like `GET /borrow`,
+/// but never pops an item or notifies subscribers. `X-Freelist-Remaining` carries the
+/// number of free items. Optional `pool=<name>` checks that named pool.
+#[openapi]
+#[head("/borrow?<pool>")]
+pub async fn borrow_available(store: &State<Mutex<Store>>, pool: Option<String>) -> Result<Availability, Error> {
+    let store = pool_store(store, pool.as_deref()).await?;
+    let remaining = store.free_count().await.map_err(Error::from)?;
+    Ok(Availability { remaining })
+}
+
+/// Notify borrow subscribers about a popped member and record it as borrowed.
+/// If either fails the member goes back to the freelist, or with `rollback_to_freelist`
+/// unset (it was never there) is dropped.
+#[allow(clippy::too_many_arguments)]
+async fn complete_borrow(
+    app: &AppState,
+    store: &Store,
+    member: &str,
+    params: Option<&Value>,
+    owner: Option<Owner<'_>>,
+    lease: Option<Duration>,
+    started: std::time::Instant,
+    rollback_to_freelist: bool,
+) -> Result<BorrowOutput, Error> {
+    let item: Value = match serde_json::from_str(member) {
+        Ok(item) => item,
+        Err(e) => {
+            if rollback_to_freelist {
+                let _ = store.return_raw(member).await;
             }
+            return Err(Error::new("Invalid item", Some(&format!("Stored value is not valid JSON: {}", e)), 500));
+        }
+    };
+    if let Err((msg, _must)) = app.subs.notify_borrow(&app.config, &item, params).await {
+        // On subscriber failure for must-succeed, return item to freelist as rollback
+        if rollback_to_freelist {
+            let _ = store.return_raw(member).await;
+        }
+        rollback_borrow(app, store.pool_name(), "subscriber_failed", member, &msg);
+        return Err(Error::new("Subscriber Error", Some(&msg), 502));
+    }
 
-            // Generate a borrow token and record the borrowed item
-            let borrow_token = uuid::Uuid::new_v4().to_string();
-            if let Err(e) = store.record_borrowed(&item, &borrow_token) {
-                // Failed to record borrow - rollback by returning item to freelist
-                let _ = store.return_item(&item);
-                return Err(Error::from(e));
+    // Generate a borrow token and record the borrowed item
+    let borrow_token = uuid::Uuid::new_v4().to_string();
+    let record = match store.record_borrowed(&item, &borrow_token, owner, lease).await {
+        Ok(record) => record,
+        Err(e) => {
+            // Failed to record borrow - rollback by returning item to freelist, unless it is
+            // already borrowed: then the popped member was a stale entry
+            let stale = e.to_string().contains("Item already borrowed");
+            if rollback_to_freelist && !stale {
+                let _ = store.return_raw(member).await;
             }
+            rollback_borrow(app, store.pool_name(), "record_failed", member, &e.to_string());
+            return Err(Error::from(e));
+        }
+    };
+
+    app.subs.defer_borrow(&app.config, &item, params, &borrow_token).await;
+    app.metrics.observe_borrow(store.pool_name(), started.elapsed());
+    app.metrics.inc_borrow(store.pool_name());
+    announce(store, "borrowed", &item).await;
+    audit::record_item(app, store, "borrow", &item, owner.map(|o| o.id), None).await;
+    Ok(BorrowOutput {
+        item: item_output(app, &item, member)?,
+        borrow_token,
+        borrow_id: record.borrow_id,
+        lease_expires_at: record.lease_expires_at,
+        token_expires_at: record.token_expires_at,
+        remaining: None,
+        sample: None,
+    })
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct BorrowOrSubmitInput {
+    /// Item to create and borrow if the freelist is empty
+    fallback_item: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    params: Option<Value>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct BorrowOrSubmitOutput {
+    /// `borrowed` if an existing item was borrowed, `submitted` if the fallback item was
+    /// submitted and borrowed
+    path: String,
+    #[serde(flatten)]
+    borrow: BorrowOutput,
+    /// The submit operation of the fallback item, on the `submitted` path
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    submit_operation_id: Option<String>,
+}
+
+/// Borrow an item, or submit and borrow a fallback item if the freelist is empty
+///
+/// Tries a normal `/borrow` first. If no item is available, submit subscribers are notified
+/// about `fallback_item` and it is then borrowed directly: it never sits in the freelist, so
+/// no other client can take it in between. `path` reports which happened. The response
+/// otherwise matches `/borrow`; `params` are passed to the borrow subscribers.
+///
+/// The fallback item must pass the same checks as `/submit` (`restrict_to_known_items`).
+/// Answers 409 if it is already borrowed and 502 if a must-succeed submit or borrow
+/// subscriber fails; either way nothing is added to the freelist. If the borrow after the
+/// submit fails, the submit operation fails with its error, named by `submit_operation_id`.
+#[openapi]
+#[post("/borrow/or-submit", data = "<input>")]
+pub async fn borrow_or_submit(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    owner: Option<OwnerId>,
+    input: Json<BorrowOrSubmitInput>,
+) -> OResult<BorrowOrSubmitOutput> {
+    let store = store.lock().await.clone();
+    let started = std::time::Instant::now();
+    let params = input.params.as_ref();
+    let owner = owner.as_ref().map(OwnerId::as_owner);
+    let lease = app.config.lease_ttl_secs.map(Duration::from_secs);
+
+    match borrow_recorded(app, &store, params, owner, lease, started).await {
+        Ok(borrow) => {
+            return Ok(Json(BorrowOrSubmitOutput { path: "borrowed".to_string(), borrow, submit_operation_id: None }));
+        }
+        Err(err) if !err.is_freelist_empty() => return Err(err),
+        Err(_) => {}
+    }
+
+    let item = &input.fallback_item;
+    ensure_known_item(&store, app, item).await?;
+    if store.is_free_or_borrowed(item).await.map_err(Error::from)? {
+        return Err(Error::new("Conflict", Some("Fallback item is already free or borrowed"), 409)
+            .with_context("reason", "already_known"));
+    }
+    app.metrics.inc_submit(store.pool_name());
+
+    // The submit completes once its subscribers accept it; the item then goes straight to borrowed
+    let op_id = uuid::Uuid::new_v4().to_string();
+    let mut must: HashSet<String> = HashSet::new();
+    for (name, def) in &app.config.submit.subscribers {
+        if def.mustSuceed && def.applies_to(item) {
+            must.insert(name.clone());
+        }
+    }
+    let mut op = Operation::new(op_id.clone(), OperationKind::Submit, item.clone(), must);
+    op.initiated_by = owner.map(|o| o.id.to_string());
+    let _ = app.ops.insert(op).await;
+    if let Err(undelivered) = app.subs.notify_submit(&app.config, item, &op_id).await {
+        let msg = undelivered.message();
+        app.ops.update_message(&op_id, Some(msg.clone())).await;
+        app.ops.set_status(&op_id, OperationStatus::Failed).await;
+        return Err(Error::new("Subscriber Error", Some(&msg), 502).with_context("operation_id", op_id.as_str()));
+    }
+
+    let member = compact_item(item)?.get().to_string();
+    let borrow = match complete_borrow(app, &store, &member, params, owner, lease, started, false).await {
+        Ok(borrow) => borrow,
+        Err(err) => {
+            app.ops.update_message(&op_id, Some(err.msg.clone().unwrap_or_else(|| err.err.clone()))).await;
+            app.ops.set_status(&op_id, OperationStatus::Failed).await;
+            return Err(err.with_context("submit_operation_id", op_id.as_str()));
+        }
+    };
+    app.ops.set_newly_added(&op_id, true).await;
+    app.ops.set_status(&op_id, OperationStatus::Succeeded).await;
+    Ok(Json(BorrowOrSubmitOutput {
+        path: "submitted".to_string(),
+        borrow,
+        submit_operation_id: Some(op_id),
+    }))
+}
+
+/// A place in the `/borrow?wait` queue, released when dropped
+struct WaitSlot<'a> {
+    queue: &'a WaitQueue,
+    id: u64,
+}
+
+impl<'a> WaitSlot<'a> {
+    fn acquire(app: &'a AppState, owner: Option<String>) -> Result<Self, Error> {
+        let max = app.config.max_waiters.unwrap_or(usize::MAX);
+        match app.waiters.try_enter(owner, max) {
+            Some(id) => Ok(WaitSlot { queue: &app.waiters, id }),
+            None => Err(Error::new("Service Unavailable", Some("Too many clients are already waiting to borrow"), 503)
+                .with_context("error", "wait_queue_full")
+                .with_retry_after(1)),
+        }
+    }
+}
+
+impl Drop for WaitSlot<'_> {
+    fn drop(&mut self) {
+        self.queue.leave(self.id);
+    }
+}
 
-            Ok(Json(BorrowOutput { item, borrow_token }))
+/// Reserve several items under a single reservation handle
+///
+/// Atomically takes `count` items out of the freelist, or none if fewer are available (503).
+/// The items are held until `/borrow/commit-batch` or `/borrow/abort-batch`; whatever is still
+/// reserved after `ttl` seconds (default 60) goes back to the freelist.
+/// `ttl` is capped to the configured `token_max_lifetime_secs`.
+/// `count` is clamped to the configured `max_batch_count` (default 100); the response
+/// reports the effective `count`.
+#[openapi]
+#[post("/borrow/reserve-batch?<count>&<ttl>")]
+pub async fn reserve_batch(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    count: usize,
+    ttl: Option<u64>,
+) -> OResult<ReserveBatchOutput> {
+    if count == 0 {
+        return Err(Error::new("Invalid count", Some("count must be at least 1"), 400));
+    }
+    let count = count.min(app.config.max_batch_count());
+    let ttl = ttl.unwrap_or(DEFAULT_RESERVATION_TTL_SECS);
+    let ttl = Duration::from_secs(app.config.token_max_lifetime_secs.map_or(ttl, |max| ttl.min(max)));
+
+    let store = store.lock().await.clone();
+    store.expire_reservations().await.map_err(Error::from)?;
+    match store.reserve_batch(count, ttl).await.map_err(Error::from)? {
+        Some(reservation) => Ok(Json(ReserveBatchOutput {
+            reservation_id: reservation.id,
+            count: reservation.items.len(),
+            items: reservation.items,
+            expires_at: reservation.expires_at,
+        })),
+        None => {
+            let free = store.free_count().await.unwrap_or_default();
+            Err(Error::new("Service Unavailable", Some("Not enough items available in the freelist"), 503)
+                .with_context("error", "freelist_empty")
+                .with_context("requested", count)
+                .with_context("free", free))
         }
-        Err(e) => Err(crate::error::Error::from(e)),
     }
 }
 
+/// Commit reserved items as borrowed
+///
+/// Commits every item of the reservation, or only `items` when given. Each committed item
+/// gets its own borrow token, exactly as from `/borrow`, and borrow subscribers are notified.
+/// If a must-succeed subscriber fails, all items taken by this call go back to the freelist.
+/// Returns 404 if the reservation does not exist or has expired.
+#[openapi]
+#[post("/borrow/commit-batch", data = "<input>")]
+pub async fn commit_batch(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    input: Json<CommitBatchInput>,
+) -> OResult<CommitBatchOutput> {
+    if input.items.as_ref().is_some_and(|items| items.is_empty()) {
+        return Err(Error::new("Invalid items", Some("items must not be empty when given"), 400));
+    }
+
+    let store = store.lock().await.clone();
+    store.expire_reservations().await.map_err(Error::from)?;
+
+    let requested = input.items.as_ref().map(|items| {
+        let mut seen = HashSet::new();
+        items.iter().filter(|item| seen.insert(item.to_string())).cloned().collect::<Vec<_>>()
+    });
+    let taken = store
+        .take_reserved(&input.reservation_id, requested.as_deref())
+        .await
+        .map_err(Error::from)?
+        .ok_or_else(|| Error::new("Not Found", Some("Reservation not found or expired"), 404))?;
+
+    let mut committed: Vec<BorrowOutput> = Vec::with_capacity(taken.len());
+    for (i, item) in taken.iter().enumerate() {
+        let result = match app.subs.notify_borrow(&app.config, item, None).await {
+            Ok(()) => {
+                let borrow_token = uuid::Uuid::new_v4().to_string();
+                let lease = app.config.lease_ttl_secs.map(Duration::from_secs);
+                let recorded = store.record_borrowed(item, &borrow_token, None, lease).await;
+                if recorded.is_ok() {
+                    app.subs.defer_borrow(&app.config, item, None, &borrow_token).await;
+                    announce(&store, "borrowed", item).await;
+                }
+                recorded.map_err(Error::from).and_then(|record| {
+                    Ok(BorrowOutput {
+                        item: compact_item(item)?,
+                        borrow_token,
+                        borrow_id: record.borrow_id,
+                        lease_expires_at: record.lease_expires_at,
+                        token_expires_at: record.token_expires_at,
+                        remaining: None,
+                        sample: None,
+                    })
+                })
+            }
+            Err((msg, _must)) => Err(Error::new("Subscriber Error", Some(&msg), 502)),
+        };
+        match result {
+            Ok(output) => committed.push(output),
+            Err(err) => {
+                // Commit all or nothing: undo what was recorded and free the rest
+                for (done, output) in taken.iter().zip(&committed) {
+                    app.subs.cancel_deferred_borrow(&output.borrow_token).await;
+                    let _ = store.force_return(done).await;
+                }
+                for item in &taken[i..] {
+                    let _ = store.return_item(item).await;
+                }
+                return Err(err);
+            }
+        }
+    }
+
+    Ok(Json(CommitBatchOutput { items: committed }))
+}
+
+/// Abort a reservation
+///
+/// Returns every item still held by the reservation to the freelist.
+/// Returns 404 if the reservation does not exist or has expired.
+#[openapi]
+#[post("/borrow/abort-batch", data = "<input>")]
+pub async fn abort_batch(
+    store: &State<Mutex<Store>>,
+    input: Json<AbortBatchInput>,
+) -> OResult<AbortBatchOutput> {
+    let store = store.lock().await.clone();
+    store.expire_reservations().await.map_err(Error::from)?;
+    match store.abort_reservation(&input.reservation_id).await.map_err(Error::from)? {
+        Some(released) => Ok(Json(AbortBatchOutput { released })),
+        None => Err(Error::new("Not Found", Some("Reservation not found or expired"), 404)),
+    }
+}
+
+/// List available items for service discovery
+///
+/// A read-only view of the freelist for consumers without admin credentials; only
+/// items are listed, never tokens or owners. Paginated like `/admin/items`.
+/// Answers 404 unless `public_item_listing` is enabled.
+#[openapi]
+#[get("/items?<offset>&<limit>")]
+pub async fn list_free_items(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> OResult<PublicItemsList> {
+    if !app.config.public_item_listing {
+        return Err(Error::new("Not Found", Some("Public item listing is disabled"), 404));
+    }
+    let store = store.lock().await.clone();
+    let total = store.free_count().await.map_err(Error::from)?;
+    let offset = offset.unwrap_or(0);
+    let items = store.scan_items(offset, limit).await.map_err(Error::from)?;
+    let count = items.len();
+    Ok(Json(PublicItemsList { items, count, total, offset, limit }))
+}
+
+/// Look at free items without borrowing them
+///
+/// Returns up to `count` (default 1) free items picked with `SRANDMEMBER`; they stay in
+/// the freelist, no operation is created and no subscriber is notified. The freelist is
+/// a set, so there is no head to look at: which items come back, and in what order, is
+/// not guaranteed, and the next `/borrow` may pop a different one. `count` is clamped to
+/// `max_batch_count` (default 100); `pool=<name>` peeks at that named pool.
+/// Requires an admin key when `admin_read_key` is configured, like `/admin/items`.
+#[openapi]
+#[get("/peek?<count>&<pool>")]
+pub async fn peek(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    _reader: AdminReadAuth,
+    count: Option<usize>,
+    pool: Option<String>,
+) -> OResult<PeekOutput> {
+    let count = count.unwrap_or(1);
+    if count == 0 {
+        return Err(Error::new("Invalid count", Some("count must be at least 1"), 400));
+    }
+    let store = pool_store(store, pool.as_deref()).await?;
+    let items = store.peek_items(count.min(app.config.max_batch_count())).await.map_err(Error::from)?;
+    let count = items.len();
+    Ok(Json(PeekOutput { items, count }))
+}
+
+/// Check a borrow token without returning the item
+///
+/// `item` is the borrowed item as a JSON string and `borrow_token` the token it was
+/// borrowed under. Answers `valid: true`, or `valid: false` with `reason` set to
+/// `not_borrowed` (the item is not out on loan), `token_mismatch` (it is borrowed
+/// under another token, e.g. after a force return and re-borrow) or `token_expired`
+/// (the token outlived `token_max_lifetime_secs`). `expires_at` is the earlier of the
+/// lease and token deadlines.
+///
+/// Prefer sending the token in the `X-Borrow-Token` header, which wins over the query
+/// parameter: URLs end up in proxy and access logs. One of the two is required.
+#[openapi]
+#[get("/borrow/verify?<item>&<borrow_token>")]
+pub async fn verify_borrow(
+    store: &State<Mutex<Store>>,
+    token_header: BorrowTokenHeader,
+    item: String,
+    borrow_token: Option<String>,
+) -> OResult<VerifyBorrowOutput> {
+    let item: Value = serde_json::from_str(&item)
+        .map_err(|e| Error::new("Invalid item", Some(&format!("Failed to parse item JSON: {}", e)), 400))?;
+    let Some(borrow_token) = token_header.0.or(borrow_token) else {
+        return Err(Error::new("Missing borrow token", Some("Send X-Borrow-Token or borrow_token"), 400));
+    };
+
+    let store = store.lock().await.clone();
+    let reason = match store.verify_borrow_token(&item, &borrow_token).await {
+        Ok(()) => None,
+        Err(e) => match Error::from(e) {
+            err if err.http_status_code == 404 => Some("not_borrowed"),
+            err if err.http_status_code == 403 => Some("token_mismatch"),
+            err => return Err(err),
+        },
+    };
+    let record = match reason {
+        None => store.get_borrow_record(&item).await.map_err(Error::from)?,
+        Some(_) => None,
+    };
+    let reason = match &record {
+        Some(record) if record.token_expires_at.is_some_and(|at| at <= now_secs()) => {
+            Some("token_expired")
+        }
+        _ => reason,
+    };
+    let expires_at = match (reason, record) {
+        (None, Some(record)) => record.reclaim_at(),
+        _ => None,
+    };
+    Ok(Json(VerifyBorrowOutput {
+        valid: reason.is_none(),
+        expires_at,
+        reason: reason.map(str::to_string),
+    }))
+}
+
 /// Return an item to the freelist
 ///
 /// Requires the borrow_token that was provided when the item was borrowed.
 /// This prevents accidentally returning an item currently borrowed by someone else.
 /// Optional `params` field accepts a JSON object that will be passed to return subscribers.
+/// The item must equal (as JSON) the item borrowed under the token; otherwise the
+/// return is rejected with 409 `item_mismatch`.
+/// With `[return] mutate_freelist = false` the borrow is closed and subscribers are notified,
+/// but the item is not added back to this pool's freelist.
+/// With `two_phase_return` the item stays borrowed after the subscribers succeed, until
+/// `/return/confirm` or the confirmation timeout (see `return_timeout_action`).
+/// Borrows made with an owner (`X-Owner-Id`, or the client IP without it) can only be
+/// returned by that owner; anyone else gets 403 `owner_mismatch`.
+/// With `token_max_lifetime_secs` set, a token older than that gets 410 `token_expired`.
+/// A token that no longer holds the item is handled per `duplicate_return_policy`:
+/// rejected (default), returned anyway (`accept_last`) or acknowledged as a no-op
+/// (`accept_first`).
+///
+/// The workflow runs in the background and an operation reference is returned at once.
+/// With `Prefer: respond-sync` the request waits for the workflow and reports its final
+/// status instead; the honored preference is echoed in `Preference-Applied`.
+///
+/// With a valid admin key, `X-Skip-Subscribers: name1,name2` skips those subscribers for
+/// this return only; the operation message records which were skipped.
+///
+/// With an `Idempotency-Key` header, a repeated return with the same key (within
+/// `idempotency_key_ttl_secs`) answers the first return's operation instead of starting
+/// another; see `idempotent`.
+///
+/// Items borrowed with `/borrow?pool=<name>` must be returned with the same `pool`.
 #[openapi]
-#[post("/return", data = "<input>")]
+#[post("/return?<pool>", data = "<input>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn return_item(
     store: &State<Mutex<Store>>,
     app: &State<AppState>,
-    input: Json<ReturnInput>,
-) -> OResult<OperationRef> {
+    owner: Option<OwnerId>,
+    prefer: Prefer,
+    skip: SkipSubscribers,
+    idempotency_key: IdempotencyKey,
+    input: ItemJson<ReturnInput>,
+    pool: Option<String>,
+) -> PreferredResult {
+    let key = idempotency_key.scoped("return");
+    let item = input.item.clone();
+    idempotent(app, key, &item, prefer, |op_id, prefer| {
+        return_checked(store, app, owner, prefer, skip, input, pool, op_id)
+    })
+    .await
+}
+
+/// `/return` under the operation id `op_id`
+#[allow(clippy::too_many_arguments)]
+async fn return_checked(
+    store: &State<Mutex<Store>>,
+    app: &AppState,
+    owner: Option<OwnerId>,
+    prefer: Prefer,
+    skip: SkipSubscribers,
+    input: ItemJson<ReturnInput>,
+    pool: Option<String>,
+    op_id: String,
+) -> PreferredResult {
     // Verify the borrow token before proceeding
-    let store_lock = store.lock().await;
-    if let Err(e) = store_lock.verify_borrow_token(&input.item, &input.borrow_token) {
-        return Err(Error::from(e));
+    let store_lock = pool_store(store, pool.as_deref()).await?;
+    ensure_known_item(&store_lock, app, &input.item).await?;
+    ensure_token_item(&store_lock, &input.item, &input.borrow_token).await?;
+    let policy = app.config.duplicate_return_policy;
+    let duplicate = match store_lock.verify_borrow_token(&input.item, &input.borrow_token).await {
+        Ok(()) => false,
+        // The item is already free or held under another token
+        Err(e) if e.kind() == redis::ErrorKind::ResponseError && policy != DuplicateReturnPolicy::Reject => true,
+        Err(e) => return Err(Error::from(e)),
+    };
+    if !duplicate {
+        ensure_owner(&store_lock, &input.item, owner.as_ref()).await?;
     }
+    ensure_no_pending_release(app, &input.item).await?;
+    if !duplicate {
+        // Returned within the grace period: deferred borrow subscribers never hear of it
+        app.subs.cancel_deferred_borrow(&input.borrow_token).await;
+    }
+    let task_store = store_lock.clone();
     drop(store_lock); // Release lock before spawning async task
 
-    // Create operation
+    if duplicate && policy == DuplicateReturnPolicy::AcceptFirst {
+        app.metrics.inc_return(task_store.pool_name());
+        let accepted = std::time::Instant::now();
+        let op_id_resp = op_id.clone();
+        let ops = app.ops.clone();
+        let sse = app.sse.clone();
+        // Record a no-op: subscribers are not notified and Redis is left alone
+        let mut op = Operation::new(op_id.clone(), OperationKind::Return, input.item.clone(), HashSet::new());
+        op.initiated_by = owner.map(|o| o.id);
+        audit::record_item(app, &task_store, "return", &input.item, op.initiated_by.as_deref(), Some(&op_id)).await;
+        let _ = ops.insert(op).await;
+        let workflow = async move {
+            ops.update_message(&op_id, Some("Duplicate return ignored".to_string())).await;
+            ops.set_status(&op_id, OperationStatus::Succeeded).await;
+            sse.notify(&op_id, serde_json::json!({"event":"completed"}).to_string()).await;
+        };
+        let workflow = timed_return(app, task_store.pool_name(), op_id_resp.clone(), accepted, workflow);
+        return respond(app, prefer, op_id_resp, workflow).await;
+    }
+
+    let raw_item = raw_member(app, &input.raw_item);
+    let workflow =
+        start_return(app, task_store, op_id.clone(), owner.map(|o| o.id), &skip, input.item.clone(), raw_item, input.params.clone())
+            .await?;
+    respond(app, prefer, op_id, workflow).await
+}
+
+/// Run a return or submit (`run`, given the operation id to use) at most once per
+/// idempotency key. A repeated key answers the operation its first request started:
+/// its current status, without waiting for it even under `Prefer: respond-sync`.
+/// Reusing a key for a different item is rejected with 422 `idempotency_key_reused`,
+/// and a repeat arriving before the first request has recorded its operation gets 409
+/// `idempotency_key_in_use`. A request that fails before starting an operation frees its
+/// key, so it can be retried under the same one.
+async fn idempotent<F, Fut>(app: &AppState, key: Option<String>, item: &Value, prefer: Prefer, run: F) -> PreferredResult
+where
+    F: FnOnce(String, Prefer) -> Fut,
+    Fut: std::future::Future<Output = PreferredResult>,
+{
     let op_id = uuid::Uuid::new_v4().to_string();
-    let op_id_resp = op_id.clone();
-    let item_value = input.item.clone();
-    let params_value = input.params.clone();
-    let subs = app.subs.clone();
-    let ops = app.ops.clone();
-    let sse = app.sse.clone();
-    let cfg = app.config.clone();
-    let redis_url = app.redis_url.clone();
-
-    // Spawn workflow in background
-    tokio::spawn(async move {
-        use std::collections::HashSet;
-        // identify must-succeed subscribers
-        let mut must: HashSet<String> = HashSet::new();
-        for (name, def) in &cfg.r#return.subscribers {
-            if def.mustSuceed {
-                must.insert(name.clone());
+    let Some(key) = key else {
+        return run(op_id, prefer).await;
+    };
+    if let Some(existing) = app.ops.claim_idempotency_key(&key, &op_id).await {
+        let op = match app.ops.get(&existing).await {
+            Some(op) => op,
+            None => {
+                return Err(Error::new("Conflict", Some("A request with this idempotency key is still being handled"), 409)
+                    .with_context("reason", "idempotency_key_in_use"));
             }
+        };
+        if op.item != *item {
+            return Err(Error::new("Unprocessable Entity", Some("Idempotency key was used for another item"), 422)
+                .with_context("reason", "idempotency_key_reused")
+                .with_context("operation_id", existing));
         }
-        let _ = ops.create(op_id.clone(), item_value.clone(), must).await;
-        sse.notify(&op_id, serde_json::json!({"event":"created"}).to_string()).await;
+        let status = OperationStatusOutput::from(op);
+        let output = OperationRef { operation_id: existing, status: status.status, message: status.message };
+        return Ok(PreferenceApplied { inner: Json(output), applied: None });
+    }
+    let result = run(op_id.clone(), prefer).await;
+    if result.is_err() && app.ops.get(&op_id).await.is_none() {
+        app.ops.release_idempotency_key(&key, &op_id).await;
+    }
+    result
+}
+
+/// Return several items at once
+///
+/// Takes an array of `{item, borrow_token, params?}` entries and checks each one like
+/// `/return`. Entries that fail a check (a bad or expired token, a mismatched item, another
+/// owner, ...) are reported in `results` with the status and error `/return` would have
+/// answered, without affecting the others. A token that does not hold its item is always
+/// rejected here, whatever `duplicate_return_policy` says, and so is an item listed twice.
+///
+/// Every accepted item gets its own return operation, exactly as from `/return`, so return
+/// subscribers are notified once per item. `operation_id` tracks the batch as a whole: it
+/// succeeds once every accepted return has, fails if any of them fails (or none was
+/// accepted), and otherwise stays `in_progress` with the item operations telling the rest.
+/// Honors `Prefer: respond-sync`, `X-Skip-Subscribers` and `pool` like `/return`. At most
+/// `max_batch_count` (default 100) entries are accepted per request.
+#[openapi]
+#[post("/return/batch?<pool>", data = "<input>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn return_batch(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    owner: Option<OwnerId>,
+    prefer: Prefer,
+    skip: SkipSubscribers,
+    input: Json<Vec<ReturnBatchEntry>>,
+    pool: Option<String>,
+) -> Result<PreferenceApplied<Json<ReturnBatchOutput>>, Error> {
+    if input.is_empty() {
+        return Err(Error::new("Invalid items", Some("At least one item must be given"), 400));
+    }
+    if input.len() > app.config.max_batch_count() {
+        return Err(Error::new("Invalid items", Some("Too many items for one batch"), 400)
+            .with_context("max_batch_count", app.config.max_batch_count()));
+    }
+    let store = pool_store(store, pool.as_deref()).await?;
 
-        // Run notifications sequentially respecting must-succeed
-        match subs.notify_return(&cfg, &item_value, params_value.as_ref()).await {
+    let mut results = Vec::with_capacity(input.len());
+    let mut returned = Vec::new();
+    let mut item_ops = Vec::new();
+    let mut workflows = Vec::new();
+    let mut seen = HashSet::new();
+    for entry in input.into_inner() {
+        let item: Value = match serde_json::from_str(entry.item.get()) {
+            Ok(item) => item,
+            Err(e) => return Err(Error::new("Invalid item", Some(&e.to_string()), 400)),
+        };
+        let checked = match seen.insert(item.to_string()) {
+            true => check_batch_return(app, &store, owner.as_ref(), &item, &entry.borrow_token).await,
+            false => Err(Error::new("Conflict", Some("Item is listed more than once"), 409)
+                .with_context("reason", "duplicate_in_batch")),
+        };
+        let started = match checked {
             Ok(()) => {
-                ops.set_status(&op_id, OperationStatus::InProgress).await;
-                sse.notify(&op_id, serde_json::json!({"event":"notifications_ok"}).to_string()).await;
-                let store = Store::new(redis_url);
-                match store.return_item(&item_value) {
-                    Ok(_) => {
-                        // Remove the borrowed record after successful return
-                        let _ = store.remove_borrowed_record(&item_value);
-                        ops.set_status(&op_id, OperationStatus::Succeeded).await;
-                        sse.notify(&op_id, serde_json::json!({"event":"completed"}).to_string()).await;
-                    }
-                    Err(e) => {
-                        ops.update_message(&op_id, Some(e.to_string())).await;
-                        ops.set_status(&op_id, OperationStatus::Failed).await;
-                        sse.notify(&op_id, serde_json::json!({"event":"failed","reason":e.to_string()}).to_string()).await;
-                    }
-                }
+                app.subs.cancel_deferred_borrow(&entry.borrow_token).await;
+                let raw_item = raw_member(app, entry.item.get());
+                let op_id = uuid::Uuid::new_v4().to_string();
+                let owner = owner.as_ref().map(|o| o.id.clone());
+                start_return(app, store.clone(), op_id.clone(), owner, &skip, item.clone(), raw_item, entry.params)
+                    .await
+                    .map(|workflow| (op_id, workflow))
             }
-            Err((msg, _)) => {
-                ops.update_message(&op_id, Some(msg.clone())).await;
-                ops.set_status(&op_id, OperationStatus::Failed).await;
-                sse.notify(&op_id, serde_json::json!({"event":"failed","reason":msg}).to_string()).await;
+            Err(err) => Err(err),
+        };
+        match started {
+            Ok((op_id, workflow)) => {
+                returned.push(item.clone());
+                item_ops.push(op_id.clone());
+                workflows.push(workflow);
+                results.push(ReturnBatchResult { item, status: 200, operation_id: Some(op_id), error: None });
+            }
+            Err(err) => {
+                let status = err.http_status_code;
+                results.push(ReturnBatchResult { item, status, operation_id: None, error: Some(err) });
             }
         }
-    });
+    }
+
+    let op_id = uuid::Uuid::new_v4().to_string();
+    let mut op = Operation::new(op_id.clone(), OperationKind::Return, Value::Array(returned), HashSet::new());
+    op.initiated_by = owner.map(|o| o.id);
+    let _ = app.ops.insert(op).await;
+    let (ops, sse) = (app.ops.clone(), app.sse.clone());
+    let batch_id = op_id.clone();
+    let workflow = async move {
+        rocket::futures::future::join_all(workflows).await;
+        let (mut failed, mut unfinished) = (0, 0);
+        for id in &item_ops {
+            match ops.get(id).await.map(|op| op.status) {
+                Some(OperationStatus::Succeeded) => {}
+                Some(OperationStatus::Failed) | None => failed += 1,
+                Some(_) => unfinished += 1,
+            }
+        }
+        let (status, message) = if item_ops.is_empty() {
+            (OperationStatus::Failed, Some("No item was returned".to_string()))
+        } else if failed > 0 {
+            (OperationStatus::Failed, Some(format!("{} of {} returns failed", failed, item_ops.len())))
+        } else if unfinished > 0 {
+            (OperationStatus::InProgress, Some(format!("{} of {} returns not finished", unfinished, item_ops.len())))
+        } else {
+            (OperationStatus::Succeeded, None)
+        };
+        let event = match status {
+            OperationStatus::Succeeded => Some(serde_json::json!({"event":"completed"})),
+            OperationStatus::Failed => Some(serde_json::json!({"event":"failed","reason":message})),
+            _ => None,
+        };
+        ops.update_message(&batch_id, message).await;
+        ops.set_status(&batch_id, status).await;
+        if let Some(event) = event {
+            sse.notify(&batch_id, event.to_string()).await;
+        }
+    };
+
+    let (status, message) = match prefer.mode() {
+        RespondMode::Async => {
+            tokio::spawn(workflow);
+            ("accepted".to_string(), None)
+        }
+        RespondMode::Sync => {
+            workflow.await;
+            match app.ops.get(&op_id).await {
+                Some(op) => {
+                    let status = OperationStatusOutput::from(op);
+                    (status.status, status.message)
+                }
+                None => return Err(Error::new("Not Found", Some("operation not found"), 404)),
+            }
+        }
+    };
+    let accepted = results.iter().filter(|result| result.operation_id.is_some()).count();
+    let output = ReturnBatchOutput {
+        operation_id: op_id,
+        status,
+        message,
+        accepted,
+        rejected: results.len() - accepted,
+        results,
+    };
+    Ok(PreferenceApplied { inner: Json(output), applied: prefer.0 })
+}
 
-    Ok(Json(OperationRef { operation_id: op_id_resp, status: "accepted".to_string() }))
+/// The checks `/return` makes before accepting a return, with any token that does not
+/// hold the item rejected
+async fn check_batch_return(
+    app: &AppState,
+    store: &Store,
+    owner: Option<&OwnerId>,
+    item: &Value,
+    borrow_token: &str,
+) -> Result<(), Error> {
+    ensure_known_item(store, app, item).await?;
+    ensure_token_item(store, item, borrow_token).await?;
+    store.verify_borrow_token(item, borrow_token).await.map_err(Error::from)?;
+    ensure_owner(store, item, owner).await?;
+    ensure_no_pending_release(app, item).await
+}
+
+/// Record the return operation `op_id` of a checked item, so its id is immediately
+/// pollable, and build its workflow for the caller to run or spawn
+#[allow(clippy::too_many_arguments)]
+async fn start_return(
+    app: &AppState,
+    store: Store,
+    op_id: String,
+    owner: Option<String>,
+    skip: &SkipSubscribers,
+    item_value: Value,
+    raw_item: Option<String>,
+    params_value: Option<Value>,
+) -> Result<impl std::future::Future<Output = ()> + Send + 'static, Error> {
+    let borrow_id = store
+        .get_borrow_record(&item_value)
+        .await
+        .map_err(Error::from)?
+        .map(|record| record.borrow_id);
+    app.metrics.inc_return(store.pool_name());
+    let accepted = std::time::Instant::now();
+
+    let mut cfg = app.config.clone();
+    let skipped = skip_subscribers(&mut cfg.r#return.subscribers, skip);
+    let mut must: HashSet<String> = HashSet::new();
+    for (name, def) in &cfg.r#return.subscribers {
+        if def.mustSuceed && def.applies_to(&item_value) {
+            must.insert(name.clone());
+        }
+    }
+    let mut op = Operation::new(op_id.clone(), OperationKind::Return, item_value.clone(), must);
+    op.message = skipped;
+    op.borrow_id = borrow_id;
+    op.initiated_by = owner;
+    audit::record_item(app, &store, "return", &item_value, op.initiated_by.as_deref(), Some(&op_id)).await;
+    let _ = app.ops.insert(op).await;
+    app.sse.notify(&op_id, serde_json::json!({"event":"created"}).to_string()).await;
+
+    let releases = cfg.two_phase_return.then(|| app.releases.clone());
+    let pool = store.pool_name().to_string();
+    let (subs, ops, sse) = (app.subs.clone(), app.ops.clone(), app.sse.clone());
+    let workflow =
+        return_workflow(subs, ops, sse, cfg, store, op_id.clone(), item_value, raw_item, params_value, releases);
+    Ok(timed_return(app, &pool, op_id, accepted, workflow))
+}
+
+/// Reject returning a different (e.g. mutated) item than the one borrowed under the token
+async fn ensure_token_item(store: &Store, item: &Value, borrow_token: &str) -> Result<(), Error> {
+    match store.item_for_token(borrow_token).await.map_err(Error::from)? {
+        Some(borrowed) if borrowed != *item => {
+            Err(Error::new("Conflict", Some("Item does not match the item borrowed under this token"), 409)
+                .with_context("reason", "item_mismatch"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Reject returns by anyone but the borrow's owner, and returns with an expired token
+async fn ensure_owner(store: &Store, item: &Value, owner: Option<&OwnerId>) -> Result<(), Error> {
+    let owner = owner.map(|o| o.id.as_str());
+    store.verify_owner(item, owner).await.map_err(|e| match Error::from(e) {
+        err if err.http_status_code == 403 => err.with_context("reason", "owner_mismatch"),
+        err if err.http_status_code == 410 => err.with_context("reason", "token_expired"),
+        err => err,
+    })
+}
+
+/// Reject a return while an earlier two-phase return of the item awaits confirmation
+async fn ensure_no_pending_release(app: &AppState, item: &Value) -> Result<(), Error> {
+    if app.releases.contains_item(item).await {
+        return Err(Error::new("Conflict", Some("A return of this item is awaiting confirmation"), 409)
+            .with_context("reason", "release_pending"));
+    }
+    Ok(())
+}
+
+/// Record `return_duration_seconds` once a return workflow leaves its operation terminal.
+/// Two-phase returns still awaiting confirmation are recorded when they are resolved.
+fn timed_return(
+    app: &AppState,
+    pool: &str,
+    op_id: String,
+    accepted: std::time::Instant,
+    workflow: impl std::future::Future<Output = ()> + Send + 'static,
+) -> impl std::future::Future<Output = ()> + Send + 'static {
+    let ops = app.ops.clone();
+    let metrics = app.metrics.clone();
+    let pool = pool.to_string();
+    async move {
+        workflow.await;
+        if ops.get(&op_id).await.is_some_and(|op| op.status.is_terminal()) {
+            metrics.observe_return(&pool, accepted.elapsed());
+        }
+    }
+}
+
+/// Record `return_duration_seconds` of a resolved two-phase return from its operation timestamps
+async fn observe_resolved_return(ops: &OperationStore, metrics: &Metrics, pool: &str, op_id: &str) {
+    if let Some(op) = ops.get(op_id).await {
+        let completed = op.completed_at.unwrap_or_else(now_secs);
+        metrics.observe_return(pool, Duration::from_secs(completed.saturating_sub(op.created_at)));
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ConfirmReturnInput {
+    operation_id: String,
+    /// Token of the borrow being returned, unless sent as `X-Borrow-Token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    borrow_token: Option<String>,
+}
+
+/// Confirm a two-phase return
+///
+/// With `two_phase_return` enabled, `/return` notifies subscribers but leaves the item
+/// borrowed; this puts it back in the freelist and completes the operation. Answers 404
+/// if the operation is not awaiting confirmation (unknown, already confirmed, or timed out).
+/// Only the borrower may confirm: send the borrow token (`X-Borrow-Token` or
+/// `borrow_token`), or the `X-Owner-Id` the item was borrowed with. Anything else answers
+/// 400 when neither is sent and 403 when they do not match the borrow.
+#[openapi]
+#[post("/return/confirm", data = "<input>")]
+pub async fn confirm_return(
+    store: &State<Mutex<Store>>,
+    app: &State<AppState>,
+    owner: Option<OwnerId>,
+    token_header: BorrowTokenHeader,
+    input: Json<ConfirmReturnInput>,
+) -> OResult<OperationRef> {
+    let op_id = &input.operation_id;
+    let not_pending = || Error::new("Not Found", Some("No return awaiting confirmation under this operation"), 404);
+    let release = app.releases.get(op_id).await.ok_or_else(not_pending)?;
+    let store = store.lock().await.in_pool(&release.pool);
+    match (token_header.0.or(input.borrow_token.clone()), owner.filter(|o| o.explicit)) {
+        (Some(token), _) => store.verify_borrow_token(&release.item, &token).await.map_err(Error::from)?,
+        (None, Some(owner)) => {
+            let record = store.get_borrow_record(&release.item).await.map_err(Error::from)?;
+            if !record.is_some_and(|r| r.owner_explicit && r.owner.as_deref() == Some(owner.id.as_str())) {
+                return Err(Error::new("Forbidden", Some("This item is borrowed by another owner"), 403)
+                    .with_context("reason", "owner_mismatch"));
+            }
+        }
+        (None, None) => {
+            return Err(Error::new("Missing borrow token", Some("Send X-Borrow-Token, borrow_token or X-Owner-Id"), 400));
+        }
+    }
+    let release = app.releases.take(op_id).await.ok_or_else(not_pending)?;
+    finish_return(&app.ops, &app.sse, &app.config, &store, op_id, &release.item, release.raw_item.as_deref()).await;
+    observe_resolved_return(&app.ops, &app.metrics, &release.pool, op_id).await;
+    match app.ops.get(op_id).await {
+        Some(op) => {
+            let status = OperationStatusOutput::from(op);
+            Ok(Json(OperationRef { operation_id: op_id.clone(), status: status.status, message: status.message }))
+        }
+        None => Err(Error::new("Not Found", Some("operation not found"), 404)),
+    }
 }
 
 /// Submit an item to the freelist
 ///
 /// Adds an item to the freelist without requiring a borrow token.
 /// This allows items to be added directly to the freelist.
+/// When `restrict_to_known_items` is enabled, only items registered via
+/// `/admin/known-items` are accepted; others get 403 `unknown_item`.
+/// When `submit_strict` is enabled, items already in the freelist or currently
+/// borrowed are rejected with 409 `already_known`.
+/// With `available_until` the item is only available until that unix timestamp; once it
+/// passes, the item is dropped from the freelist unless it is borrowed at that moment.
+/// Honors `Prefer: respond-sync` and `X-Skip-Subscribers` like `/return`.
+/// Optional `pool=<name>` adds the item to that named pool instead of the default one;
+/// it cannot be combined with `available_until`.
+/// An `Idempotency-Key` header makes retries safe, as for `/return`.
 #[openapi]
-#[post("/submit", data = "<input>")]
+#[post("/submit?<pool>", data = "<input>")]
+#[allow(clippy::too_many_arguments)]
 pub async fn submit_item(
-    _store: &State<Mutex<Store>>,
+    store: &State<Mutex<Store>>,
     app: &State<AppState>,
-    input: Json<SubmitInput>,
-) -> OResult<OperationRef> {
+    owner: Option<OwnerId>,
+    prefer: Prefer,
+    skip: SkipSubscribers,
+    idempotency_key: IdempotencyKey,
+    input: ItemJson<SubmitInput>,
+    pool: Option<String>,
+) -> PreferredResult {
+    let key = idempotency_key.scoped("submit");
+    let item = input.item.clone();
+    idempotent(app, key, &item, prefer, |op_id, prefer| {
+        submit_checked(store, app, owner, prefer, skip, input, pool, op_id)
+    })
+    .await
+}
+
+/// `/submit` under the operation id `op_id`
+#[allow(clippy::too_many_arguments)]
+async fn submit_checked(
+    store: &State<Mutex<Store>>,
+    app: &AppState,
+    owner: Option<OwnerId>,
+    prefer: Prefer,
+    skip: SkipSubscribers,
+    input: ItemJson<SubmitInput>,
+    pool: Option<String>,
+    op_id: String,
+) -> PreferredResult {
+    if input.available_until.is_some_and(|until| until <= now_secs()) {
+        return Err(Error::new("Bad Request", Some("available_until is in the past"), 400));
+    }
+    if input.available_until.is_some() && pool.is_some() {
+        return Err(Error::new("Bad Request", Some("available_until cannot be combined with pool"), 400));
+    }
+
     // No borrow token verification needed - direct submission
+    let task_store = {
+        let store = pool_store(store, pool.as_deref()).await?;
+        ensure_known_item(&store, app, &input.item).await?;
+        if app.config.submit_strict && store.is_free_or_borrowed(&input.item).await.map_err(Error::from)? {
+            return Err(Error::new("Conflict", Some("Item is already free or borrowed"), 409)
+                .with_context("reason", "already_known"));
+        }
+        store.clone()
+    };
+    app.metrics.inc_submit(task_store.pool_name());
 
     // Create operation
-    let op_id = uuid::Uuid::new_v4().to_string();
     let op_id_resp = op_id.clone();
     let item_value = input.item.clone();
+    let raw_item = raw_member(app, &input.raw_item);
     let subs = app.subs.clone();
     let ops = app.ops.clone();
     let sse = app.sse.clone();
-    let cfg = app.config.clone();
-    let redis_url = app.redis_url.clone();
-
-    // Spawn workflow in background
-    tokio::spawn(async move {
-        use std::collections::HashSet;
-        // identify must-succeed subscribers
-        let mut must: HashSet<String> = HashSet::new();
-        for (name, def) in &cfg.submit.subscribers {
-            if def.mustSuceed {
-                must.insert(name.clone());
+    let mut cfg = app.config.clone();
+    let skipped = skip_subscribers(&mut cfg.submit.subscribers, &skip);
+
+    // Record the operation before responding so its id is immediately pollable
+    let mut must: HashSet<String> = HashSet::new();
+    for (name, def) in &cfg.submit.subscribers {
+        if def.mustSuceed && def.applies_to(&item_value) {
+            must.insert(name.clone());
+        }
+    }
+    let mut op = Operation::new(op_id.clone(), OperationKind::Submit, item_value.clone(), must);
+    op.message = skipped;
+    op.initiated_by = owner.map(|o| o.id);
+    audit::record_item(app, &task_store, "submit", &item_value, op.initiated_by.as_deref(), Some(&op_id)).await;
+    let _ = ops.insert(op).await;
+    sse.notify(&op_id, serde_json::json!({"event":"created"}).to_string()).await;
+
+    let available_until = input.available_until;
+    let workflow = submit_workflow(subs, ops, sse, cfg, task_store, op_id, item_value, raw_item, available_until);
+    respond(app, prefer, op_id_resp, workflow).await
+}
+
+/// Run an operation's workflow in the background, or inline with `Prefer: respond-sync`,
+/// and describe the operation in the response
+async fn respond(
+    app: &AppState,
+    prefer: Prefer,
+    op_id: String,
+    workflow: impl std::future::Future<Output = ()> + Send + 'static,
+) -> PreferredResult {
+    let output = match prefer.mode() {
+        RespondMode::Async => {
+            tokio::spawn(workflow);
+            OperationRef { operation_id: op_id, status: "accepted".to_string(), message: None }
+        }
+        RespondMode::Sync => {
+            workflow.await;
+            match app.ops.get(&op_id).await {
+                Some(op) => {
+                    let status = OperationStatusOutput::from(op);
+                    OperationRef { operation_id: op_id, status: status.status, message: status.message }
+                }
+                None => return Err(Error::new("Not Found", Some("operation not found"), 404)),
             }
         }
-        let _ = ops.create(op_id.clone(), item_value.clone(), must).await;
-        sse.notify(&op_id, serde_json::json!({"event":"created"}).to_string()).await;
+    };
+    Ok(PreferenceApplied { inner: Json(output), applied: prefer.0 })
+}
 
-        // Run notifications sequentially respecting must-succeed
-        match subs.notify_submit(&cfg, &item_value).await {
-            Ok(()) => {
-                ops.set_status(&op_id, OperationStatus::InProgress).await;
-                sse.notify(&op_id, serde_json::json!({"event":"notifications_ok"}).to_string()).await;
-                let store = Store::new(redis_url);
-                match store.return_item(&item_value) {
-                    Ok(_) => {
-                        ops.set_status(&op_id, OperationStatus::Succeeded).await;
-                        sse.notify(&op_id, serde_json::json!({"event":"completed"}).to_string()).await;
-                    }
-                    Err(e) => {
-                        ops.update_message(&op_id, Some(e.to_string())).await;
-                        ops.set_status(&op_id, OperationStatus::Failed).await;
-                        sse.notify(&op_id, serde_json::json!({"event":"failed","reason":e.to_string()}).to_string()).await;
+/// Notify return subscribers, then put the item back and close the borrow.
+/// With `releases` (two-phase returns) the item is parked there instead, still borrowed,
+/// until the return is confirmed or times out.
+#[allow(clippy::too_many_arguments)]
+async fn return_workflow(
+    subs: Subscribers,
+    ops: OperationStore,
+    sse: Broadcasters,
+    cfg: AppConfig,
+    store: Store,
+    op_id: String,
+    item_value: Value,
+    raw_item: Option<String>,
+    params_value: Option<Value>,
+    releases: Option<PendingReleases>,
+) {
+    // Run notifications sequentially respecting must-succeed
+    match subs.notify_return(&cfg, &item_value, params_value.as_ref(), &op_id).await {
+        Ok(()) => {
+            return_notified(&ops, &sse, &cfg, &store, &op_id, item_value, raw_item, releases.as_ref()).await;
+        }
+        Err(undelivered) => {
+            let entry = RetryEntry {
+                operation_id: op_id,
+                kind: OperationKind::Return,
+                item: item_value,
+                raw_item,
+                params: params_value,
+                pool: store.pool_name().to_string(),
+                available_until: None,
+                two_phase: releases.is_some(),
+                subscribers: Vec::new(),
+                attempt: 0,
+                first_failed_at: now_secs(),
+            };
+            queue_or_fail(&ops, &sse, &cfg, &store, None, entry, &undelivered).await;
+        }
+    }
+}
+
+/// Put the item of a return every subscriber accepted back, or with `releases` park it
+/// until the return is confirmed
+#[allow(clippy::too_many_arguments)]
+async fn return_notified(
+    ops: &OperationStore,
+    sse: &Broadcasters,
+    cfg: &AppConfig,
+    store: &Store,
+    op_id: &str,
+    item_value: Value,
+    raw_item: Option<String>,
+    releases: Option<&PendingReleases>,
+) {
+    ops.set_status(op_id, OperationStatus::InProgress).await;
+    sse.notify(op_id, serde_json::json!({"event":"notifications_ok"}).to_string()).await;
+    if let Some(releases) = releases {
+        let deadline = now_secs() + cfg.return_confirm_timeout_secs();
+        let pool = store.pool_name().to_string();
+        releases.insert(op_id, PendingRelease { item: item_value, raw_item, pool, deadline }).await;
+        ops.update_message(op_id, Some(AWAITING_CONFIRMATION.to_string())).await;
+        sse.notify(op_id, serde_json::json!({"event":"awaiting_confirmation","deadline":deadline}).to_string()).await;
+        return;
+    }
+    finish_return(ops, sse, cfg, store, op_id, &item_value, raw_item.as_deref()).await;
+}
+
+/// How long a sweep holds the queued retries it claimed before another sweep, on any
+/// instance, may claim them again
+const RETRY_CLAIM: Duration = Duration::from_secs(600);
+
+/// Queue another try at the subscribers that did not accept a notification (replacing
+/// the `claimed` entry it came from, if any), leaving the operation in progress. The
+/// operation fails instead when the retry queue is off or full, or the notification has
+/// been retried for `retry_queue.max_age_secs`.
+async fn queue_or_fail(
+    ops: &OperationStore,
+    sse: &Broadcasters,
+    cfg: &AppConfig,
+    store: &Store,
+    claimed: Option<&str>,
+    mut entry: RetryEntry,
+    undelivered: &Undelivered,
+) {
+    let op_id = entry.operation_id.clone();
+    let now = now_secs();
+    entry.subscribers = undelivered.subscribers();
+    let due = now + cfg.retry_queue.delay_secs(entry.attempt + 1);
+    let expired = now.saturating_sub(entry.first_failed_at) >= cfg.retry_queue.max_age_secs;
+    let queued = match (cfg.retry_queue.enabled && !expired, serde_json::to_string(&entry)) {
+        (true, Ok(json)) => match claimed {
+            Some(claimed) => store.reschedule_retry(claimed, &json, due).await.is_ok(),
+            None => store.enqueue_retry(&json, due, cfg.retry_queue.max_entries).await.unwrap_or(false),
+        },
+        _ => false,
+    };
+    if queued {
+        let msg = format!("Retrying {}: {}", entry.subscribers.join(", "), undelivered.message());
+        ops.update_message(&op_id, Some(msg.clone())).await;
+        ops.set_status(&op_id, OperationStatus::InProgress).await;
+        let event = serde_json::json!({
+            "event": "retry_scheduled",
+            "subscribers": entry.subscribers,
+            "attempt": entry.attempt + 1,
+            "next_attempt_at": due,
+            "reason": msg,
+        });
+        sse.notify(&op_id, event.to_string()).await;
+        return;
+    }
+
+    if let Some(claimed) = claimed {
+        let _ = store.finish_retry(claimed).await;
+    }
+    let msg = match entry.attempt {
+        0 => undelivered.message(),
+        retries => format!("{} (gave up after {} retries)", undelivered.message(), retries),
+    };
+    ops.update_message(&op_id, Some(msg.clone())).await;
+    ops.set_status(&op_id, OperationStatus::Failed).await;
+    sse.notify(&op_id, serde_json::json!({"event":"failed","reason":msg}).to_string()).await;
+}
+
+/// Retry the queued return and submit notifications that are due, each in its own task.
+/// Only the subscribers that have not accepted a notification yet are posted to again;
+/// once all have, the return or submit completes as if they had accepted right away.
+pub(crate) async fn retry_queued_notifications(
+    subs: &Subscribers,
+    ops: &OperationStore,
+    sse: &Broadcasters,
+    cfg: &AppConfig,
+    store: &Store,
+    releases: &PendingReleases,
+    metrics: &std::sync::Arc<Metrics>,
+) {
+    if !cfg.retry_queue.enabled {
+        return;
+    }
+    let Ok(claimed) = store.claim_retries(now_secs(), RETRY_CLAIM).await else {
+        return;
+    };
+    for claimed in claimed {
+        let Ok(entry) = serde_json::from_str::<RetryEntry>(&claimed) else {
+            log::warn!("dropping unreadable retry queue entry: {}", claimed);
+            let _ = store.finish_retry(&claimed).await;
+            continue;
+        };
+        let (subs, ops, sse, cfg) = (subs.clone(), ops.clone(), sse.clone(), cfg.clone());
+        let (store, releases, metrics) = (store.in_pool(&entry.pool), releases.clone(), metrics.clone());
+        tokio::spawn(async move {
+            let op_id = entry.operation_id.clone();
+            let outcome = subs
+                .notify_operation(&cfg, entry.kind, &entry.item, entry.params.as_ref(), &op_id, Some(&entry.subscribers))
+                .await;
+            let mut entry = entry;
+            entry.attempt += 1;
+            match outcome {
+                Ok(()) => {
+                    let _ = store.finish_retry(&claimed).await;
+                    match entry.kind {
+                        OperationKind::Return => {
+                            let releases = entry.two_phase.then_some(&releases);
+                            return_notified(&ops, &sse, &cfg, &store, &op_id, entry.item, entry.raw_item, releases)
+                                .await;
+                        }
+                        OperationKind::Submit => {
+                            submit_notified(&ops, &sse, &cfg, &store, &op_id, entry.item, entry.raw_item, entry.available_until)
+                                .await;
+                        }
                     }
                 }
+                Err(undelivered) => queue_or_fail(&ops, &sse, &cfg, &store, Some(&claimed), entry, &undelivered).await,
+            }
+            let finished = ops.get(&op_id).await.is_some_and(|op| op.kind == OperationKind::Return && op.status.is_terminal());
+            if finished {
+                observe_resolved_return(&ops, &metrics, store.pool_name(), &op_id).await;
             }
-            Err((msg, _)) => {
-                ops.update_message(&op_id, Some(msg.clone())).await;
+        });
+    }
+}
+
+/// Message of a two-phase return waiting for `/return/confirm`
+const AWAITING_CONFIRMATION: &str = "Awaiting confirmation";
+
+/// Put a returned item back in the freelist (unless notification-only) and close its borrow
+pub(crate) async fn finish_return(
+    ops: &OperationStore,
+    sse: &Broadcasters,
+    cfg: &AppConfig,
+    store: &Store,
+    op_id: &str,
+    item_value: &Value,
+    raw_item: Option<&str>,
+) {
+    let stored = if cfg.r#return.mutate_freelist {
+        store_item(store, item_value, raw_item).await.map(|_| ())
+    } else {
+        Ok(())
+    };
+    match stored {
+        Ok(_) => {
+            // Remove the borrowed record after successful return
+            let _ = store.remove_borrowed_record(item_value).await;
+            if cfg.r#return.mutate_freelist {
+                announce(store, "returned", item_value).await;
+            }
+            ops.update_message(op_id, None).await;
+            ops.set_status(op_id, OperationStatus::Succeeded).await;
+            sse.notify(op_id, serde_json::json!({"event":"completed"}).to_string()).await;
+        }
+        Err(e) => {
+            ops.update_message(op_id, Some(e.to_string())).await;
+            ops.set_status(op_id, OperationStatus::Failed).await;
+            sse.notify(op_id, serde_json::json!({"event":"failed","reason":e.to_string()}).to_string()).await;
+        }
+    }
+}
+
+/// Reclaim borrows whose lease or borrow token ran out: each item is force returned to the
+/// freelist, the reclaim is logged with the item and owner, and a `lease_expired` (or
+/// `token_expired`) event is published on the borrow's event stream
+/// (`/operations/<borrow_id>/events`). Every pool is swept.
+pub(crate) async fn reclaim_expired_leases(store: &Store, sse: &Broadcasters, subs: &Subscribers) {
+    let Ok(pools) = store.pool_names().await else {
+        return;
+    };
+    for pool in pools {
+        reclaim_pool_leases(&store.in_pool(&pool), sse, subs).await;
+    }
+}
+
+async fn reclaim_pool_leases(store: &Store, sse: &Broadcasters, subs: &Subscribers) {
+    let now = now_secs();
+    let Ok(tokens) = store.take_expired_leases(now).await else {
+        return;
+    };
+    for token in tokens {
+        let record = match store.item_for_token(&token).await {
+            Ok(Some(item)) => store.get_borrow_record(&item).await.ok().flatten(),
+            _ => None,
+        };
+        // No item means the borrow already ended
+        let Ok(Some(item)) = store.force_return_by_token(&token).await else {
+            continue;
+        };
+        subs.cancel_deferred_borrow(&token).await;
+        announce(store, "returned", &item).await;
+        let owner = record.as_ref().and_then(|r| r.owner.clone());
+        // Reclaimed while its lease still ran, so the borrow token expired first
+        let token_expired = record
+            .as_ref()
+            .is_some_and(|r| r.token_expires_at.is_some() && r.lease_expires_at.is_none_or(|at| at > now));
+        let (expired, event) =
+            if token_expired { ("token", "token_expired") } else { ("lease", "lease_expired") };
+        log::warn!("borrow {} expired, item reclaimed: item={} owner={:?}", expired, item, owner);
+        if let Some(record) = record {
+            let event = serde_json::json!({"event": event, "item": item, "owner": owner});
+            sse.notify(&record.borrow_id, event.to_string()).await;
+        }
+    }
+}
+
+/// Resolve two-phase returns whose confirmation window ran out, per `return_timeout_action`
+pub(crate) async fn time_out_releases(
+    releases: &PendingReleases,
+    ops: &OperationStore,
+    sse: &Broadcasters,
+    cfg: &AppConfig,
+    store: &Store,
+    metrics: &Metrics,
+) {
+    for (op_id, release) in releases.take_expired(now_secs()).await {
+        match cfg.return_timeout_action {
+            ReturnTimeoutAction::Confirm => {
+                let store = store.in_pool(&release.pool);
+                finish_return(ops, sse, cfg, &store, &op_id, &release.item, release.raw_item.as_deref()).await;
+            }
+            ReturnTimeoutAction::Rollback => {
+                let reason = "Return was not confirmed in time; the item stays borrowed";
+                ops.update_message(&op_id, Some(reason.to_string())).await;
                 ops.set_status(&op_id, OperationStatus::Failed).await;
-                sse.notify(&op_id, serde_json::json!({"event":"failed","reason":msg}).to_string()).await;
+                sse.notify(&op_id, serde_json::json!({"event":"failed","reason":reason}).to_string()).await;
             }
         }
-    });
+        observe_resolved_return(ops, metrics, &release.pool, &op_id).await;
+    }
+}
 
-    Ok(Json(OperationRef { operation_id: op_id_resp, status: "accepted".to_string() }))
+/// Notify submit subscribers, then add the item to the freelist
+#[allow(clippy::too_many_arguments)]
+async fn submit_workflow(
+    subs: Subscribers,
+    ops: OperationStore,
+    sse: Broadcasters,
+    cfg: AppConfig,
+    store: Store,
+    op_id: String,
+    item_value: Value,
+    raw_item: Option<String>,
+    available_until: Option<u64>,
+) {
+    // Run notifications sequentially respecting must-succeed
+    match subs.notify_submit(&cfg, &item_value, &op_id).await {
+        Ok(()) => submit_notified(&ops, &sse, &cfg, &store, &op_id, item_value, raw_item, available_until).await,
+        Err(undelivered) => {
+            let entry = RetryEntry {
+                operation_id: op_id,
+                kind: OperationKind::Submit,
+                item: item_value,
+                raw_item,
+                params: None,
+                pool: store.pool_name().to_string(),
+                available_until,
+                two_phase: false,
+                subscribers: Vec::new(),
+                attempt: 0,
+                first_failed_at: now_secs(),
+            };
+            queue_or_fail(&ops, &sse, &cfg, &store, None, entry, &undelivered).await;
+        }
+    }
+}
+
+/// Add the item of a submit every subscriber accepted to the freelist
+#[allow(clippy::too_many_arguments)]
+async fn submit_notified(
+    ops: &OperationStore,
+    sse: &Broadcasters,
+    cfg: &AppConfig,
+    store: &Store,
+    op_id: &str,
+    item_value: Value,
+    raw_item: Option<String>,
+    available_until: Option<u64>,
+) {
+    ops.set_status(op_id, OperationStatus::InProgress).await;
+    sse.notify(op_id, serde_json::json!({"event":"notifications_ok"}).to_string()).await;
+    let stored = if cfg.submit.mutate_freelist {
+        match store_item(store, &item_value, raw_item.as_deref()).await {
+            Ok(added) => match available_until {
+                Some(until) => store
+                    .set_available_until(&item_value, raw_item.as_deref(), until)
+                    .await
+                    .map(|_| Some(added)),
+                None => Ok(Some(added)),
+            },
+            Err(e) => Err(e),
+        }
+    } else {
+        Ok(None)
+    };
+    match stored {
+        Ok(added) => {
+            if let Some(added) = added {
+                ops.set_newly_added(op_id, added).await;
+                announce(store, "submitted", &item_value).await;
+            }
+            ops.set_status(op_id, OperationStatus::Succeeded).await;
+            sse.notify(op_id, serde_json::json!({"event":"completed"}).to_string()).await;
+        }
+        Err(e) => {
+            ops.update_message(op_id, Some(e.to_string())).await;
+            ops.set_status(op_id, OperationStatus::Failed).await;
+            sse.notify(op_id, serde_json::json!({"event":"failed","reason":e.to_string()}).to_string()).await;
+        }
+    }
+}
+
+/// Remove the subscribers an operator skipped from this operation's copy of the config.
+/// Returns the operation message recording which were skipped, if any.
+fn skip_subscribers(subscribers: &mut HashMap<String, SubscriberDef>, skip: &SkipSubscribers) -> Option<String> {
+    let mut skipped: Vec<&str> = skip.0.iter().filter(|name| subscribers.remove(*name).is_some()).map(String::as_str).collect();
+    if skipped.is_empty() {
+        return None;
+    }
+    skipped.sort();
+    log::warn!("skipping subscribers for one operation on operator request: {}", skipped.join(","));
+    Some(format!("Skipped subscribers: {}", skipped.join(", ")))
+}
+
+/// The exact item text to store, when items are kept in their raw form
+fn raw_member(app: &AppState, raw_item: &str) -> Option<String> {
+    (app.config.item_encoding == ItemEncoding::Raw).then(|| raw_item.to_string())
+}
+
+/// Add an item to the freelist, verbatim when its raw text is given; true if it was new
+/// A handle on the `pool` query parameter's pool, or the store's own pool without one
+pub(crate) async fn pool_store(store: &Mutex<Store>, pool: Option<&str>) -> Result<Store, Error> {
+    match pool {
+        None => Ok(store.lock().await.clone()),
+        Some(pool) if is_valid_pool_name(pool) => Ok(store.lock().await.in_pool(pool)),
+        Some(pool) => Err(Error::new("Bad Request", Some("Invalid pool name"), 400).with_context("pool", pool)),
+    }
+}
+
+/// Publish a freelist mutation for `/admin/events`; a failure only loses the event
+pub(crate) async fn announce(store: &Store, event: &str, item: &Value) {
+    if let Err(e) = store.publish_freelist_event(event, item).await {
+        log::warn!("failed to publish {} event: item={} error={}", event, item, e);
+    }
+}
+
+async fn store_item(store: &Store, item: &Value, raw_item: Option<&str>) -> redis::RedisResult<bool> {
+    // A deadline from an earlier submit does not carry over; submits set their own after this
+    store.clear_available_until(item, raw_item).await?;
+    match raw_item {
+        Some(raw) => store.return_raw(raw).await,
+        None => store.return_item(item).await,
+    }
+}
+
+/// The `item` of a borrow response: the stored text in raw mode, compact JSON otherwise
+fn item_output(app: &AppState, item: &Value, member: &str) -> Result<Box<RawValue>, Error> {
+    match app.config.item_encoding {
+        ItemEncoding::Raw => RawValue::from_string(member.to_string())
+            .map_err(|e| Error::new("Invalid item", Some(&e.to_string()), 500)),
+        ItemEncoding::Canonical => compact_item(item),
+    }
+}
+
+fn compact_item(item: &Value) -> Result<Box<RawValue>, Error> {
+    serde_json::value::to_raw_value(item).map_err(|e| Error::new("Invalid item", Some(&e.to_string()), 500))
+}
+
+/// Count and log a borrow whose item was put back after a failure; for subscriber
+/// failures `detail` names the subscriber
+fn rollback_borrow(app: &AppState, pool: &str, reason: &str, member: &str, detail: &str) {
+    app.metrics.inc_borrow_rollback(pool);
+    log::warn!("borrow rolled back: reason={} item={} detail={:?}", reason, member, detail);
+}
+
+/// When the pool is restricted to known items, reject anything outside that set
+async fn ensure_known_item(store: &Store, app: &AppState, item: &Value) -> Result<(), Error> {
+    if !app.config.restrict_to_known_items {
+        return Ok(());
+    }
+    if store.is_known_item(item).await.map_err(Error::from)? {
+        Ok(())
+    } else {
+        Err(Error::new("Forbidden", Some("Item is not part of this pool"), 403)
+            .with_context("error", "unknown_item"))
+    }
 }
 
 // list endpoint removed
@@ -259,25 +2081,100 @@ pub async fn submit_item(
 #[get("/operations/<id>")]
 pub async fn get_operation_status(app: &State<AppState>, id: &str) -> OResult<OperationStatusOutput> {
     if let Some(op) = app.ops.get(id).await {
-        Ok(Json(OperationStatusOutput {
-            operation_id: op.id,
-            status: format!("{:?}", op.status).to_lowercase(),
-            message: op.message,
-        }))
+        Ok(Json(OperationStatusOutput::from(op)))
     } else {
         Err(Error::new("Not Found", Some("operation not found"), 404))
     }
 }
 
-/// Subscribe to Server-Sent Events for an operation
-#[get("/operations/<id>/events")] 
-pub async fn stream_operation_events(app: &State<AppState>, id: &str) -> EventStream![] {
+/// Long-poll the status of an async operation
+///
+/// Blocks until the operation reaches a terminal state (`succeeded` or `failed`) or
+/// `timeout` seconds elapse (default 30, at most 300), then returns the current status.
+/// When the timeout elapses first, the response is still 200 with `timed_out: true`.
+/// This is an alternative to the SSE stream for clients behind proxies that buffer it.
+#[openapi]
+#[get("/operations/<id>/wait?<timeout>")]
+pub async fn wait_operation_status(
+    app: &State<AppState>,
+    id: &str,
+    timeout: Option<u64>,
+) -> OResult<OperationStatusOutput> {
+    let timeout = Duration::from_secs(timeout.unwrap_or(30).min(300));
+    let deadline = tokio::time::Instant::now() + timeout;
+
+    // Subscribe before reading the status so no transition is missed in between
     let mut rx = app.sse.subscribe(id).await;
+    loop {
+        let op = match app.ops.get(id).await {
+            Some(op) => op,
+            None => return Err(Error::new("Not Found", Some("operation not found"), 404)),
+        };
+        if op.status.is_terminal() {
+            let mut output = OperationStatusOutput::from(op);
+            output.timed_out = Some(false);
+            return Ok(Json(output));
+        }
+
+        match tokio::time::timeout_at(deadline, rx.recv()).await {
+            // Any event (or a lagged receiver) may mean the status changed; re-check it
+            Ok(Ok(_)) | Ok(Err(tokio::sync::broadcast::error::RecvError::Lagged(_))) => continue,
+            Ok(Err(tokio::sync::broadcast::error::RecvError::Closed)) | Err(_) => {
+                let op = match app.ops.get(id).await {
+                    Some(op) => op,
+                    None => return Err(Error::new("Not Found", Some("operation not found"), 404)),
+                };
+                let timed_out = !op.status.is_terminal();
+                let mut output = OperationStatusOutput::from(op);
+                output.timed_out = Some(timed_out);
+                return Ok(Json(output));
+            }
+        }
+    }
+}
+
+/// Subscribe to Server-Sent Events for an operation
+///
+/// Each event carries an increasing id. With `since=<event id>` the buffered events after
+/// that id are replayed before streaming live (`since=0` replays from the beginning), so a
+/// late subscriber can reconstruct the full history. A `Last-Event-ID` header, as sent by a
+/// reconnecting `EventSource`, takes precedence over `since`.
+#[get("/operations/<id>/events?<since>")]
+pub async fn stream_operation_events(
+    app: &State<AppState>,
+    id: &str,
+    since: Option<u64>,
+    last_event_id: LastEventId,
+) -> EventStream![] {
+    let (replay, mut rx) = match last_event_id.0.or(since) {
+        Some(since) => app.sse.subscribe_since(id, since).await,
+        None => (Vec::new(), app.sse.subscribe(id).await),
+    };
+    let ops = app.ops.clone();
+    let verbose = app.config.sse_event_format == SseEventFormat::Verbose;
+    let max_bytes = app.config.sse_max_event_bytes;
+    let id = id.to_string();
+    // Verbose events carry the operation as it is when the event goes out
+    let render = move |data: String| {
+        let ops = ops.clone();
+        let id = id.clone();
+        async move {
+            let op = if verbose { ops.get(&id).await } else { None };
+            let data = match op {
+                Some(op) => with_operation(data, &op),
+                None => data,
+            };
+            cap_event(data, max_bytes)
+        }
+    };
     EventStream! {
+        for event in replay {
+            yield Event::data(render(event.data).await).id(event.id.to_string());
+        }
         let mut ping = interval(Duration::from_secs(15));
         loop {
             tokio::select! {
-                Ok(msg) = rx.recv() => yield Event::data(msg),
+                Ok(event) = rx.recv() => yield Event::data(render(event.data).await).id(event.id.to_string()),
                 _ = ping.tick() => yield Event::data("ping"),
             }
         }
@@ -0,0 +1,56 @@
+use rocket::http::Status;
+use rocket::serde::json::Json;
+use rocket::State;
+use serde::Serialize;
+use tokio::sync::Mutex;
+
+use crate::store::Store;
+use crate::AppState;
+
+#[derive(Serialize)]
+pub struct Readiness {
+    ready: bool,
+    /// Why the instance is not ready; empty when it is
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    problems: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct Health {
+    status: &'static str,
+    redis: &'static str,
+}
+
+/// Health check for load balancers: a single Redis PING, 503 while it fails
+#[get("/health")]
+pub async fn health(store: &State<Mutex<Store>>) -> (Status, Json<Health>) {
+    let store = store.lock().await.clone();
+    match store.test_connection().await {
+        Ok(()) => (Status::Ok, Json(Health { status: "ok", redis: "up" })),
+        Err(_) => (Status::ServiceUnavailable, Json(Health { status: "unavailable", redis: "down" })),
+    }
+}
+
+/// Liveness probe: answers as long as the process serves requests
+#[get("/livez")]
+pub fn livez() -> &'static str {
+    "ok"
+}
+
+/// Readiness probe: 503 while Redis is unreachable, or with
+/// `readiness_requires_subscribers` while an enabled must-succeed subscriber is
+/// unreachable, so the orchestrator keeps the instance out of rotation.
+#[get("/readyz")]
+pub async fn readyz(app: &State<AppState>, store: &State<Mutex<Store>>) -> (Status, Json<Readiness>) {
+    let store = store.lock().await.clone();
+    let mut problems = Vec::new();
+    if let Err(e) = store.test_connection().await {
+        problems.push(format!("redis: {}", e));
+    }
+    if app.config.readiness_requires_subscribers {
+        problems.extend(app.subs.unreachable_must_succeed(&app.config).await);
+    }
+
+    let status = if problems.is_empty() { Status::Ok } else { Status::ServiceUnavailable };
+    (status, Json(Readiness { ready: problems.is_empty(), problems }))
+}
@@ -2,49 +2,140 @@
 extern crate rocket;
 
 // Re-export the rocket builder function for integration tests
+mod audit;
 mod error;
 mod handlers;
 mod guards;
 mod subscribers;
 mod ops;
+mod logging;
+mod load_shed;
+mod health;
+mod metrics;
+mod waiters;
+#[cfg(unix)]
+mod unix_socket;
 
 // Re-export these modules for use in main.rs
 pub mod store;
 pub mod config;
 
-use rocket_okapi::settings::UrlObject;
+use rocket_okapi::okapi::openapi3::{OpenApi, Server};
+use rocket_okapi::settings::{OpenApiSettings, UrlObject};
 use rocket_okapi::swagger_ui::make_swagger_ui;
-use rocket_okapi::{openapi_get_routes, rapidoc::*, swagger_ui::*};
+use rocket_okapi::{get_openapi_route, openapi_get_routes_spec, rapidoc::*, swagger_ui::*};
+use rocket::fairing::AdHoc;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::Mutex;
 
 use crate::store::Store;
 
-/// Generate and print the OpenAPI specification
-pub fn print_openapi_spec() {
-    let settings = rocket_okapi::settings::OpenApiSettings::new();
-    let spec = rocket_okapi::openapi_spec![
+/// How often expired batch reservations and finished operations past their TTL are swept
+const EXPIRY_SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Routes documented in the OpenAPI spec, together with the stamped spec
+fn api_routes() -> (Vec<rocket::Route>, OpenApi) {
+    let settings = OpenApiSettings::new();
+    let (routes, mut spec) = openapi_get_routes_spec![settings:
         handlers::ip::borrow,
+        handlers::ip::borrow_available,
+        handlers::ip::borrow_or_submit,
+        handlers::ip::borrow_batch,
+        handlers::ip::borrow_specific,
+        handlers::ip::return_batch,
+        handlers::ip::peek,
+        handlers::ip::reserve_batch,
+        handlers::ip::commit_batch,
+        handlers::ip::abort_batch,
+        handlers::ip::verify_borrow,
+        handlers::ip::list_free_items,
         handlers::ip::return_item,
+        handlers::ip::confirm_return,
         handlers::ip::submit_item,
         handlers::ip::get_operation_status,
+        handlers::ip::wait_operation_status,
         handlers::admin::list_items,
         handlers::admin::list_borrowed,
+        handlers::admin::list_borrow_tokens,
+        handlers::admin::list_borrowed_by_owner,
+        handlers::admin::set_items,
         handlers::admin::delete_item,
+        handlers::admin::set_item_metadata,
+        handlers::admin::get_item_metadata,
+        handlers::admin::transfer_item,
+        handlers::admin::rebuild_indexes,
+        handlers::admin::migrate,
         handlers::admin::force_return,
+        handlers::admin::force_return_by_token,
         handlers::admin::delete_borrowed_item,
         handlers::admin::list_operations,
+        handlers::admin::operations_summary,
+        handlers::admin::list_waiters,
         handlers::admin::delete_operation,
+        handlers::admin::resolve_operation,
+        handlers::admin::export_operations,
+        handlers::admin::import_operations,
         handlers::admin::get_stats,
-    ](&settings);
-    println!("{}", serde_json::to_string_pretty(&spec).unwrap());
+        handlers::admin::list_subscribers,
+        handlers::admin::disable_subscriber,
+        handlers::admin::enable_subscriber,
+        handlers::admin::replay_dead_letters,
+        handlers::admin::add_known_items,
+        handlers::admin::list_audit,
+        handlers::admin::list_item_audit,
+        handlers::admin::seed_cidr,
+        handlers::admin::issue_stream_token,
+    ];
+    stamp_spec(&mut spec);
+    (routes, spec)
+}
+
+/// Tie the spec to this crate's version so generated clients can track compatibility
+fn stamp_spec(spec: &mut OpenApi) {
+    spec.info.title = "IP Allocator Web Server".to_owned();
+    spec.info.version = env!("CARGO_PKG_VERSION").to_owned();
+    spec.info.description = Some(
+        "Borrow, return and submit items from a Redis-backed freelist, \
+         with webhook subscribers notified on every operation."
+            .to_owned(),
+    );
+    spec.servers = vec![Server {
+        url: "/".to_owned(),
+        description: Some("The server hosting this specification".to_owned()),
+        ..Default::default()
+    }];
+}
+
+/// Generate the OpenAPI specification as pretty-printed JSON
+pub fn openapi_spec_json() -> String {
+    serde_json::to_string_pretty(&api_routes().1).unwrap()
+}
+
+/// Generate and print the OpenAPI specification
+pub fn print_openapi_spec() {
+    println!("{}", openapi_spec_json());
+}
+
+/// Load and validate a config file without touching Redis, for `--validate-config`.
+/// Returns the problems found, including a file that cannot be read or parsed.
+pub fn validate_config_file(path: &std::path::Path) -> Result<(), Vec<String>> {
+    let cfg = config::AppConfig::from_path(path).map_err(|e| vec![format!("cannot load config: {:#}", e)])?;
+    cfg.validate()
 }
 
 pub struct AppState {
-    redis_url: String,
     config: config::AppConfig,
     subs: subscribers::Subscribers,
     ops: ops::OperationStore,
     sse: ops::Broadcasters,
+    /// `/borrow?wait` requests currently parked
+    waiters: waiters::WaitQueue,
+    metrics: Arc<metrics::Metrics>,
+    /// Two-phase returns awaiting confirmation
+    releases: ops::PendingReleases,
+    /// Tokens authorizing `/admin/events`
+    stream_tokens: guards::stream_token::StreamTokens,
 }
 
 /// Build and configure the Rocket instance
@@ -55,48 +146,146 @@ pub fn rocket(redis_url: String) -> rocket::Rocket<rocket::Build> {
 
 /// Build and configure the Rocket instance with custom config
 pub fn rocket_with_config(redis_url: String, app_config: config::AppConfig) -> rocket::Rocket<rocket::Build> {
-    let store = Store::new(redis_url.clone());
-    let subs = subscribers::Subscribers::new();
-    let ops = ops::OperationStore::new();
+    let summary_redis_url = redis_url.clone();
+    let store = Store::new(app_config.redis_connection_url(&redis_url))
+        .with_sentinel(app_config.redis_sentinel.clone())
+        .with_key_prefix(&app_config.key_prefix)
+        .with_pool_size(app_config.redis_pool_size())
+        .with_pool_wait(app_config.redis_pool_wait())
+        .with_scripts_only(app_config.redis_scripts_only)
+        .with_deterministic_borrow(app_config.deterministic_borrow)
+        .with_indexed_fields(app_config.indexed_fields.clone())
+        .with_token_max_lifetime(app_config.token_max_lifetime_secs.map(Duration::from_secs))
+        .with_event_channels(app_config.event_channels());
+    let deterministic_borrow = app_config.deterministic_borrow;
+    let summary_config = app_config.clone();
+    let sweeper_store = store.clone();
+    let ops = ops::OperationStore::new()
+        .with_ttl(app_config.operation_ttl_secs().map(|secs| ops::OperationTtl {
+            base: Duration::from_secs(secs),
+            jitter_pct: app_config.ttl_jitter_pct(),
+        }))
+        .with_idempotency_key_ttl(Duration::from_secs(app_config.idempotency_key_ttl_secs()))
+        .with_redis(store.clone());
+    let sweeper_ops = ops.clone();
     let sse = ops::Broadcasters::new();
+    let sweeper_sse = sse.clone();
+    let metrics = Arc::new(metrics::Metrics::new());
+    let subs = subscribers::Subscribers::from_config(&app_config)
+        .with_events(sse.clone())
+        .with_metrics(metrics.clone());
+    let releases = ops::PendingReleases::new().with_redis(store.clone());
+    let sweeper_releases = releases.clone();
+    let sweeper_config = app_config.clone();
+    let sweeper_metrics = metrics.clone();
+    let sweeper_subs = subs.clone();
+    let lease_warning = app_config.lease_warning_secs.map(Duration::from_secs);
+    let lease_warning_url = app_config.lease_warning_url.clone();
+    let (api_routes, spec) = api_routes();
+    let load_shedder = app_config.max_in_flight_requests.map(load_shed::LoadShedder::new);
+    #[cfg(unix)]
+    let unix_socket = app_config
+        .unix_socket
+        .clone()
+        .map(|path| unix_socket::UnixSocketListener { path });
+    let request_logger = app_config.request_logging.enabled.then(|| logging::RequestLogger {
+        redact_fields: app_config.request_logging.redact_fields.clone(),
+        max_body_bytes: app_config.request_logging.max_body_bytes,
+    });
 
-    rocket::build()
+    let mut rocket = rocket::build()
         .configure(rocket::Config {
             address: "0.0.0.0".parse().expect("valid IP address"),
             port: 8000,
             ..rocket::Config::default()
         })
         .manage(AppState {
-            redis_url,
             config: app_config,
             subs,
             ops,
             sse,
+            waiters: waiters::WaitQueue::new(),
+            metrics,
+            releases,
+            stream_tokens: guards::stream_token::StreamTokens::new(),
         })
         .manage(Mutex::new(store))
-        .mount(
-            "/",
-            openapi_get_routes![
-                handlers::ip::borrow,
-                handlers::ip::return_item,
-                handlers::ip::submit_item,
-                handlers::ip::get_operation_status,
-                handlers::admin::list_items,
-                handlers::admin::list_borrowed,
-                handlers::admin::delete_item,
-                handlers::admin::force_return,
-                handlers::admin::delete_borrowed_item,
-                handlers::admin::list_operations,
-                handlers::admin::delete_operation,
-                handlers::admin::get_stats,
-            ],
-        )
+        .attach(AdHoc::on_liftoff("Startup summary", move |rocket| {
+            let config = rocket.config();
+            let summary = summary_config.startup_summary(&summary_redis_url, &config.address.to_string(), config.port);
+            Box::pin(async move {
+                log::info!("effective configuration:\n{}", summary);
+            })
+        }))
+        .attach(AdHoc::on_liftoff("Expiry sweeper", move |_| {
+            Box::pin(async move {
+                // Requests also sweep reservations lazily; this catches ones nobody touches again
+                rocket::tokio::spawn(async move {
+                    let http = reqwest::Client::new();
+                    let mut tick = rocket::tokio::time::interval(EXPIRY_SWEEP_INTERVAL);
+                    loop {
+                        tick.tick().await;
+                        if let Some(window) = lease_warning {
+                            let now = store::now_secs();
+                            let mut warnings =
+                                sweeper_store.warn_expiring_reservations(now, window).await.unwrap_or_default();
+                            for pool in sweeper_store.pool_names().await.unwrap_or_default() {
+                                let pool = sweeper_store.in_pool(&pool);
+                                warnings.extend(pool.warn_expiring_leases(now, window).await.unwrap_or_default());
+                            }
+                            if let Some(url) = &lease_warning_url {
+                                for warning in warnings {
+                                    let _ = http
+                                        .post(url)
+                                        .header(reqwest::header::CONTENT_TYPE, "application/json")
+                                        .body(warning)
+                                        .send()
+                                        .await;
+                                }
+                            }
+                        }
+                        let _ = sweeper_store.expire_reservations().await;
+                        let _ = sweeper_store.expire_available_items(store::now_secs()).await;
+                        handlers::ip::reclaim_expired_leases(&sweeper_store, &sweeper_sse, &sweeper_subs).await;
+                        handlers::ip::time_out_releases(
+                            &sweeper_releases,
+                            &sweeper_ops,
+                            &sweeper_sse,
+                            &sweeper_config,
+                            &sweeper_store,
+                            &sweeper_metrics,
+                        )
+                        .await;
+                        handlers::ip::retry_queued_notifications(
+                            &sweeper_subs,
+                            &sweeper_ops,
+                            &sweeper_sse,
+                            &sweeper_config,
+                            &sweeper_store,
+                            &sweeper_releases,
+                            &sweeper_metrics,
+                        )
+                        .await;
+                        sweeper_ops.purge_expired().await;
+                    }
+                });
+            })
+        }))
+        .register("/", catchers![guards::item_json::invalid_json])
+        .mount("/", api_routes)
+        .mount("/", vec![get_openapi_route(spec, &OpenApiSettings::new())])
         .mount(
             "/",
             routes![
                 handlers::ip::stream_operation_events,
+                handlers::admin::stream_admin_events,
                 handlers::admin::admin_ui,
                 handlers::admin::admin_favicon,
+                load_shed::overloaded,
+                metrics::metrics,
+                health::health,
+                health::livez,
+                health::readyz,
             ],
         )
         .mount(
@@ -120,5 +309,24 @@ pub fn rocket_with_config(redis_url: String, app_config: config::AppConfig) -> r
                 },
                 ..Default::default()
             }),
-        )
+        );
+
+    if let Some(shedder) = load_shedder {
+        rocket = rocket.attach(shedder);
+    }
+    if let Some(logger) = request_logger {
+        rocket = rocket.attach(logger);
+    }
+    #[cfg(unix)]
+    if let Some(listener) = unix_socket {
+        rocket = rocket.attach(listener);
+    }
+    if deterministic_borrow {
+        rocket = rocket.attach(AdHoc::on_liftoff("Deterministic borrow warning", |_| {
+            Box::pin(async {
+                log::warn!("deterministic_borrow is enabled: borrows are predictable; this mode is for tests only");
+            })
+        }));
+    }
+    rocket
 }
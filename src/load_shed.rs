@@ -0,0 +1,65 @@
+use std::sync::Arc;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::Method;
+use rocket::{Data, Request};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::Error;
+
+/// Probe endpoints that must keep answering under load
+const EXEMPT_PATHS: &[&str] = &["/health", "/livez", "/readyz", "/metrics"];
+
+/// Internal route requests are rerouted to when shed
+const OVERLOADED_PATH: &str = "/__overloaded";
+
+/// Held in the request's local cache, so the slot frees when the request is dropped
+struct InFlightPermit {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Caps the number of requests in flight. Requests beyond the cap are answered with
+/// a fast 503 `overloaded` instead of queuing; probe endpoints are never shed.
+pub struct LoadShedder {
+    permits: Arc<Semaphore>,
+}
+
+impl LoadShedder {
+    pub fn new(max_in_flight: usize) -> Self {
+        Self { permits: Arc::new(Semaphore::new(max_in_flight)) }
+    }
+}
+
+#[rocket::async_trait]
+impl Fairing for LoadShedder {
+    fn info(&self) -> Info {
+        Info {
+            name: "Load shedder",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, _data: &mut Data<'_>) {
+        if EXEMPT_PATHS.contains(&req.uri().path().as_str()) {
+            return;
+        }
+        match self.permits.clone().try_acquire_owned() {
+            Ok(permit) => {
+                req.local_cache(|| InFlightPermit { _permit: permit });
+            }
+            Err(_) => {
+                req.set_method(Method::Get);
+                req.set_uri(Origin::parse(OVERLOADED_PATH).expect("valid path"));
+            }
+        }
+    }
+}
+
+/// Response for requests shed by [`LoadShedder`]
+#[get("/__overloaded")]
+pub fn overloaded() -> Error {
+    Error::new("Service Unavailable", Some("Too many requests in flight"), 503)
+        .with_context("error", "overloaded")
+        .with_retry_after(1)
+}
@@ -0,0 +1,164 @@
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::http::uri::Origin;
+use rocket::http::RawStr;
+use rocket::{Data, Request};
+use serde_json::Value;
+
+/// Replacement for redacted values
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Rocket only buffers this many bytes for peeking at a request body
+const MAX_PEEK_BYTES: usize = 512;
+
+/// Query parameters carrying credentials, redacted whatever `redact_fields` says:
+/// borrow tokens and the `/admin/events` stream token
+const CREDENTIAL_PARAMS: &[&str] = &["borrow_token", "token"];
+
+/// Replace the value of every object key matching one of `fields`, at any depth.
+///
+/// A key matches when it contains a field name, case-insensitively, so `secret`
+/// also covers `hmac_secret` and `client_secret`.
+pub fn redact_json(value: &Value, fields: &[String]) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.iter()
+                .map(|(key, v)| {
+                    let lower = key.to_lowercase();
+                    if fields.iter().any(|f| lower.contains(&f.to_lowercase())) {
+                        (key.clone(), Value::String(REDACTED.to_owned()))
+                    } else {
+                        (key.clone(), redact_json(v, fields))
+                    }
+                })
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(|v| redact_json(v, fields)).collect()),
+        other => other.clone(),
+    }
+}
+
+/// The request target with the values of credential query parameters, and of those
+/// whose name matches one of `fields` (as in [`redact_json`]), replaced
+pub fn redact_uri(uri: &Origin<'_>, fields: &[String]) -> String {
+    let Some(query) = uri.query() else {
+        return uri.to_string();
+    };
+    let pairs: Vec<String> = query
+        .as_str()
+        .split('&')
+        .map(|pair| {
+            let (key, _) = pair.split_once('=').unwrap_or((pair, ""));
+            let name = RawStr::new(key).url_decode_lossy().to_lowercase();
+            let sensitive = CREDENTIAL_PARAMS.contains(&name.as_str())
+                || fields.iter().any(|f| name.contains(&f.to_lowercase()));
+            match sensitive {
+                true => format!("{}={}", key, REDACTED),
+                false => pair.to_string(),
+            }
+        })
+        .collect();
+    format!("{}?{}", uri.path(), pairs.join("&"))
+}
+
+/// Logs each request line, with credentials in the query redacted, and, for JSON bodies,
+/// the body with sensitive fields redacted.
+///
+/// Bodies that are not complete, valid JSON within `max_body_bytes` are never logged
+/// verbatim since they cannot be redacted reliably; only their size is reported.
+pub struct RequestLogger {
+    pub redact_fields: Vec<String>,
+    pub max_body_bytes: usize,
+}
+
+#[rocket::async_trait]
+impl Fairing for RequestLogger {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request logger",
+            kind: Kind::Request,
+        }
+    }
+
+    async fn on_request(&self, req: &mut Request<'_>, data: &mut Data<'_>) {
+        let is_json = req.content_type().map(|ct| ct.is_json()).unwrap_or(false);
+        if !is_json || self.max_body_bytes == 0 {
+            log::info!("{} {}", req.method(), redact_uri(req.uri(), &self.redact_fields));
+            return;
+        }
+
+        let limit = self.max_body_bytes.min(MAX_PEEK_BYTES);
+        let len = data.peek(limit).await.len();
+        let complete = len < limit || data.peek_complete();
+        let peeked = data.peek(limit).await;
+        let body = match serde_json::from_slice::<Value>(peeked) {
+            Ok(value) if complete => redact_json(&value, &self.redact_fields).to_string(),
+            _ => format!("<{} bytes not logged>", peeked.len()),
+        };
+        log::info!("{} {} body={}", req.method(), redact_uri(req.uri(), &self.redact_fields), body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fields() -> Vec<String> {
+        vec!["borrow_token".to_string(), "secret".to_string()]
+    }
+
+    #[test]
+    fn redacts_top_level_fields() {
+        let input = json!({"item": {"ip": "10.0.0.1"}, "borrow_token": "abc"});
+        let redacted = redact_json(&input, &fields());
+        assert_eq!(redacted["borrow_token"], REDACTED);
+        assert_eq!(redacted["item"]["ip"], "10.0.0.1");
+    }
+
+    #[test]
+    fn redacts_nested_fields_and_arrays() {
+        let input = json!({
+            "returns": [
+                {"item": {"ip": "10.0.0.1"}, "borrow_token": "one"},
+                {"item": {"ip": "10.0.0.2", "hmac_secret": "s"}, "borrow_token": "two"}
+            ],
+            "params": {"auth": {"client_secret": "x", "user": "u"}}
+        });
+        let redacted = redact_json(&input, &fields());
+        assert_eq!(redacted["returns"][0]["borrow_token"], REDACTED);
+        assert_eq!(redacted["returns"][1]["borrow_token"], REDACTED);
+        assert_eq!(redacted["returns"][1]["item"]["hmac_secret"], REDACTED);
+        assert_eq!(redacted["returns"][1]["item"]["ip"], "10.0.0.2");
+        assert_eq!(redacted["params"]["auth"]["client_secret"], REDACTED);
+        assert_eq!(redacted["params"]["auth"]["user"], "u");
+    }
+
+    #[test]
+    fn matching_is_case_insensitive() {
+        let input = json!({"Borrow_Token": "abc", "SECRET": "x"});
+        let redacted = redact_json(&input, &fields());
+        assert_eq!(redacted, json!({"Borrow_Token": REDACTED, "SECRET": REDACTED}));
+    }
+
+    #[test]
+    fn redacts_credentials_in_the_query() {
+        let uri = Origin::parse("/borrow/verify?item=%7B%7D&borrow_token=abc").unwrap();
+        assert_eq!(redact_uri(&uri, &[]), format!("/borrow/verify?item=%7B%7D&borrow_token={}", REDACTED));
+
+        let uri = Origin::parse("/borrow/verify?borrow%5Ftoken=abc&client_secret=x").unwrap();
+        let redacted = redact_uri(&uri, &fields());
+        assert!(!redacted.contains("abc") && !redacted.contains("=x"), "{}", redacted);
+
+        let uri = Origin::parse("/admin/events?pool=a&token=s3cret").unwrap();
+        assert_eq!(redact_uri(&uri, &fields()), format!("/admin/events?pool=a&token={}", REDACTED));
+
+        let uri = Origin::parse("/admin/items?offset=1").unwrap();
+        assert_eq!(redact_uri(&uri, &fields()), "/admin/items?offset=1");
+    }
+
+    #[test]
+    fn leaves_scalars_untouched() {
+        assert_eq!(redact_json(&json!("secret"), &fields()), json!("secret"));
+        assert_eq!(redact_json(&json!([1, 2]), &fields()), json!([1, 2]));
+    }
+}
@@ -1,7 +1,7 @@
 use dotenv::dotenv;
 use std::env;
 
-use ip_allocator_webserver::{rocket_with_config, print_openapi_spec, store::Store, config};
+use ip_allocator_webserver::{rocket_with_config, print_openapi_spec, store::Store, validate_config_file};
 
 #[rocket::main]
 async fn main() {
@@ -11,6 +11,23 @@ async fn main() {
         print_openapi_spec();
         return;
     }
+    if let Some(pos) = args.iter().position(|a| a == "--validate-config") {
+        let Some(path) = args.get(pos + 1) else {
+            eprintln!("--validate-config requires a path");
+            std::process::exit(2);
+        };
+        match validate_config_file(std::path::Path::new(path)) {
+            Ok(()) => println!("✓ {} is valid", path),
+            Err(problems) => {
+                eprintln!("✗ {} is invalid:", path);
+                for problem in problems {
+                    eprintln!("  - {}", problem);
+                }
+                std::process::exit(2);
+            }
+        }
+        return;
+    }
 
     let redis_url = env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string());
 
@@ -32,30 +49,54 @@ async fn main() {
         }
     }
 
-    let store = Store::new(redis_url.clone());
-
-    // Test Redis connection on startup - fail fast if unavailable
-    if let Err(e) = store.test_connection() {
-        eprintln!("=================================================");
-        eprintln!("ERROR: Failed to connect to Redis");
-        eprintln!("=================================================");
-        eprintln!();
-        eprintln!("Connection error: {}", e);
-        eprintln!();
-        eprintln!("Current REDIS_URL: {}", redis_url);
-        eprintln!();
-        eprintln!("Please ensure that:");
-        eprintln!("  1. Redis server is running and accessible");
-        eprintln!("  2. The REDIS_URL environment variable is set correctly");
-        eprintln!("     Example: export REDIS_URL='redis://127.0.0.1:6379/'");
-        eprintln!("  3. Network connectivity allows access to the Redis server");
-        eprintln!("  4. Redis authentication credentials are correct (if required)");
-        eprintln!();
-        eprintln!("=================================================");
-        std::process::exit(1);
-    }
+    let store = Store::new(app_config.redis_connection_url(&redis_url))
+        .with_sentinel(app_config.redis_sentinel.clone())
+        .with_pool_size(app_config.redis_pool_size())
+        .with_pool_wait(app_config.redis_pool_wait())
+        .with_scripts_only(app_config.redis_scripts_only);
 
-    println!("✓ Successfully connected to Redis at {}", redis_url);
+    // Test Redis connection on startup - fail fast if unavailable, unless configured to
+    // start anyway and let /readyz hold traffic back until Redis answers
+    let redis_up = match store.test_connection().await {
+        Ok(()) => {
+            println!("✓ Successfully connected to Redis at {}", redis_url);
+            true
+        }
+        Err(e) if !app_config.startup_requires_redis() => {
+            eprintln!("WARNING: Redis at {} is unreachable ({}); starting anyway, not ready until it answers", redis_url, e);
+            false
+        }
+        Err(e) => {
+            eprintln!("=================================================");
+            eprintln!("ERROR: Failed to connect to Redis");
+            eprintln!("=================================================");
+            eprintln!();
+            eprintln!("Connection error: {}", e);
+            eprintln!();
+            eprintln!("Current REDIS_URL: {}", redis_url);
+            eprintln!();
+            eprintln!("Please ensure that:");
+            eprintln!("  1. Redis server is running and accessible");
+            eprintln!("  2. The REDIS_URL environment variable is set correctly");
+            eprintln!("     Example: export REDIS_URL='redis://127.0.0.1:6379/'");
+            eprintln!("  3. Network connectivity allows access to the Redis server");
+            eprintln!("  4. Redis authentication credentials are correct (if required)");
+            eprintln!();
+            eprintln!("=================================================");
+            std::process::exit(1);
+        }
+    };
+
+    // Without Redis at startup, scripts are loaded on first use instead
+    if app_config.redis_scripts_only && redis_up {
+        match store.load_scripts().await {
+            Ok(hashes) => println!("✓ Loaded {} Redis scripts", hashes.len()),
+            Err(e) => {
+                eprintln!("ERROR: Failed to load Redis scripts: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     let _ = rocket_with_config(redis_url, app_config)
         .launch()
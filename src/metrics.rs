@@ -0,0 +1,277 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use rocket::State;
+use tokio::sync::Mutex;
+
+use crate::store::{Store, DEFAULT_POOL};
+use crate::AppState;
+
+/// Upper bounds (seconds) of the latency histogram buckets, Prometheus' defaults
+const LATENCY_BUCKETS: [f64; 11] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
+
+/// A latency histogram with cumulative `LATENCY_BUCKETS`
+#[derive(Default)]
+struct Histogram {
+    /// Observations per bucket, not cumulative; the last slot counts those above every bound
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    fn observe(&self, elapsed: Duration) {
+        let secs = elapsed.as_secs_f64();
+        let slot = LATENCY_BUCKETS.iter().position(|le| secs <= *le).unwrap_or(LATENCY_BUCKETS.len());
+        self.buckets[slot].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn render_samples(&self, out: &mut String, name: &str, labels: &str) {
+        let mut cumulative = 0;
+        for (slot, le) in LATENCY_BUCKETS.iter().map(|le| le.to_string()).chain(["+Inf".to_string()]).enumerate() {
+            cumulative += self.buckets[slot].load(Ordering::Relaxed);
+            let _ = writeln!(out, "{}_bucket{{{},le=\"{}\"}} {}", name, labels, le, cumulative);
+        }
+        let sum = self.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(out, "{}_sum{{{}}} {}", name, labels, sum);
+        let _ = writeln!(out, "{}_count{{{}}} {}", name, labels, self.count.load(Ordering::Relaxed));
+    }
+}
+
+/// Counters and histograms of one pool
+#[derive(Default)]
+struct PoolMetrics {
+    /// Borrows whose item went back to the freelist after a subscriber or record failure
+    borrow_rollback_total: AtomicU64,
+    borrow_total: AtomicU64,
+    /// Borrows with `wait` that gave up with the freelist still empty
+    borrow_timeout_total: AtomicU64,
+    return_total: AtomicU64,
+    submit_total: AtomicU64,
+    /// Store pop through borrow record of successful borrows
+    borrow_duration: Histogram,
+    /// Accept to terminal status of returns
+    return_duration: Histogram,
+}
+
+/// Counters and histograms of one subscriber
+#[derive(Default)]
+struct SubscriberMetrics {
+    /// Notifications that were not accepted, retries exhausted
+    failure_total: AtomicU64,
+    /// Post through final answer (or async completion) of each notification
+    dispatch_duration: Histogram,
+}
+
+/// Process-wide counters exposed on `/metrics` in Prometheus text format, per pool
+pub struct Metrics {
+    /// Pools that saw traffic, plus the default pool from the start
+    pools: RwLock<BTreeMap<String, Arc<PoolMetrics>>>,
+    /// Subscribers that were notified, keyed by (kind, name)
+    subscribers: RwLock<BTreeMap<(String, String), Arc<SubscriberMetrics>>>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        let pools = BTreeMap::from([(DEFAULT_POOL.to_string(), Arc::default())]);
+        Self { pools: RwLock::new(pools), subscribers: RwLock::default() }
+    }
+}
+
+/// Point-in-time pool sizes read from Redis at scrape time
+#[derive(Default)]
+pub struct PoolGauges {
+    /// Free items per pool, the default pool first
+    pub free: Vec<(String, usize)>,
+    /// Borrowed items per pool, the default pool first
+    pub borrowed: Vec<(String, usize)>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn pool(&self, pool: &str) -> Arc<PoolMetrics> {
+        if let Some(metrics) = self.pools.read().unwrap_or_else(|e| e.into_inner()).get(pool) {
+            return metrics.clone();
+        }
+        let mut pools = self.pools.write().unwrap_or_else(|e| e.into_inner());
+        pools.entry(pool.to_string()).or_default().clone()
+    }
+
+    fn subscriber(&self, kind: &str, name: &str) -> Arc<SubscriberMetrics> {
+        let key = (kind.to_string(), name.to_string());
+        if let Some(metrics) = self.subscribers.read().unwrap_or_else(|e| e.into_inner()).get(&key) {
+            return metrics.clone();
+        }
+        let mut subscribers = self.subscribers.write().unwrap_or_else(|e| e.into_inner());
+        subscribers.entry(key).or_default().clone()
+    }
+
+    pub fn inc_borrow_rollback(&self, pool: &str) {
+        self.pool(pool).borrow_rollback_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_borrow(&self, pool: &str) {
+        self.pool(pool).borrow_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_borrow_timeout(&self, pool: &str) {
+        self.pool(pool).borrow_timeout_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_return(&self, pool: &str) {
+        self.pool(pool).return_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn inc_submit(&self, pool: &str) {
+        self.pool(pool).submit_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_borrow(&self, pool: &str, elapsed: Duration) {
+        self.pool(pool).borrow_duration.observe(elapsed);
+    }
+
+    pub fn observe_return(&self, pool: &str, elapsed: Duration) {
+        self.pool(pool).return_duration.observe(elapsed);
+    }
+
+    pub fn inc_subscriber_failure(&self, kind: &str, name: &str) {
+        self.subscriber(kind, name).failure_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn observe_subscriber_dispatch(&self, kind: &str, name: &str, elapsed: Duration) {
+        self.subscriber(kind, name).dispatch_duration.observe(elapsed);
+    }
+
+    pub fn render(&self, gauges: &PoolGauges) -> String {
+        // The default pool first, the rest by name
+        let mut pools: Vec<(String, Arc<PoolMetrics>)> = self
+            .pools
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(pool, metrics)| (pool.clone(), metrics.clone()))
+            .collect();
+        pools.sort_by_key(|(pool, _)| pool != DEFAULT_POOL);
+
+        let mut out = String::new();
+        let counter = |value: fn(&PoolMetrics) -> &AtomicU64| -> Vec<(String, u64)> {
+            pools.iter().map(|(pool, metrics)| (pool_label(pool), value(metrics).load(Ordering::Relaxed))).collect()
+        };
+        metric(
+            &mut out,
+            "borrow_rollback_total",
+            "counter",
+            "Borrows rolled back to the freelist after a failure",
+            &counter(|m| &m.borrow_rollback_total),
+        );
+        metric(&mut out, "borrow_total", "counter", "Successful borrows", &counter(|m| &m.borrow_total));
+        metric(
+            &mut out,
+            "borrow_timeout_total",
+            "counter",
+            "Borrows with wait that timed out on an empty freelist",
+            &counter(|m| &m.borrow_timeout_total),
+        );
+        metric(&mut out, "return_total", "counter", "Accepted returns", &counter(|m| &m.return_total));
+        metric(&mut out, "submit_total", "counter", "Accepted submits", &counter(|m| &m.submit_total));
+        histogram(
+            &mut out,
+            "borrow_duration_seconds",
+            "Time to pop and record a successful borrow",
+            pools.iter().map(|(pool, m)| (pool_label(pool), &m.borrow_duration)),
+        );
+        histogram(
+            &mut out,
+            "return_duration_seconds",
+            "Time from accepting a return to its terminal status",
+            pools.iter().map(|(pool, m)| (pool_label(pool), &m.return_duration)),
+        );
+
+        let subscribers: Vec<(String, Arc<SubscriberMetrics>)> = self
+            .subscribers
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|((kind, name), metrics)| (format!("kind=\"{}\",subscriber=\"{}\"", kind, name), metrics.clone()))
+            .collect();
+        let failures: Vec<(String, u64)> = subscribers
+            .iter()
+            .map(|(labels, m)| (labels.clone(), m.failure_total.load(Ordering::Relaxed)))
+            .collect();
+        metric(&mut out, "subscriber_failure_total", "counter", "Subscriber notifications that failed", &failures);
+        if !subscribers.is_empty() {
+            histogram(
+                &mut out,
+                "subscriber_dispatch_duration_seconds",
+                "Time to notify a subscriber, retries included",
+                subscribers.iter().map(|(labels, m)| (labels.clone(), &m.dispatch_duration)),
+            );
+        }
+
+        let free: Vec<(String, u64)> = gauges.free.iter().map(|(pool, n)| (pool_label(pool), *n as u64)).collect();
+        metric(&mut out, "freelist_size", "gauge", "Items free to borrow", &free);
+        let borrowed: Vec<(String, u64)> =
+            gauges.borrowed.iter().map(|(pool, n)| (pool_label(pool), *n as u64)).collect();
+        metric(&mut out, "borrowed_size", "gauge", "Items currently borrowed", &borrowed);
+        out
+    }
+}
+
+/// The label set of a per-pool sample
+fn pool_label(pool: &str) -> String {
+    format!("pool=\"{}\"", pool)
+}
+
+/// Write one histogram family with the samples of every label set
+fn histogram<'a>(
+    out: &mut String,
+    name: &str,
+    help: &str,
+    series: impl Iterator<Item = (String, &'a Histogram)>,
+) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} histogram", name);
+    for (labels, histogram) in series {
+        histogram.render_samples(out, name, &labels);
+    }
+}
+
+/// Write one metric family with a sample per label set
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, samples: &[(String, u64)]) {
+    if samples.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in samples {
+        let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+    }
+}
+
+/// Prometheus scrape endpoint
+///
+/// Pool sizes are read from Redis on each scrape and left out if Redis is unreachable,
+/// so the counters stay available.
+#[get("/metrics")]
+pub async fn metrics(app: &State<AppState>, store: &State<Mutex<Store>>) -> String {
+    let gauges = {
+        let store = store.lock().await.clone();
+        let mut borrowed = Vec::new();
+        for pool in store.pool_names().await.unwrap_or_default() {
+            match store.in_pool(&pool).borrowed_count().await {
+                Ok(count) => borrowed.push((pool, count)),
+                Err(_) => break,
+            }
+        }
+        PoolGauges { free: store.pool_sizes().await.unwrap_or_default(), borrowed }
+    };
+    app.metrics.render(&gauges)
+}
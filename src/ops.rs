@@ -1,12 +1,16 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use rocket_okapi::okapi::schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::sync::RwLock;
 use tokio::sync::broadcast;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+use crate::store::{now_secs, Store};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum OperationStatus {
     Pending,
@@ -15,99 +19,584 @@ pub enum OperationStatus {
     Failed,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl OperationStatus {
+    /// Whether the operation has finished and will not change again
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, OperationStatus::Succeeded | OperationStatus::Failed)
+    }
+}
+
+/// The request that started an operation
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum OperationKind {
+    Return,
+    Submit,
+}
+
+impl OperationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationKind::Return => "return",
+            OperationKind::Submit => "submit",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Operation {
     pub id: String,
+    pub kind: OperationKind,
     pub item: Value,
     pub status: OperationStatus,
     pub message: Option<String>,
     pub must_succeed: HashSet<String>,
     pub subscribers: HashMap<String, OperationStatus>,
+    /// Id of the borrow this operation completes, for return operations
+    #[serde(default)]
+    pub borrow_id: Option<String>,
+    /// Owner id (or client IP) of the request that started the operation
+    #[serde(default)]
+    pub initiated_by: Option<String>,
+    /// Unix timestamp (seconds) at which the operation was created
+    pub created_at: u64,
+    /// Unix timestamp (seconds) at which the operation reached a terminal state
+    #[serde(default)]
+    pub completed_at: Option<u64>,
+    /// For submits that reached the freelist: whether the item was not already in it
+    #[serde(default)]
+    pub newly_added: Option<bool>,
+    /// How long the operation is kept once finished, in milliseconds; forever when unset
+    #[serde(default)]
+    pub ttl_ms: Option<u64>,
 }
 
 impl Operation {
-    pub fn new(id: String, item: Value, must_succeed: HashSet<String>) -> Self {
+    pub fn new(id: String, kind: OperationKind, item: Value, must_succeed: HashSet<String>) -> Self {
         let mut subscribers = HashMap::new();
         for name in &must_succeed {
             subscribers.insert(name.clone(), OperationStatus::Pending);
         }
         Self {
             id,
+            kind,
             item,
             status: OperationStatus::Pending,
             message: None,
             must_succeed,
             subscribers,
+            borrow_id: None,
+            initiated_by: None,
+            created_at: now_secs(),
+            completed_at: None,
+            newly_added: None,
+            ttl_ms: None,
         }
     }
+
+    /// Whether the operation finished longer than its TTL ago
+    fn is_expired(&self, now_ms: u64) -> bool {
+        match (self.completed_at, self.ttl_ms) {
+            (Some(completed_at), Some(ttl_ms)) => completed_at * 1000 + ttl_ms <= now_ms,
+            _ => false,
+        }
+    }
+
+    /// Named top-level fields (all with `None`), each as JSON, for the Redis hash
+    fn fields(&self, names: Option<&[&str]>) -> Vec<(String, String)> {
+        let Ok(Value::Object(fields)) = serde_json::to_value(self) else {
+            return Vec::new();
+        };
+        fields
+            .into_iter()
+            .filter(|(name, _)| names.is_none_or(|names| names.contains(&name.as_str())))
+            .map(|(name, value)| (name, value.to_string()))
+            .collect()
+    }
+
+    fn from_fields(fields: HashMap<String, String>) -> Option<Self> {
+        let fields = fields
+            .into_iter()
+            .map(|(name, value)| serde_json::from_str(&value).map(|value| (name, value)))
+            .collect::<Result<serde_json::Map<_, _>, _>>()
+            .ok()?;
+        serde_json::from_value(Value::Object(fields)).ok()
+    }
+
+    /// How long the persisted copy should outlive this moment: until the TTL runs out
+    /// once finished, or a full TTL from now while unfinished. Kept when there is no TTL.
+    fn persisted_ttl(&self) -> Option<Duration> {
+        let ttl_ms = self.ttl_ms?;
+        let remaining = match self.completed_at {
+            Some(completed_at) => (completed_at * 1000 + ttl_ms).saturating_sub(now_ms()),
+            None => ttl_ms,
+        };
+        Some(Duration::from_millis(remaining))
+    }
+}
+
+/// Retention of finished operations. Each operation draws its own TTL from
+/// `base ± jitter_pct%`, so a burst of operations does not expire all at once.
+#[derive(Debug, Clone, Copy)]
+pub struct OperationTtl {
+    pub base: Duration,
+    pub jitter_pct: u8,
+}
+
+impl OperationTtl {
+    pub fn sample(&self) -> Duration {
+        let base = self.base.as_millis() as u64;
+        let spread = base * u64::from(self.jitter_pct.min(100)) / 100;
+        if spread == 0 {
+            return self.base;
+        }
+        let offset = (uuid::Uuid::new_v4().as_u128() % (2 * spread as u128 + 1)) as u64;
+        Duration::from_millis(base - spread + offset)
+    }
+}
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// How many operations of one kind succeeded and failed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct OutcomeCounts {
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// How long an `Idempotency-Key` maps to its operation unless configured otherwise
+pub const DEFAULT_IDEMPOTENCY_KEY_TTL: Duration = Duration::from_secs(3600);
+
+/// The operation an idempotency key started, until `expires_at_ms`
+#[derive(Debug, Clone)]
+struct IdempotencyKeyEntry {
+    operation_id: String,
+    expires_at_ms: u64,
 }
 
 #[derive(Clone)]
 pub struct OperationStore {
     inner: Arc<RwLock<HashMap<String, Operation>>>,
+    ttl: Option<OperationTtl>,
+    /// Where operations are persisted, so they outlive a restart; memory only when unset
+    redis: Option<Store>,
+    /// Client-chosen idempotency keys, each mapped to the operation its first request
+    /// started; only used without Redis or while it is unreachable
+    idempotency_keys: Arc<RwLock<HashMap<String, IdempotencyKeyEntry>>>,
+    idempotency_key_ttl: Duration,
 }
 
 impl OperationStore {
     pub fn new() -> Self {
         Self {
             inner: Arc::new(RwLock::new(HashMap::new())),
+            ttl: None,
+            redis: None,
+            idempotency_keys: Arc::new(RwLock::new(HashMap::new())),
+            idempotency_key_ttl: DEFAULT_IDEMPOTENCY_KEY_TTL,
         }
     }
 
-    pub async fn create(&self, id: String, item: Value, must: HashSet<String>) -> Operation {
-        let op = Operation::new(id.clone(), item, must);
-        let mut guard = self.inner.write().await;
-        guard.insert(id.clone(), op.clone());
+    /// Remember idempotency keys this long after their first request
+    pub fn with_idempotency_key_ttl(mut self, ttl: Duration) -> Self {
+        self.idempotency_key_ttl = ttl;
+        self
+    }
+
+    /// Claim `key` for the operation `operation_id` is about to start. Returns the
+    /// operation an earlier request already claimed it for instead, if that claim has not
+    /// expired; checking and claiming is one step, so concurrent retries cannot both win.
+    /// With Redis the claim is kept there, so it holds across replicas and restarts; if
+    /// Redis cannot be reached it is kept in memory.
+    pub async fn claim_idempotency_key(&self, key: &str, operation_id: &str) -> Option<String> {
+        if let Some(store) = &self.redis {
+            match store.claim_idempotency_key(key, operation_id, self.idempotency_key_ttl).await {
+                Ok(existing) => return existing,
+                Err(e) => log::warn!("failed to claim idempotency key {} in Redis, keeping it in memory: {}", key, e),
+            }
+        }
+        let now = now_ms();
+        let mut keys = self.idempotency_keys.write().await;
+        if let Some(entry) = keys.get(key).filter(|entry| entry.expires_at_ms > now) {
+            return Some(entry.operation_id.clone());
+        }
+        let expires_at_ms = now + self.idempotency_key_ttl.as_millis() as u64;
+        keys.insert(key.to_string(), IdempotencyKeyEntry { operation_id: operation_id.to_string(), expires_at_ms });
+        None
+    }
+
+    /// Give up a claim whose request failed before starting its operation, so that a
+    /// retry with the same key is handled afresh
+    pub async fn release_idempotency_key(&self, key: &str, operation_id: &str) {
+        if let Some(store) = &self.redis {
+            if let Err(e) = store.release_idempotency_key(key, operation_id).await {
+                log::warn!("failed to release idempotency key {} in Redis: {}", key, e);
+            }
+        }
+        let mut keys = self.idempotency_keys.write().await;
+        if keys.get(key).is_some_and(|entry| entry.operation_id == operation_id) {
+            keys.remove(key);
+        }
+    }
+
+    /// Drop finished operations after a (jittered) TTL instead of keeping them forever
+    pub fn with_ttl(mut self, ttl: Option<OperationTtl>) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Also write every operation to the Redis hash `operations:<id>`, expiring with the
+    /// operation's TTL. Reads are served from memory and fall back to Redis, e.g. after
+    /// a restart; a failed write is logged and the in-memory copy stays authoritative.
+    pub fn with_redis(mut self, store: Store) -> Self {
+        self.redis = Some(store);
+        self
+    }
+
+    /// Write `fields` of the operation (all with `None`, replacing the stored hash)
+    async fn persist(&self, op: &Operation, fields: Option<&[&str]>) {
+        let Some(store) = &self.redis else { return };
+        if let Err(e) = store
+            .save_operation(&op.id, &op.fields(fields), op.persisted_ttl(), fields.is_none())
+            .await
+        {
+            log::warn!("failed to persist operation {}: {}", op.id, e);
+        }
+    }
+
+    /// Update one operation and persist the `fields` the update changed. An operation
+    /// missing from memory (started by another replica, or before a restart, e.g. one the
+    /// retry worker picked up) is loaded from Redis first, so the update is not lost.
+    async fn update(&self, id: &str, fields: &[&str], change: impl FnOnce(&mut Operation)) -> Option<Operation> {
+        if !self.inner.read().await.contains_key(id) {
+            self.load(id).await?;
+        }
+        let op = {
+            let mut guard = self.inner.write().await;
+            let op = guard.get_mut(id)?;
+            change(op);
+            op.clone()
+        };
+        self.persist(&op, Some(fields)).await;
+        Some(op)
+    }
+
+    pub async fn insert(&self, mut op: Operation) -> Operation {
+        if op.ttl_ms.is_none() {
+            op.ttl_ms = self.ttl.map(|ttl| ttl.sample().as_millis() as u64);
+        }
+        {
+            let mut guard = self.inner.write().await;
+            guard.insert(op.id.clone(), op.clone());
+        }
+        self.persist(&op, None).await;
         op
     }
 
     pub async fn get(&self, id: &str) -> Option<Operation> {
-        let guard = self.inner.read().await;
-        guard.get(id).cloned()
+        let cached = self.inner.read().await.get(id).cloned();
+        let op = match cached {
+            Some(op) => op,
+            None => self.load(id).await?,
+        };
+        Some(op).filter(|op| !op.is_expired(now_ms()))
     }
 
-    pub async fn update_message(&self, id: &str, msg: Option<String>) {
+    /// Read a persisted operation missing from memory and keep it in memory again
+    async fn load(&self, id: &str) -> Option<Operation> {
+        let fields = self.redis.as_ref()?.load_operation(id).await.ok()?;
+        let op = Operation::from_fields(fields)?;
         let mut guard = self.inner.write().await;
-        if let Some(op) = guard.get_mut(id) {
-            op.message = msg;
-        }
+        Some(guard.entry(op.id.clone()).or_insert(op).clone())
     }
 
-    pub async fn set_status(&self, id: &str, status: OperationStatus) {
+    /// Remove operations whose TTL has run out from memory; returns how many were removed.
+    /// Persisted copies expire in Redis on their own.
+    pub async fn purge_expired(&self) -> usize {
+        let now = now_ms();
+        self.idempotency_keys.write().await.retain(|_, entry| entry.expires_at_ms > now);
         let mut guard = self.inner.write().await;
-        if let Some(op) = guard.get_mut(id) {
+        let before = guard.len();
+        guard.retain(|_, op| !op.is_expired(now));
+        before - guard.len()
+    }
+
+    pub async fn update_message(&self, id: &str, msg: Option<String>) {
+        self.update(id, &["message"], |op| op.message = msg).await;
+    }
+
+    pub async fn set_newly_added(&self, id: &str, newly_added: bool) {
+        self.update(id, &["newly_added"], |op| op.newly_added = Some(newly_added)).await;
+    }
+
+    pub async fn set_status(&self, id: &str, status: OperationStatus) {
+        self.update(id, &["status", "completed_at"], |op| {
+            if status.is_terminal() && op.completed_at.is_none() {
+                op.completed_at = Some(now_secs());
+            }
             op.status = status;
-        }
+        })
+        .await;
     }
 
+    #[allow(dead_code)]
     pub async fn update_subscriber(
         &self,
         id: &str,
         name: &str,
         status: OperationStatus,
     ) -> Option<Operation> {
-        let mut guard = self.inner.write().await;
-        if let Some(op) = guard.get_mut(id) {
+        self.update(id, &["subscribers"], |op| {
             op.subscribers.insert(name.to_string(), status);
-            return Some(op.clone());
-        }
-        None
+        })
+        .await
     }
 
+    /// Every operation, including persisted ones missing from memory
     pub async fn get_all(&self) -> Vec<Operation> {
+        if let Some(store) = &self.redis {
+            let persisted = store.load_operations().await.unwrap_or_default();
+            let mut guard = self.inner.write().await;
+            for op in persisted.into_iter().filter_map(Operation::from_fields) {
+                guard.entry(op.id.clone()).or_insert(op);
+            }
+        }
         let guard = self.inner.read().await;
-        guard.values().cloned().collect()
+        let now = now_ms();
+        guard.values().filter(|op| !op.is_expired(now)).cloned().collect()
+    }
+
+    /// Insert operations as they are, e.g. from an export, replacing any with the same id.
+    /// Returns how many replaced an existing operation.
+    pub async fn import(&self, ops: Vec<Operation>) -> usize {
+        let mut replaced = 0;
+        for op in ops {
+            if self.inner.write().await.insert(op.id.clone(), op.clone()).is_some() {
+                replaced += 1;
+            }
+            self.persist(&op, None).await;
+        }
+        replaced
     }
 
     pub async fn delete(&self, id: &str) -> bool {
-        let mut guard = self.inner.write().await;
-        guard.remove(id).is_some()
+        let mut removed = self.inner.write().await.remove(id).is_some();
+        if let Some(store) = &self.redis {
+            match store.delete_operation(id).await {
+                Ok(deleted) => removed |= deleted,
+                Err(e) => log::warn!("failed to delete persisted operation {}: {}", id, e),
+            }
+        }
+        removed
+    }
+
+    /// Count outcomes of `kind` operations that reached a terminal state at or after `since`
+    pub async fn outcomes_since(&self, kind: OperationKind, since: u64) -> OutcomeCounts {
+        let guard = self.inner.read().await;
+        let mut counts = OutcomeCounts::default();
+        let finished = guard
+            .values()
+            .filter(|op| op.kind == kind && op.completed_at.is_some_and(|at| at >= since));
+        for op in finished {
+            match op.status {
+                OperationStatus::Succeeded => counts.succeeded += 1,
+                OperationStatus::Failed => counts.failed += 1,
+                _ => {}
+            }
+        }
+        counts
+    }
+}
+
+/// Field set on SSE events cut down to `sse_max_event_bytes`
+pub const TRUNCATED_MARKER: &str = "truncated";
+
+/// Fit an SSE event into `max_bytes`. An oversized event keeps only its `event` name and
+/// gains `"truncated": true` and its original size in `bytes`.
+pub fn cap_event(data: String, max_bytes: Option<usize>) -> String {
+    let Some(max_bytes) = max_bytes.filter(|max| data.len() > *max) else {
+        return data;
+    };
+    let event = serde_json::from_str::<Value>(&data).ok().and_then(|v| v.get("event").cloned());
+    let mut marker = serde_json::Map::new();
+    if let Some(event) = event.filter(|event| event.to_string().len() < max_bytes) {
+        marker.insert("event".to_string(), event);
+    }
+    marker.insert(TRUNCATED_MARKER.to_string(), Value::Bool(true));
+    marker.insert("bytes".to_string(), Value::from(data.len()));
+    Value::Object(marker).to_string()
+}
+
+/// Add the operation's current `item`, `status` and `subscribers` to an SSE event, for
+/// the verbose event format; events that are not JSON objects are left alone
+pub fn with_operation(data: String, op: &Operation) -> String {
+    let Ok(Value::Object(mut event)) = serde_json::from_str::<Value>(&data) else {
+        return data;
+    };
+    event.insert("item".to_string(), op.item.clone());
+    event.insert("status".to_string(), serde_json::to_value(&op.status).unwrap_or(Value::Null));
+    event.insert("subscribers".to_string(), serde_json::to_value(&op.subscribers).unwrap_or(Value::Null));
+    Value::Object(event).to_string()
+}
+
+/// Number of past events kept per operation for late subscribers
+const EVENT_HISTORY_LEN: usize = 64;
+
+/// An SSE event for an operation, numbered from 1 in the order it was sent
+#[derive(Debug, Clone)]
+pub struct OperationEvent {
+    pub id: u64,
+    pub data: String,
+}
+
+struct EventChannel {
+    tx: broadcast::Sender<OperationEvent>,
+    history: VecDeque<OperationEvent>,
+    last_id: u64,
+}
+
+impl EventChannel {
+    fn new() -> Self {
+        let (tx, _rx) = broadcast::channel(64);
+        Self { tx, history: VecDeque::with_capacity(EVENT_HISTORY_LEN), last_id: 0 }
+    }
+}
+
+/// A return or submit notification queued for another try at the must-succeed
+/// subscribers that did not accept it, kept as JSON in the Redis retry queue
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RetryEntry {
+    pub operation_id: String,
+    pub kind: OperationKind,
+    pub item: Value,
+    /// Member text to store when items are kept raw
+    #[serde(default)]
+    pub raw_item: Option<String>,
+    #[serde(default)]
+    pub params: Option<Value>,
+    /// Pool the item is returned or submitted to
+    pub pool: String,
+    /// Unix timestamp (seconds) until which a submitted item is available
+    #[serde(default)]
+    pub available_until: Option<u64>,
+    /// Whether the return waits for `/return/confirm` once every subscriber accepted it
+    #[serde(default)]
+    pub two_phase: bool,
+    /// Subscribers yet to accept the notification
+    pub subscribers: Vec<String>,
+    /// Retries made so far
+    pub attempt: u32,
+    /// Unix timestamp (seconds) of the first failure
+    pub first_failed_at: u64,
+}
+
+/// A two-phase return whose subscribers succeeded; the item stays borrowed until the
+/// return is confirmed or times out
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingRelease {
+    pub item: Value,
+    /// Member text to store when items are kept raw
+    pub raw_item: Option<String>,
+    /// Pool the item is borrowed from
+    pub pool: String,
+    /// Unix timestamp (seconds) at which the timeout action applies
+    pub deadline: u64,
+}
+
+/// Two-phase returns awaiting `/return/confirm`, keyed by operation id
+#[derive(Clone, Default)]
+pub struct PendingReleases {
+    inner: Arc<RwLock<HashMap<String, PendingRelease>>>,
+    /// Where releases are kept, so any instance can confirm or time them out, also after
+    /// a restart; memory only when unset
+    redis: Option<Store>,
+}
+
+impl PendingReleases {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep releases in the Redis hash `pending_releases` instead of memory
+    pub fn with_redis(mut self, store: Store) -> Self {
+        self.redis = Some(store);
+        self
+    }
+
+    pub async fn insert(&self, op_id: &str, release: PendingRelease) {
+        if let Some(store) = &self.redis {
+            let saved = match serde_json::to_string(&release) {
+                Ok(json) => store.save_pending_release(op_id, &json).await.map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match saved {
+                Ok(()) => return,
+                Err(e) => log::warn!("failed to persist pending release {}: {}", op_id, e),
+            }
+        }
+        self.inner.write().await.insert(op_id.to_string(), release);
+    }
+
+    /// The pending release of an operation, without claiming it
+    pub async fn get(&self, op_id: &str) -> Option<PendingRelease> {
+        if let Some(release) = self.inner.read().await.get(op_id) {
+            return Some(release.clone());
+        }
+        let json = self.redis.as_ref()?.pending_release(op_id).await.ok()??;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Claim a pending release; only one caller (confirm or timeout) gets it
+    pub async fn take(&self, op_id: &str) -> Option<PendingRelease> {
+        if let Some(release) = self.inner.write().await.remove(op_id) {
+            return Some(release);
+        }
+        let json = self.redis.as_ref()?.take_pending_release(op_id).await.ok()??;
+        serde_json::from_str(&json).ok()
+    }
+
+    /// Every pending release, in memory and in Redis
+    async fn all(&self) -> Vec<(String, PendingRelease)> {
+        let mut releases: Vec<(String, PendingRelease)> =
+            self.inner.read().await.iter().map(|(id, release)| (id.clone(), release.clone())).collect();
+        if let Some(store) = &self.redis {
+            let persisted = store.pending_releases().await.unwrap_or_default();
+            releases.extend(
+                persisted
+                    .into_iter()
+                    .filter_map(|(id, json)| serde_json::from_str(&json).ok().map(|release| (id, release))),
+            );
+        }
+        releases
+    }
+
+    /// Claim every release whose deadline is at or before `now`
+    pub async fn take_expired(&self, now: u64) -> Vec<(String, PendingRelease)> {
+        let mut expired = Vec::new();
+        for (id, release) in self.all().await {
+            if release.deadline > now {
+                continue;
+            }
+            if let Some(release) = self.take(&id).await {
+                expired.push((id, release));
+            }
+        }
+        expired
+    }
+
+    pub async fn contains_item(&self, item: &Value) -> bool {
+        self.all().await.iter().any(|(_, release)| &release.item == item)
     }
 }
 
 #[derive(Clone)]
 pub struct Broadcasters {
-    inner: Arc<RwLock<HashMap<String, broadcast::Sender<String>>>>,
+    inner: Arc<RwLock<HashMap<String, EventChannel>>>,
 }
 
 impl Broadcasters {
@@ -117,26 +606,171 @@ impl Broadcasters {
         }
     }
 
-    pub async fn subscribe(&self, id: &str) -> broadcast::Receiver<String> {
+    pub async fn subscribe(&self, id: &str) -> broadcast::Receiver<OperationEvent> {
         let mut guard = self.inner.write().await;
-        match guard.get(id) {
-            Some(tx) => tx.subscribe(),
-            None => {
-                let (tx, rx) = broadcast::channel(64);
-                guard.insert(id.to_string(), tx);
-                rx
-            }
-        }
+        guard.entry(id.to_string()).or_insert_with(EventChannel::new).tx.subscribe()
+    }
+
+    /// Subscribe and also get the buffered events with an id greater than `since`.
+    /// Both are taken under one lock, so no event is missed or seen twice.
+    pub async fn subscribe_since(&self, id: &str, since: u64) -> (Vec<OperationEvent>, broadcast::Receiver<OperationEvent>) {
+        let mut guard = self.inner.write().await;
+        let channel = guard.entry(id.to_string()).or_insert_with(EventChannel::new);
+        let replay = channel.history.iter().filter(|e| e.id > since).cloned().collect();
+        (replay, channel.tx.subscribe())
     }
 
     pub async fn notify(&self, id: &str, payload: String) {
         let mut guard = self.inner.write().await;
-        let tx = guard.entry(id.to_string()).or_insert_with(|| {
-            let (tx, _rx) = broadcast::channel(64);
-            tx
-        });
-        let _ = tx.send(payload);
+        let channel = guard.entry(id.to_string()).or_insert_with(EventChannel::new);
+        channel.last_id += 1;
+        let event = OperationEvent { id: channel.last_id, data: payload };
+        if channel.history.len() == EVENT_HISTORY_LEN {
+            channel.history.pop_front();
+        }
+        channel.history.push_back(event.clone());
+        let _ = channel.tx.send(event);
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finished(kind: OperationKind, status: OperationStatus, completed_at: Option<u64>) -> Operation {
+        let mut op = Operation::new(uuid::Uuid::new_v4().to_string(), kind, Value::Null, HashSet::new());
+        op.status = status;
+        op.completed_at = completed_at;
+        op
+    }
+
+    #[tokio::test]
+    async fn outcomes_only_count_operations_finished_in_window() {
+        let store = OperationStore::new();
+        let now = now_secs();
+        for op in [
+            finished(OperationKind::Return, OperationStatus::Succeeded, Some(now)),
+            finished(OperationKind::Return, OperationStatus::Succeeded, Some(now - 10)),
+            finished(OperationKind::Return, OperationStatus::Failed, Some(now - 20)),
+            // Outside the window
+            finished(OperationKind::Return, OperationStatus::Failed, Some(now - 500)),
+            // Not finished yet
+            finished(OperationKind::Return, OperationStatus::InProgress, None),
+            finished(OperationKind::Submit, OperationStatus::Failed, Some(now - 5)),
+        ] {
+            store.insert(op).await;
+        }
+
+        let since = now - 60;
+        assert_eq!(
+            store.outcomes_since(OperationKind::Return, since).await,
+            OutcomeCounts { succeeded: 2, failed: 1 }
+        );
+        assert_eq!(
+            store.outcomes_since(OperationKind::Submit, since).await,
+            OutcomeCounts { succeeded: 0, failed: 1 }
+        );
+        assert_eq!(
+            store.outcomes_since(OperationKind::Return, 0).await,
+            OutcomeCounts { succeeded: 2, failed: 2 }
+        );
+    }
+
+    #[tokio::test]
+    async fn reaching_a_terminal_state_stamps_completion_once() {
+        let store = OperationStore::new();
+        let op = store
+            .insert(Operation::new("op".to_string(), OperationKind::Submit, Value::Null, HashSet::new()))
+            .await;
+        assert!(op.completed_at.is_none());
+
+        store.set_status("op", OperationStatus::InProgress).await;
+        assert!(store.get("op").await.expect("op").completed_at.is_none());
+
+        store.set_status("op", OperationStatus::Failed).await;
+        assert!(store.get("op").await.expect("op").completed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn operation_ttls_are_jittered_within_band() {
+        let ttl = OperationTtl { base: Duration::from_secs(3600), jitter_pct: 10 };
+        let store = OperationStore::new().with_ttl(Some(ttl));
+        let mut ttls = Vec::new();
+        for _ in 0..2 {
+            let op = Operation::new(uuid::Uuid::new_v4().to_string(), OperationKind::Submit, Value::Null, HashSet::new());
+            ttls.push(store.insert(op).await.ttl_ms.expect("ttl assigned"));
+        }
+
+        for ttl_ms in &ttls {
+            assert!((3_240_000..=3_960_000).contains(ttl_ms), "{} outside the jitter band", ttl_ms);
+        }
+        assert_ne!(ttls[0], ttls[1]);
+    }
+
+    #[tokio::test]
+    async fn replay_keeps_only_recent_events_after_the_given_id() {
+        let sse = Broadcasters::new();
+        for n in 1..=EVENT_HISTORY_LEN + 6 {
+            sse.notify("op", format!("event {}", n)).await;
+        }
+
+        let (replay, _rx) = sse.subscribe_since("op", 0).await;
+        let ids: Vec<u64> = replay.iter().map(|e| e.id).collect();
+        assert_eq!(ids, (7..=EVENT_HISTORY_LEN as u64 + 6).collect::<Vec<_>>());
+
+        let (replay, _rx) = sse.subscribe_since("op", EVENT_HISTORY_LEN as u64 + 4).await;
+        let data: Vec<&str> = replay.iter().map(|e| e.data.as_str()).collect();
+        assert_eq!(data, ["event 69", "event 70"]);
+    }
 
+    #[test]
+    fn operations_round_trip_through_hash_fields() {
+        let mut op = finished(OperationKind::Submit, OperationStatus::Failed, Some(now_secs()));
+        op.item = serde_json::json!({"ip": "10.0.0.1"});
+        op.message = Some("subscriber `dns` failed".to_string());
+        op.subscribers.insert("dns".to_string(), OperationStatus::Failed);
+
+        let fields: HashMap<String, String> = op.fields(None).into_iter().collect();
+        assert_eq!(fields["status"], r#""failed""#);
+        let restored = Operation::from_fields(fields).expect("restores");
+        assert_eq!(serde_json::to_value(&restored).unwrap(), serde_json::to_value(&op).unwrap());
+
+        let partial = op.fields(Some(&["status", "completed_at"]));
+        assert_eq!(partial.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn succeeded_operations_are_dropped_after_retention() {
+        let ttl = OperationTtl { base: Duration::from_secs(60), jitter_pct: 0 };
+        let store = OperationStore::new().with_ttl(Some(ttl));
+        let done = store.insert(finished(OperationKind::Return, OperationStatus::Pending, None)).await.id;
+        let running = store.insert(finished(OperationKind::Submit, OperationStatus::InProgress, None)).await.id;
+        store.set_status(&done, OperationStatus::Succeeded).await;
+        assert!(store.get(&done).await.is_some());
+
+        // Move the clock past the retention window by backdating every timestamp
+        for op in store.inner.write().await.values_mut() {
+            op.created_at -= 120;
+            op.completed_at = op.completed_at.map(|at| at - 120);
+        }
+        assert!(store.get(&done).await.is_none());
+        assert!(store.get(&running).await.is_some(), "unfinished operations are kept regardless of age");
+        assert_eq!(store.purge_expired().await, 1);
+        assert!(store.get(&running).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn finished_operations_expire_after_their_ttl() {
+        let store = OperationStore::new();
+        let mut op = finished(OperationKind::Return, OperationStatus::Succeeded, Some(now_secs() - 10));
+        op.ttl_ms = Some(5_000);
+        let expired = store.insert(op).await.id;
+        let mut op = finished(OperationKind::Return, OperationStatus::InProgress, None);
+        op.ttl_ms = Some(0);
+        let running = store.insert(op).await.id;
+
+        assert!(store.get(&expired).await.is_none());
+        assert!(store.get(&running).await.is_some(), "unfinished operations never expire");
+        assert_eq!(store.purge_expired().await, 1);
+    }
+}
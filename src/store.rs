@@ -1,6 +1,13 @@
-use redis::{Client, Commands, RedisResult};
+use deadpool::managed::{self, Object, Pool, RecycleResult};
+use redis::aio::ConnectionLike;
+use redis::{AsyncCommands, Client, Cmd, Pipeline, RedisError, RedisFuture, RedisResult};
+use rocket::futures::StreamExt;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::time::Duration;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 // The key name for the freelist in Redis
 const FREELIST_KEY: &str = "freelist";
@@ -8,62 +15,1340 @@ const FREELIST_KEY: &str = "freelist";
 const FREELIST_NOTIFY_CHANNEL: &str = "freelist:notify";
 // Hash key for tracking borrowed items and their owners
 const BORROWED_ITEMS_KEY: &str = "borrowed_items";
+// Hash key for per-borrow metadata, keyed like BORROWED_ITEMS_KEY
+const BORROW_RECORDS_KEY: &str = "borrow_records";
+// Hash mapping each active borrow token back to its item
+const BORROW_TOKENS_KEY: &str = "borrow_tokens";
+// Set of every item that legitimately belongs to a pool
+const KNOWN_ITEMS_KEY: &str = "known_items";
+// Sorted set of batch reservation ids scored by their expiry (unix seconds)
+const RESERVATION_DEADLINES_KEY: &str = "reservation_deadlines";
+// Prefix of the per-reservation sets holding the reserved items
+const RESERVATION_KEY_PREFIX: &str = "reservation:";
+// Sorted set of reservation ids already warned about, scored by their expiry
+const RESERVATION_WARNED_KEY: &str = "reservation_warned";
+// Prefix of the per-field freelist indexes (`freelist:<field>:<value>`)
+const FREELIST_INDEX_PREFIX: &str = "freelist:";
+// Set of every index key created, so indexes can be rebuilt from scratch
+const FREELIST_INDEXES_KEY: &str = "freelist:indexes";
+// Sorted set of freelist members that are only available until a deadline (unix seconds)
+const AVAILABLE_UNTIL_KEY: &str = "available_until";
+// Pool name of the main freelist; other pools live under POOL_KEY_PREFIX
+pub const DEFAULT_POOL: &str = "default";
+// Prefix of the keys of other named pools (`pool:<name>:freelist`, ...)
+const POOL_KEY_PREFIX: &str = "pool:";
+// Set of the named pools other than the default one that items were submitted, returned or moved into
+const POOLS_KEY: &str = "pools";
+// Sorted set of borrow tokens with a lease, scored by the lease deadline (unix seconds)
+const BORROW_LEASES_KEY: &str = "borrow_leases";
+// Sorted set of `<borrow token>:<deadline>` leases already warned about, scored by the deadline
+const LEASE_WARNED_KEY: &str = "lease_warned";
+// List of admin audit entries (JSON), newest first
+const ADMIN_AUDIT_KEY: &str = "admin_audit";
+// List of borrow, return and submit audit entries (JSON), newest first; shared by all pools
+const ITEM_AUDIT_KEY: &str = "audit_log";
+// Operator metadata per canonical item; shared by all pools and kept across borrows
+const ITEM_METADATA_KEY: &str = "item_metadata";
+// Prefix of the hashes persisting operations (`operations:<id>`), one JSON value per field
+const OPERATION_KEY_PREFIX: &str = "operations:";
+// Prefix of the `Idempotency-Key` claims (`idempotency_keys:<endpoint>:<key>`), each holding an operation id
+const IDEMPOTENCY_KEY_PREFIX: &str = "idempotency_keys:";
+// Sorted set of queued subscriber retries (JSON entries), scored by their next attempt (unix seconds)
+const RETRY_QUEUE_KEY: &str = "retry_queue";
+// Most queued retries claimed by one sweep
+const RETRY_CLAIM_BATCH: usize = 100;
+// Hash of two-phase returns awaiting `/return/confirm` (JSON), keyed by operation id
+const PENDING_RELEASES_KEY: &str = "pending_releases";
+// Most admin audit entries kept; older ones are trimmed
+const ADMIN_AUDIT_MAX_ENTRIES: isize = 10_000;
+// Most borrow, return and submit audit entries kept; older ones are trimmed
+const ITEM_AUDIT_MAX_ENTRIES: isize = 100_000;
+// Members read per SSCAN/HSCAN/ZSCAN call, and written per SADD/HSET/ZADD, when migrating
+const MIGRATE_BATCH: usize = 500;
+// Items added per script call when seeding, so a large range does not block Redis for long
+const SEED_BATCH: usize = 1000;
+// Mutex held around destructive admin bulk operations
+const ADMIN_LOCK_KEY: &str = "admin_lock";
+
+// Drop an item's borrow token, metadata and token index entry.
+// KEYS: borrowed_items, borrow_records, borrow_tokens; ARGV: item key.
+// Returns 1 if the item was borrowed, 0 otherwise.
+const CLEAR_BORROW_SCRIPT: &str = r#"
+local token = redis.call('HGET', KEYS[1], ARGV[1])
+if token then
+    redis.call('HDEL', KEYS[3], token)
+end
+redis.call('HDEL', KEYS[2], ARGV[1])
+return redis.call('HDEL', KEYS[1], ARGV[1])
+"#;
+
+// Move the item held under a borrow token back to the freelist.
+// KEYS: borrow_tokens, borrowed_items, borrow_records, freelist; ARGV: token, notify channel.
+// Returns the item key, or nil if the token is not active.
+const FORCE_RETURN_BY_TOKEN_SCRIPT: &str = r#"
+local item = redis.call('HGET', KEYS[1], ARGV[1])
+if not item then
+    return false
+end
+if redis.call('HGET', KEYS[2], item) ~= ARGV[1] then
+    redis.call('HDEL', KEYS[1], ARGV[1])
+    return false
+end
+redis.call('HDEL', KEYS[1], ARGV[1])
+redis.call('HDEL', KEYS[2], item)
+redis.call('HDEL', KEYS[3], item)
+redis.call('SADD', KEYS[4], item)
+redis.call('PUBLISH', ARGV[2], 'item_returned')
+return item
+"#;
+
+// Pop ARGV[1] items from the freelist into a new reservation, all or nothing.
+// KEYS: freelist, reservation set, reservation_deadlines; ARGV: count, reservation id, expires_at.
+// Returns the reserved item keys, or nil if the freelist holds fewer than count items.
+const RESERVE_BATCH_SCRIPT: &str = r#"
+if redis.call('SCARD', KEYS[1]) < tonumber(ARGV[1]) then
+    return false
+end
+local items = redis.call('SPOP', KEYS[1], ARGV[1])
+for _, item in ipairs(items) do
+    redis.call('SADD', KEYS[2], item)
+end
+redis.call('ZADD', KEYS[3], ARGV[3], ARGV[2])
+return items
+"#;
+
+// Take items out of a reservation (all of them when no item keys are given).
+// KEYS: reservation set, reservation_deadlines; ARGV: reservation id, item keys...
+// Returns the taken item keys, or nil if the reservation does not exist.
+const TAKE_RESERVED_SCRIPT: &str = r#"
+if not redis.call('ZSCORE', KEYS[2], ARGV[1]) then
+    return false
+end
+local items
+if #ARGV > 1 then
+    items = {}
+    for i = 2, #ARGV do
+        if redis.call('SISMEMBER', KEYS[1], ARGV[i]) == 0 then
+            return redis.error_reply('Item not in reservation')
+        end
+    end
+    for i = 2, #ARGV do
+        redis.call('SREM', KEYS[1], ARGV[i])
+        items[#items + 1] = ARGV[i]
+    end
+else
+    items = redis.call('SMEMBERS', KEYS[1])
+    redis.call('DEL', KEYS[1])
+end
+if redis.call('SCARD', KEYS[1]) == 0 then
+    redis.call('ZREM', KEYS[2], ARGV[1])
+end
+return items
+"#;
+
+// Put every item of a reservation back in the freelist and drop the reservation.
+// KEYS: reservation set, reservation_deadlines, freelist; ARGV: reservation id, notify channel.
+// Returns the items released, or nil if the reservation does not exist.
+const ABORT_RESERVATION_SCRIPT: &str = r#"
+if not redis.call('ZSCORE', KEYS[2], ARGV[1]) then
+    return false
+end
+local items = redis.call('SMEMBERS', KEYS[1])
+for _, item in ipairs(items) do
+    redis.call('SADD', KEYS[3], item)
+end
+redis.call('DEL', KEYS[1])
+redis.call('ZREM', KEYS[2], ARGV[1])
+if #items > 0 then
+    redis.call('PUBLISH', ARGV[2], 'item_returned')
+end
+return items
+"#;
+
+// Release every reservation whose deadline has passed back to the freelist.
+// KEYS: reservation_deadlines, freelist; ARGV: now, reservation key prefix, notify channel.
+// Returns the items released.
+const EXPIRE_RESERVATIONS_SCRIPT: &str = r#"
+local ids = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1])
+local released = {}
+for _, id in ipairs(ids) do
+    local key = ARGV[2] .. id
+    for _, item in ipairs(redis.call('SMEMBERS', key)) do
+        redis.call('SADD', KEYS[2], item)
+        table.insert(released, item)
+    end
+    redis.call('DEL', key)
+    redis.call('ZREM', KEYS[1], id)
+end
+if #released > 0 then
+    redis.call('PUBLISH', ARGV[3], 'item_returned')
+end
+return released
+"#;
+
+// Announce each item of every reservation expiring within the warning window, once per reservation.
+// KEYS: reservation_deadlines, reservation_warned; ARGV: now, window end, reservation key prefix, notify channel.
+// Returns the published messages.
+const WARN_EXPIRING_SCRIPT: &str = r#"
+redis.call('ZREMRANGEBYSCORE', KEYS[2], '-inf', ARGV[1])
+local due = redis.call('ZRANGEBYSCORE', KEYS[1], '(' .. ARGV[1], ARGV[2], 'WITHSCORES')
+local messages = {}
+for i = 1, #due, 2 do
+    local id, deadline = due[i], due[i + 1]
+    if redis.call('ZADD', KEYS[2], 'NX', deadline, id) == 1 then
+        local expires_in = tonumber(deadline) - tonumber(ARGV[1])
+        for _, item in ipairs(redis.call('SMEMBERS', ARGV[3] .. id)) do
+            local message = '{"event":"lease_expiring","item":' .. item .. ',"expires_in":' .. expires_in .. '}'
+            redis.call('PUBLISH', ARGV[4], message)
+            table.insert(messages, message)
+        end
+    end
+end
+return messages
+"#;
+
+// Announce each borrowed item whose lease expires within the warning window, once per lease
+// deadline, so a renewed lease is announced again.
+// KEYS: borrow_leases, lease_warned, borrow_tokens; ARGV: now, window end, notify channel.
+// Returns the published messages.
+const WARN_EXPIRING_LEASES_SCRIPT: &str = r#"
+redis.call('ZREMRANGEBYSCORE', KEYS[2], '-inf', ARGV[1])
+local due = redis.call('ZRANGEBYSCORE', KEYS[1], '(' .. ARGV[1], ARGV[2], 'WITHSCORES')
+local messages = {}
+for i = 1, #due, 2 do
+    local token, deadline = due[i], due[i + 1]
+    local item = redis.call('HGET', KEYS[3], token)
+    if item and redis.call('ZADD', KEYS[2], 'NX', deadline, token .. ':' .. deadline) == 1 then
+        local expires_in = tonumber(deadline) - tonumber(ARGV[1])
+        local message = '{"event":"lease_expiring","item":' .. item .. ',"expires_in":' .. expires_in .. '}'
+        redis.call('PUBLISH', ARGV[3], message)
+        table.insert(messages, message)
+    end
+end
+return messages
+"#;
+
+// Drop time-boxed members whose availability ended from the freelist. Members that are
+// borrowed at that point are not in the freelist and simply lose their deadline.
+// KEYS: available_until, freelist; ARGV: now.
+// Returns the number of members removed from the freelist.
+const EXPIRE_AVAILABLE_SCRIPT: &str = r#"
+local members = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1])
+local removed = 0
+for _, member in ipairs(members) do
+    removed = removed + redis.call('SREM', KEYS[2], member)
+    redis.call('ZREM', KEYS[1], member)
+end
+return removed
+"#;
+
+// Add a member to (or remove it from) its per-field index sets.
+// KEYS: index registry, index sets...; ARGV: 'add' or 'rem', member.
+const INDEX_ITEM_SCRIPT: &str = r#"
+for i = 2, #KEYS do
+    if ARGV[1] == 'add' then
+        redis.call('SADD', KEYS[i], ARGV[2])
+        redis.call('SADD', KEYS[1], KEYS[i])
+    else
+        redis.call('SREM', KEYS[i], ARGV[2])
+    end
+end
+return 1
+"#;
+
+// Pop a free member through one index set. Index entries whose member is no longer
+// free are stale (the freelist is authoritative) and are discarded along the way.
+// KEYS: freelist, index set; ARGV: index key prefix, indexed fields...
+// Returns the member, or nil if no free member matches.
+const BORROW_INDEXED_SCRIPT: &str = r#"
+while true do
+    local member = redis.call('SPOP', KEYS[2])
+    if not member then
+        return false
+    end
+    if redis.call('SREM', KEYS[1], member) == 1 then
+        local ok, item = pcall(cjson.decode, member)
+        if ok and type(item) == 'table' then
+            for i = 2, #ARGV do
+                local value = item[ARGV[i]]
+                if type(value) == 'string' then
+                    redis.call('SREM', ARGV[1] .. ARGV[i] .. ':' .. value, member)
+                end
+            end
+        end
+        return member
+    end
+end
+"#;
+
+// Drop every index set and index the freelist again from scratch.
+// KEYS: freelist, index registry; ARGV: index key prefix, indexed fields...
+// Returns the number of index entries written.
+const REBUILD_INDEXES_SCRIPT: &str = r#"
+for _, key in ipairs(redis.call('SMEMBERS', KEYS[2])) do
+    redis.call('DEL', key)
+end
+redis.call('DEL', KEYS[2])
+local indexed = 0
+for _, member in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    local ok, item = pcall(cjson.decode, member)
+    if ok and type(item) == 'table' then
+        for i = 2, #ARGV do
+            local value = item[ARGV[i]]
+            if type(value) == 'string' then
+                local key = ARGV[1] .. ARGV[i] .. ':' .. value
+                redis.call('SADD', key, member)
+                redis.call('SADD', KEYS[2], key)
+                indexed = indexed + 1
+            end
+        end
+    end
+end
+return indexed
+"#;
+
+// Move a free member from one pool's freelist to another's, registering the destination pool.
+// KEYS: source freelist, destination freelist, pool registry;
+// ARGV: member, destination pool name, notify channel or ''.
+// Returns 1 if moved, 0 if the member was not free in the source pool.
+const TRANSFER_ITEM_SCRIPT: &str = r#"
+if redis.call('SREM', KEYS[1], ARGV[1]) == 0 then
+    return 0
+end
+redis.call('SADD', KEYS[2], ARGV[1])
+redis.call('SADD', KEYS[3], ARGV[2])
+if ARGV[3] ~= '' then
+    redis.call('PUBLISH', ARGV[3], 'item_returned')
+end
+return 1
+"#;
+
+// Converge the freelist to exactly the given members, leaving borrowed items alone.
+// KEYS: freelist, borrowed_items; ARGV: notify channel, desired members...
+// Returns {added, removed, unchanged}; borrowed members count as unchanged.
+const SET_FREELIST_SCRIPT: &str = r#"
+local desired = {}
+local added, unchanged = 0, 0
+for i = 2, #ARGV do
+    local item = ARGV[i]
+    if not desired[item] then
+        desired[item] = true
+        if redis.call('HEXISTS', KEYS[2], item) == 1 or redis.call('SISMEMBER', KEYS[1], item) == 1 then
+            unchanged = unchanged + 1
+        else
+            redis.call('SADD', KEYS[1], item)
+            added = added + 1
+        end
+    end
+end
+local removed = 0
+for _, item in ipairs(redis.call('SMEMBERS', KEYS[1])) do
+    if not desired[item] then
+        redis.call('SREM', KEYS[1], item)
+        removed = removed + 1
+    end
+end
+if added > 0 then
+    redis.call('PUBLISH', ARGV[1], 'item_returned')
+end
+return {added, removed, unchanged}
+"#;
+
+// Add items to the freelist unless they are borrowed, for seeding a pool.
+// KEYS: freelist, borrowed; ARGV: notify channel, items... Returns the items added.
+const SEED_ITEMS_SCRIPT: &str = r#"
+local added = {}
+for i = 2, #ARGV do
+    local item = ARGV[i]
+    if redis.call('HEXISTS', KEYS[2], item) == 0 and redis.call('SADD', KEYS[1], item) == 1 then
+        table.insert(added, item)
+    end
+end
+if #added > 0 then
+    redis.call('PUBLISH', ARGV[1], 'item_returned')
+end
+return added
+"#;
+
+// Claim an idempotency key for an operation unless an earlier request holds it.
+// KEYS: idempotency key; ARGV: operation id, TTL in seconds.
+// Returns nil once claimed, or the operation id of the earlier claim.
+const CLAIM_IDEMPOTENCY_KEY_SCRIPT: &str = r#"
+if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'EX', ARGV[2]) then
+    return false
+end
+return redis.call('GET', KEYS[1])
+"#;
+
+// Release a lock only if it is still held under the caller's token.
+// KEYS: lock key; ARGV: token. Returns 1 if released.
+const RELEASE_LOCK_SCRIPT: &str = r#"
+if redis.call('GET', KEYS[1]) == ARGV[1] then
+    return redis.call('DEL', KEYS[1])
+end
+return 0
+"#;
+
+// Single-command and pipelined operations, as scripts for `scripts_only` mode.
+// KEYS: freelist. Returns the popped member or nil.
+const BORROW_SCRIPT: &str = r#"
+return redis.call('SPOP', KEYS[1])
+"#;
+
+// Deterministic variant for tests: pop the lexicographically smallest member.
+// KEYS: freelist. Returns the popped member or nil.
+const BORROW_SMALLEST_SCRIPT: &str = r#"
+local members = redis.call('SMEMBERS', KEYS[1])
+if #members == 0 then
+    return false
+end
+table.sort(members)
+redis.call('SREM', KEYS[1], members[1])
+return members[1]
+"#;
+
+// Pop a free member and record it as borrowed in one step, so no crash can leave it popped
+// but unrecorded.
+// KEYS: freelist, borrowed_items, borrow_records, borrow_tokens, borrow_leases;
+// ARGV: borrow token, record JSON, lease deadline or '', '1' to take the smallest member.
+// Returns {member, 1} once recorded, {member, 0} if the member is already recorded under
+// another token, or nil if the freelist is empty. A member already recorded is a stale
+// freelist entry and is dropped, so the next borrow (which, taking the smallest member,
+// would pick it again) gets another item; the holder's return puts it back.
+const BORROW_AND_RECORD_SCRIPT: &str = r#"
+local member
+if ARGV[4] == '1' then
+    local members = redis.call('SMEMBERS', KEYS[1])
+    if #members == 0 then
+        return false
+    end
+    table.sort(members)
+    member = members[1]
+else
+    member = redis.call('SRANDMEMBER', KEYS[1])
+    if not member then
+        return false
+    end
+end
+if redis.call('HSETNX', KEYS[2], member, ARGV[1]) == 0 then
+    redis.call('SREM', KEYS[1], member)
+    return {member, 0}
+end
+redis.call('SREM', KEYS[1], member)
+redis.call('HSET', KEYS[3], member, ARGV[2])
+redis.call('HSET', KEYS[4], ARGV[1], member)
+if ARGV[3] ~= '' then
+    redis.call('ZADD', KEYS[5], ARGV[3], ARGV[1])
+end
+return {member, 1}
+"#;
+
+// Take one particular free member and record it as borrowed in one step.
+// KEYS: freelist, borrowed_items, borrow_records, borrow_tokens, borrow_leases;
+// ARGV: freelist member, borrow key (the canonical item), borrow token, record JSON,
+// lease deadline or ''. Returns 1 once recorded, 0 if the member is not free, or -1 if it
+// is already recorded under another token (it then stays in the freelist).
+const BORROW_SPECIFIC_SCRIPT: &str = r#"
+if redis.call('SISMEMBER', KEYS[1], ARGV[1]) == 0 then
+    return 0
+end
+if redis.call('HSETNX', KEYS[2], ARGV[2], ARGV[3]) == 0 then
+    return -1
+end
+redis.call('SREM', KEYS[1], ARGV[1])
+redis.call('HSET', KEYS[3], ARGV[2], ARGV[4])
+redis.call('HSET', KEYS[4], ARGV[3], ARGV[2])
+if ARGV[5] ~= '' then
+    redis.call('ZADD', KEYS[5], ARGV[5], ARGV[3])
+end
+return 1
+"#;
+
+// Borrow and describe what is left in one step.
+// KEYS: freelist; ARGV: sample size, '1' to pop the smallest member instead of a random one.
+// Returns {member, remaining count, sample members...} or nil if the freelist is empty.
+const BORROW_WITH_CONTEXT_SCRIPT: &str = r#"
+local member
+if ARGV[2] == '1' then
+    local members = redis.call('SMEMBERS', KEYS[1])
+    if #members == 0 then
+        return false
+    end
+    table.sort(members)
+    member = members[1]
+    redis.call('SREM', KEYS[1], member)
+else
+    member = redis.call('SPOP', KEYS[1])
+    if not member then
+        return false
+    end
+end
+local result = {member, redis.call('SCARD', KEYS[1])}
+for _, m in ipairs(redis.call('SRANDMEMBER', KEYS[1], tonumber(ARGV[1]))) do
+    table.insert(result, m)
+end
+return result
+"#;
+
+// KEYS: freelist; ARGV: member, notify channel. Returns 1 if the member was new.
+const RETURN_ITEM_SCRIPT: &str = r#"
+local added = redis.call('SADD', KEYS[1], ARGV[1])
+redis.call('PUBLISH', ARGV[2], 'item_returned')
+return added
+"#;
+
+// Announce a freelist mutation with the pool's counts after it.
+// KEYS: freelist, borrowed_items; ARGV: notify channel, event, item JSON, pool name.
+const FREELIST_EVENT_SCRIPT: &str = r#"
+local message = '{"event":"' .. ARGV[2] .. '","item":' .. ARGV[3] .. ',"pool":"' .. ARGV[4]
+    .. '","free":' .. redis.call('SCARD', KEYS[1]) .. ',"borrowed":' .. redis.call('HLEN', KEYS[2]) .. '}'
+redis.call('PUBLISH', ARGV[1], message)
+return message
+"#;
+
+// KEYS: borrowed_items, borrow_records, borrow_tokens, borrow_leases;
+// ARGV: item key, token, record JSON, lease deadline ('' for none).
+// Returns 0 without writing if the item is already recorded under another token.
+const RECORD_BORROW_SCRIPT: &str = r#"
+if redis.call('HSETNX', KEYS[1], ARGV[1], ARGV[2]) == 0 then
+  return 0
+end
+redis.call('HSET', KEYS[2], ARGV[1], ARGV[3])
+redis.call('HSET', KEYS[3], ARGV[2], ARGV[1])
+if ARGV[4] ~= '' then
+  redis.call('ZADD', KEYS[4], ARGV[4], ARGV[2])
+end
+return 1
+"#;
+
+// Take the borrow tokens whose lease ran out by ARGV[1] off the lease set.
+// KEYS: borrow_leases; ARGV: now.
+// Returns the tokens; ones already returned are no longer active and are skipped by the caller.
+const TAKE_EXPIRED_LEASES_SCRIPT: &str = r#"
+local tokens = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1])
+for _, token in ipairs(tokens) do
+    redis.call('ZREM', KEYS[1], token)
+end
+return tokens
+"#;
+
+// Queue a subscriber retry unless the queue is full.
+// KEYS: retry_queue; ARGV: entry, due, capacity. Returns 1 if queued, 0 if full.
+const ENQUEUE_RETRY_SCRIPT: &str = r#"
+if redis.call('ZCARD', KEYS[1]) >= tonumber(ARGV[3]) then
+    return 0
+end
+redis.call('ZADD', KEYS[1], ARGV[2], ARGV[1])
+return 1
+"#;
+
+// Claim the retries due by ARGV[1] by pushing them back to ARGV[2], so they are picked
+// up again only if whoever claimed them does not finish them by then.
+// KEYS: retry_queue; ARGV: now, claimed until, most entries. Returns the entries.
+const CLAIM_RETRIES_SCRIPT: &str = r#"
+local entries = redis.call('ZRANGEBYSCORE', KEYS[1], '-inf', ARGV[1], 'LIMIT', 0, tonumber(ARGV[3]))
+for _, entry in ipairs(entries) do
+    redis.call('ZADD', KEYS[1], ARGV[2], entry)
+end
+return entries
+"#;
+
+// Take one field out of a hash, so only one caller gets it.
+// KEYS: hash; ARGV: field. Returns the value, or nil if the field is not set.
+const TAKE_FIELD_SCRIPT: &str = r#"
+local value = redis.call('HGET', KEYS[1], ARGV[1])
+if value then
+    redis.call('HDEL', KEYS[1], ARGV[1])
+end
+return value
+"#;
+
+/// Every script the store may invoke, loaded up front by [`Store::load_scripts`]
+const SCRIPTS: &[&str] = &[
+    BORROW_AND_RECORD_SCRIPT,
+    BORROW_SPECIFIC_SCRIPT,
+    CLEAR_BORROW_SCRIPT,
+    FORCE_RETURN_BY_TOKEN_SCRIPT,
+    RESERVE_BATCH_SCRIPT,
+    TAKE_RESERVED_SCRIPT,
+    ABORT_RESERVATION_SCRIPT,
+    EXPIRE_RESERVATIONS_SCRIPT,
+    EXPIRE_AVAILABLE_SCRIPT,
+    WARN_EXPIRING_SCRIPT,
+    WARN_EXPIRING_LEASES_SCRIPT,
+    SET_FREELIST_SCRIPT,
+    SEED_ITEMS_SCRIPT,
+    TRANSFER_ITEM_SCRIPT,
+    INDEX_ITEM_SCRIPT,
+    BORROW_INDEXED_SCRIPT,
+    REBUILD_INDEXES_SCRIPT,
+    RELEASE_LOCK_SCRIPT,
+    CLAIM_IDEMPOTENCY_KEY_SCRIPT,
+    BORROW_SCRIPT,
+    BORROW_SMALLEST_SCRIPT,
+    BORROW_WITH_CONTEXT_SCRIPT,
+    RETURN_ITEM_SCRIPT,
+    FREELIST_EVENT_SCRIPT,
+    RECORD_BORROW_SCRIPT,
+    TAKE_EXPIRED_LEASES_SCRIPT,
+    ENQUEUE_RETRY_SCRIPT,
+    CLAIM_RETRIES_SCRIPT,
+    TAKE_FIELD_SCRIPT,
+];
+
+/// A group of items held out of the freelist until committed, aborted or expired
+#[derive(Debug, Clone)]
+pub struct Reservation {
+    pub id: String,
+    pub items: Vec<Value>,
+    /// Unix timestamp (seconds) after which the items go back to the freelist
+    pub expires_at: u64,
+}
+
+/// Metadata recorded alongside a borrow token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BorrowRecord {
+    /// Unique id of this borrow, carried over to the eventual return operation
+    pub borrow_id: String,
+    /// Unix timestamp (seconds) at which the item was borrowed
+    pub borrowed_at: u64,
+    /// Owner id (or client IP) of the borrowing request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Whether `owner` was given as `X-Owner-Id`; only then do returns have to match it
+    #[serde(default = "crate::config::default_true")]
+    pub owner_explicit: bool,
+    /// Unix timestamp (seconds) at which the lease runs out and the item is reclaimed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lease_expires_at: Option<u64>,
+    /// Unix timestamp (seconds) after which the borrow token no longer returns the item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_expires_at: Option<u64>,
+}
+
+impl BorrowRecord {
+    /// Unix timestamp (seconds) at which the sweeper reclaims the item: the end of the
+    /// lease or of the borrow token, whichever comes first
+    pub fn reclaim_at(&self) -> Option<u64> {
+        match (self.lease_expires_at, self.token_expires_at) {
+            (Some(lease), Some(token)) => Some(lease.min(token)),
+            (lease, token) => lease.or(token),
+        }
+    }
+}
+
+/// Requester a borrow is recorded for
+#[derive(Debug, Clone, Copy)]
+pub struct Owner<'a> {
+    /// Owner id, or client IP
+    pub id: &'a str,
+    /// Whether `id` was given explicitly rather than taken from the client IP
+    pub explicit: bool,
+}
+
+/// A fresh borrow record starting now, with its JSON as stored in `borrow_records`
+fn new_borrow_record(
+    owner: Option<Owner<'_>>,
+    lease: Option<Duration>,
+    token_lifetime: Option<Duration>,
+) -> RedisResult<(BorrowRecord, String)> {
+    let borrowed_at = now_secs();
+    let record = BorrowRecord {
+        borrow_id: uuid::Uuid::new_v4().to_string(),
+        borrowed_at,
+        owner: owner.map(|o| o.id.to_string()),
+        owner_explicit: owner.is_some_and(|o| o.explicit),
+        lease_expires_at: lease.map(|lease| borrowed_at + lease.as_secs()),
+        token_expires_at: token_lifetime.map(|lifetime| borrowed_at + lifetime.as_secs()),
+    };
+    let record_json = serde_json::to_string(&record).map_err(|e| {
+        redis::RedisError::from((
+            redis::ErrorKind::TypeError,
+            "Failed to serialize JSON",
+            format!("{}", e),
+        ))
+    })?;
+    Ok((record, record_json))
+}
+
+/// Error of a borrow whose item is already recorded under another token
+fn already_borrowed() -> redis::RedisError {
+    redis::RedisError::from((
+        redis::ErrorKind::ResponseError,
+        "Item already borrowed",
+        "the item is recorded under another borrow token".to_string(),
+    ))
+}
+
+/// Clear the borrow bookkeeping for an item of a pool; returns 1 if it was borrowed
+async fn clear_borrow(con: &mut PooledConnection, keys: &PoolKeys, item_key: &str) -> RedisResult<i32> {
+    redis::Script::new(CLEAR_BORROW_SCRIPT)
+        .key(&keys.borrowed)
+        .key(&keys.records)
+        .key(&keys.tokens)
+        .arg(item_key)
+        .invoke_async(con)
+        .await
+}
+
+/// Parse a member read back from Redis
+fn parse_member(member: &str) -> RedisResult<Value> {
+    serde_json::from_str::<Value>(member).map_err(|e| {
+        redis::RedisError::from((
+            redis::ErrorKind::TypeError,
+            "Stored value is not valid JSON",
+            format!("{}", e),
+        ))
+    })
+}
+
+/// Parse item keys read back from Redis
+fn parse_items(keys: Vec<String>) -> RedisResult<Vec<Value>> {
+    keys.iter().map(|k| parse_member(k)).collect()
+}
+
+/// Freelist mutations announced on the notify channel by `publish_freelist_event`
+pub const FREELIST_EVENTS: [&str; 4] = ["borrowed", "returned", "submitted", "deleted"];
+
+/// The `event` of a structured notify-channel message; `None` for plain ones like `item_returned`
+fn message_event(message: &str) -> Option<String> {
+    serde_json::from_str::<Value>(message).ok()?.get("event")?.as_str().map(str::to_string)
+}
+
+/// Whether a notify-channel message is one of `FREELIST_EVENTS`
+pub fn is_freelist_event(message: &str) -> bool {
+    message_event(message).is_some_and(|event| FREELIST_EVENTS.contains(&event.as_str()))
+}
+
+/// Whether a notify-channel message may mean an item became free, i.e. it is not a
+/// `borrowed` or `deleted` event
+fn may_free_item(message: &str) -> bool {
+    !matches!(message_event(message).as_deref(), Some("borrowed" | "deleted"))
+}
+
+/// Serialize an item into the string used as its Redis member/field
+fn item_key(item: &Value) -> RedisResult<String> {
+    serde_json::to_string(item).map_err(|e| {
+        redis::RedisError::from((
+            redis::ErrorKind::TypeError,
+            "Failed to serialize JSON",
+            format!("{}", e),
+        ))
+    })
+}
+
+/// `name` under the key prefix: `<prefix>:<name>`, or `name` as-is without a prefix
+fn prefixed(prefix: &str, name: &str) -> String {
+    match prefix {
+        "" => name.to_string(),
+        prefix => format!("{}:{}", prefix, name),
+    }
+}
+
+/// Redis keys of one pool's freelist and borrow bookkeeping. The default pool keeps the
+/// historical key names; pool `<name>` uses `pool:<name>:<key>` and its own notify channel.
+/// Both go under the store's key prefix, if any.
+#[derive(Debug)]
+struct PoolKeys {
+    name: String,
+    freelist: String,
+    notify: String,
+    borrowed: String,
+    records: String,
+    tokens: String,
+    leases: String,
+    lease_warned: String,
+    known: String,
+}
+
+impl PoolKeys {
+    fn new(prefix: &str, pool: &str) -> Self {
+        if pool == DEFAULT_POOL {
+            return Self {
+                name: pool.to_string(),
+                freelist: prefixed(prefix, FREELIST_KEY),
+                notify: prefixed(prefix, FREELIST_NOTIFY_CHANNEL),
+                borrowed: prefixed(prefix, BORROWED_ITEMS_KEY),
+                records: prefixed(prefix, BORROW_RECORDS_KEY),
+                tokens: prefixed(prefix, BORROW_TOKENS_KEY),
+                leases: prefixed(prefix, BORROW_LEASES_KEY),
+                lease_warned: prefixed(prefix, LEASE_WARNED_KEY),
+                known: prefixed(prefix, KNOWN_ITEMS_KEY),
+            };
+        }
+        let key = |name: &str| prefixed(prefix, &format!("{}{}:{}", POOL_KEY_PREFIX, pool, name));
+        Self {
+            name: pool.to_string(),
+            freelist: key(FREELIST_KEY),
+            notify: prefixed(prefix, &format!("{}:{}", FREELIST_NOTIFY_CHANNEL, pool)),
+            borrowed: key(BORROWED_ITEMS_KEY),
+            records: key(BORROW_RECORDS_KEY),
+            tokens: key(BORROW_TOKENS_KEY),
+            leases: key(BORROW_LEASES_KEY),
+            lease_warned: key(LEASE_WARNED_KEY),
+            known: key(KNOWN_ITEMS_KEY),
+        }
+    }
+}
+
+/// Whether `name` can name a pool: non-empty ASCII letters, digits, `-` and `_`
+pub fn is_valid_pool_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Current unix time in seconds
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+/// Connections held by a [`Store`] pool unless configured otherwise
+pub const DEFAULT_POOL_SIZE: usize = 16;
+
+/// How long a request waits for a free pooled connection unless configured otherwise
+pub const DEFAULT_POOL_WAIT: Duration = Duration::from_secs(5);
+
+/// How long to wait for one sentinel to answer before asking the next
+const SENTINEL_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Redis Sentinel deployment to find the current master through. The Redis URL then only
+/// supplies the scheme, credentials and database; host and port come from the sentinels.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Sentinel {
+    /// Sentinel addresses as `host:port` (port 26379 when left out), asked in order
+    pub addresses: Vec<String>,
+    /// Name the sentinels monitor the master under
+    pub master_name: String,
+    /// Password of the sentinels themselves, if they require one
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+impl Sentinel {
+    /// Ask the sentinels for the current master and point `redis_url` at it
+    pub async fn master_url(&self, redis_url: &str) -> RedisResult<String> {
+        let mut url = redis::parse_redis_url(redis_url)
+            .ok_or_else(|| RedisError::from((redis::ErrorKind::InvalidClientConfig, "Redis URL did not parse")))?;
+        let mut last_error = None;
+        for address in &self.addresses {
+            match self.ask(address).await {
+                Ok((host, port)) => {
+                    url.set_host(Some(&host)).map_err(|e| {
+                        RedisError::from((redis::ErrorKind::InvalidClientConfig, "Invalid master host", e.to_string()))
+                    })?;
+                    let _ = url.set_port(Some(port));
+                    return Ok(url.to_string());
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| RedisError::from((redis::ErrorKind::InvalidClientConfig, "No sentinels configured"))))
+    }
+
+    /// `SENTINEL GET-MASTER-ADDR-BY-NAME` on one sentinel
+    async fn ask(&self, address: &str) -> RedisResult<(String, u16)> {
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| {
+                RedisError::from((redis::ErrorKind::InvalidClientConfig, "Invalid sentinel port", address.to_string()))
+            })?),
+            None => (address, 26379),
+        };
+        let info = redis::ConnectionInfo {
+            addr: redis::ConnectionAddr::Tcp(host.to_string(), port),
+            redis: redis::RedisConnectionInfo { password: self.password.clone(), ..Default::default() },
+        };
+        let query = async {
+            let mut con = Client::open(info)?.get_async_connection().await?;
+            redis::cmd("SENTINEL")
+                .arg("GET-MASTER-ADDR-BY-NAME")
+                .arg(&self.master_name)
+                .query_async::<_, Option<(String, u16)>>(&mut con)
+                .await
+        };
+        match tokio::time::timeout(SENTINEL_TIMEOUT, query).await {
+            Ok(Ok(Some(master))) => Ok(master),
+            Ok(Ok(None)) => Err(RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "Sentinel does not know the master",
+                format!("{} at {}", self.master_name, address),
+            ))),
+            Ok(Err(e)) => Err(e),
+            Err(_) => Err(RedisError::from((redis::ErrorKind::IoError, "Sentinel timed out", address.to_string()))),
+        }
+    }
+}
+
+/// Opens the pooled Redis connections. The URL is parsed per connection, so a bad URL
+/// surfaces from the first command like a refused connection would. With a sentinel the
+/// master is looked up for every new connection, so a failover only costs the connections
+/// open to the old master.
+struct RedisManager {
+    redis_url: String,
+    sentinel: Option<Arc<Sentinel>>,
+    opened: Arc<AtomicUsize>,
+}
+
+impl RedisManager {
+    /// The URL of the Redis to connect to right now
+    async fn current_url(&self) -> RedisResult<String> {
+        match &self.sentinel {
+            Some(sentinel) => sentinel.master_url(&self.redis_url).await,
+            None => Ok(self.redis_url.clone()),
+        }
+    }
+}
+
+#[deadpool::async_trait]
+impl managed::Manager for RedisManager {
+    type Type = redis::aio::Connection;
+    type Error = RedisError;
+
+    async fn create(&self) -> RedisResult<redis::aio::Connection> {
+        let con = Client::open(self.current_url().await?)?.get_async_connection().await?;
+        self.opened.fetch_add(1, Ordering::Relaxed);
+        Ok(con)
+    }
+
+    /// Check an idle connection before reuse; a dead one is dropped and replaced. With a
+    /// sentinel so is one to a server that is no longer the master.
+    async fn recycle(&self, con: &mut redis::aio::Connection) -> RecycleResult<RedisError> {
+        if self.sentinel.is_none() {
+            redis::cmd("PING").query_async::<_, ()>(con).await?;
+            return Ok(());
+        }
+        let role: Vec<redis::Value> = redis::cmd("ROLE").query_async(con).await?;
+        match role.first() {
+            Some(redis::Value::Data(role)) if role == b"master" => Ok(()),
+            _ => Err(managed::RecycleError::StaticMessage("no longer the Redis master")),
+        }
+    }
+}
+
+/// A connection checked out of the pool, handed back when dropped
+struct PooledConnection(Object<RedisManager>);
+
+impl ConnectionLike for PooledConnection {
+    fn req_packed_command<'a>(&'a mut self, cmd: &'a Cmd) -> RedisFuture<'a, redis::Value> {
+        self.0.req_packed_command(cmd)
+    }
+
+    fn req_packed_commands<'a>(
+        &'a mut self,
+        cmd: &'a Pipeline,
+        offset: usize,
+        count: usize,
+    ) -> RedisFuture<'a, Vec<redis::Value>> {
+        self.0.req_packed_commands(cmd, offset, count)
+    }
+
+    fn get_db(&self) -> i64 {
+        self.0.get_db()
+    }
+}
+
+/// How a key is scanned and written by [`Store::migrate_to`]
+#[derive(Clone, Copy)]
+enum MigrateKind {
+    Set,
+    Hash,
+    SortedSet,
+}
+
+/// Scan one key in batches and add each batch to `target`; returns the members seen
+async fn copy_key<C: ConnectionLike>(
+    con: &mut PooledConnection,
+    mut target: Option<&mut C>,
+    key: &str,
+    kind: MigrateKind,
+) -> RedisResult<usize> {
+    let (scan, write) = match kind {
+        MigrateKind::Set => ("SSCAN", "SADD"),
+        MigrateKind::Hash => ("HSCAN", "HSET"),
+        MigrateKind::SortedSet => ("ZSCAN", "ZADD"),
+    };
+    let mut copied = 0;
+    let mut cursor = 0u64;
+    loop {
+        let (next, batch): (u64, Vec<String>) = redis::cmd(scan)
+            .arg(key)
+            .cursor_arg(cursor)
+            .arg("COUNT")
+            .arg(MIGRATE_BATCH)
+            .query_async(con)
+            .await?;
+        if !batch.is_empty() {
+            copied += match kind {
+                MigrateKind::Set => batch.len(),
+                MigrateKind::Hash | MigrateKind::SortedSet => batch.len() / 2,
+            };
+            if let Some(target) = target.as_deref_mut() {
+                let mut cmd = redis::cmd(write);
+                cmd.arg(key);
+                match kind {
+                    // ZSCAN answers member, score pairs; ZADD takes score, member
+                    MigrateKind::SortedSet => {
+                        for pair in batch.chunks(2) {
+                            cmd.arg(&pair[1]).arg(&pair[0]);
+                        }
+                    }
+                    MigrateKind::Set | MigrateKind::Hash => {
+                        cmd.arg(&batch);
+                    }
+                }
+                cmd.query_async::<_, ()>(target).await?;
+            }
+        }
+        if next == 0 {
+            return Ok(copied);
+        }
+        cursor = next;
+    }
+}
 
 #[derive(Clone)]
 pub struct Store {
     redis_url: String,
+    sentinel: Option<Arc<Sentinel>>,
+    scripts_only: bool,
+    deterministic_borrow: bool,
+    /// Item fields with a per-value freelist index
+    indexed_fields: Arc<Vec<String>>,
+    /// How long a borrow token can return its item, regardless of any lease
+    token_max_lifetime: Option<Duration>,
+    /// Freelist events published on their own channel instead of the pool's notify channel
+    event_channels: Arc<HashMap<String, String>>,
+    /// Prepended to every key and channel name; see [`Store::with_key_prefix`]
+    key_prefix: Arc<str>,
+    /// The item pool this handle borrows from and returns to; see [`Store::in_pool`]
+    keys: Arc<PoolKeys>,
+    /// Shared by every clone; at most `pool_size` connections are open at once
+    pool: Pool<RedisManager>,
+    opened: Arc<AtomicUsize>,
+}
+
+fn build_pool(
+    redis_url: &str,
+    sentinel: Option<Arc<Sentinel>>,
+    opened: &Arc<AtomicUsize>,
+    size: usize,
+    wait: Duration,
+) -> Pool<RedisManager> {
+    let manager = RedisManager { redis_url: redis_url.to_string(), sentinel, opened: opened.clone() };
+    Pool::builder(manager)
+        .max_size(size)
+        .wait_timeout(Some(wait))
+        .runtime(deadpool::Runtime::Tokio1)
+        .build()
+        .expect("the pool has a runtime for its timeouts")
 }
 
 impl Store {
+    /// Create a store over a pool of up to `DEFAULT_POOL_SIZE` connections, opened on demand
     pub fn new(redis_url: String) -> Self {
-        Self { redis_url }
+        let opened = Arc::new(AtomicUsize::new(0));
+        let pool = build_pool(&redis_url, None, &opened, DEFAULT_POOL_SIZE, DEFAULT_POOL_WAIT);
+        Self {
+            redis_url,
+            sentinel: None,
+            scripts_only: false,
+            deterministic_borrow: false,
+            indexed_fields: Arc::new(Vec::new()),
+            token_max_lifetime: None,
+            event_channels: Arc::new(HashMap::new()),
+            key_prefix: Arc::from(""),
+            keys: Arc::new(PoolKeys::new("", DEFAULT_POOL)),
+            pool,
+            opened,
+        }
+    }
+
+    /// A handle on the named item pool, sharing this store's connections and settings.
+    /// Borrows, returns and borrow bookkeeping then use that pool's keys; `default` is
+    /// the main freelist. Callers check the name with [`is_valid_pool_name`].
+    pub fn in_pool(&self, pool: &str) -> Self {
+        let mut store = self.clone();
+        store.keys = Arc::new(PoolKeys::new(&self.key_prefix, pool));
+        store
+    }
+
+    /// Name of the item pool this handle works on
+    pub fn pool_name(&self) -> &str {
+        &self.keys.name
+    }
+
+    /// Cap the number of pooled Redis connections; callers beyond it wait for a free one
+    pub fn with_pool_size(self, pool_size: usize) -> Self {
+        self.pool.resize(pool_size);
+        self
+    }
+
+    /// Fail a request that waited this long for a free pooled connection. Call before
+    /// handing out clones: it replaces the connection pool.
+    pub fn with_pool_wait(mut self, wait: Duration) -> Self {
+        self.pool = build_pool(&self.redis_url, self.sentinel.clone(), &self.opened, self.pool_size(), wait);
+        self
+    }
+
+    /// Find the Redis master through these sentinels instead of connecting to the Redis
+    /// URL's host. Call before handing out clones: it replaces the connection pool.
+    pub fn with_sentinel(mut self, sentinel: Option<Sentinel>) -> Self {
+        self.sentinel = sentinel.map(Arc::new);
+        self.pool = build_pool(&self.redis_url, self.sentinel.clone(), &self.opened, self.pool_size(), self.pool_wait());
+        self
+    }
+
+    /// Put every Redis key and channel under `<prefix>:`, so that several allocators can
+    /// share one Redis. An empty prefix keeps the plain names. Custom event channels (see
+    /// [`Store::with_event_channels`]) are used as configured.
+    pub fn with_key_prefix(mut self, prefix: &str) -> Self {
+        self.key_prefix = Arc::from(prefix);
+        self.keys = Arc::new(PoolKeys::new(prefix, &self.keys.name));
+        self
+    }
+
+    /// Prefixed name of one of the store's global keys
+    fn key(&self, name: &str) -> String {
+        prefixed(&self.key_prefix, name)
+    }
+
+    /// Redis key of the index set of free items whose `field` equals `value`
+    fn index_key(&self, field: &str, value: &str) -> String {
+        format!("{}{}:{}", self.key(FREELIST_INDEX_PREFIX), field, value)
+    }
+
+    /// Keep a per-value index of these item fields for filtered borrows
+    pub fn with_indexed_fields(mut self, fields: Vec<String>) -> Self {
+        self.indexed_fields = Arc::new(fields);
+        self
+    }
+
+    /// Expire borrow tokens this long after the borrow; they then no longer return the item
+    pub fn with_token_max_lifetime(mut self, lifetime: Option<Duration>) -> Self {
+        self.token_max_lifetime = lifetime;
+        self
+    }
+
+    /// Publish the given freelist events on their own channel (event name to channel),
+    /// shared by every pool, instead of the pool's notify channel
+    pub fn with_event_channels(mut self, channels: HashMap<String, String>) -> Self {
+        self.event_channels = Arc::new(channels);
+        self
+    }
+
+    /// Testing only: borrow the smallest freelist member instead of a random one
+    pub fn with_deterministic_borrow(mut self, deterministic_borrow: bool) -> Self {
+        self.deterministic_borrow = deterministic_borrow;
+        self
+    }
+
+    /// Number of Redis connections this store and its clones have opened so far
+    pub fn connections_opened(&self) -> usize {
+        self.opened.load(Ordering::Relaxed)
+    }
+
+    /// Configured maximum number of pooled connections
+    pub fn pool_size(&self) -> usize {
+        self.pool.status().max_size
+    }
+
+    /// How long a request waits for a free pooled connection
+    pub fn pool_wait(&self) -> Duration {
+        self.pool.timeouts().wait.unwrap_or(DEFAULT_POOL_WAIT)
+    }
+
+    /// Check a connection out of the pool, opening one if none is idle
+    async fn connection(&self) -> RedisResult<PooledConnection> {
+        match self.pool.get().await {
+            Ok(con) => Ok(PooledConnection(con)),
+            Err(managed::PoolError::Backend(e)) => Err(e),
+            Err(managed::PoolError::Timeout(_)) => Err(RedisError::from((
+                redis::ErrorKind::IoError,
+                "Redis pool exhausted",
+                format!("no connection free within {:?}", self.pool_wait()),
+            ))),
+            Err(e) => Err(RedisError::from((redis::ErrorKind::IoError, "Redis pool unavailable", e.to_string()))),
+        }
+    }
+
+    /// Route borrow, return and borrow recording through Lua scripts invoked with
+    /// `EVALSHA`, so a Redis ACL needs only script access plus the scripts' commands
+    pub fn with_scripts_only(mut self, scripts_only: bool) -> Self {
+        self.scripts_only = scripts_only;
+        self
     }
 
-    fn get_redis_client(&self) -> RedisResult<Client> {
-        redis::Client::open(self.redis_url.clone())
+    /// `SCRIPT LOAD` every script the store uses; returns their SHA1 hashes
+    pub async fn load_scripts(&self) -> RedisResult<Vec<String>> {
+        let mut con = self.connection().await?;
+        let mut hashes = Vec::with_capacity(SCRIPTS.len());
+        for code in SCRIPTS {
+            hashes.push(redis::cmd("SCRIPT").arg("LOAD").arg(*code).query_async(&mut con).await?);
+        }
+        Ok(hashes)
+    }
+
+    async fn get_redis_client(&self) -> RedisResult<Client> {
+        match &self.sentinel {
+            Some(sentinel) => redis::Client::open(sentinel.master_url(&self.redis_url).await?),
+            None => redis::Client::open(self.redis_url.clone()),
+        }
     }
 
-    /// Test the Redis connection to ensure it's working
-    /// This should be called on startup to fail fast if Redis is unavailable
-    pub fn test_connection(&self) -> RedisResult<()> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    /// Test the Redis connection to ensure it's working: checks a connection out of the
+    /// pool and PINGs it. This should be called on startup to fail fast if Redis is unavailable
+    pub async fn test_connection(&self) -> RedisResult<()> {
+        let mut con = self.connection().await?;
         // Simple PING command to verify connection
-        redis::cmd("PING").query::<()>(&mut con)?;
+        redis::cmd("PING").query_async::<_, ()>(&mut con).await?;
         Ok(())
     }
 
-    pub fn borrow(&self) -> RedisResult<Value> {
+    pub async fn borrow(&self) -> RedisResult<Value> {
+        parse_member(&self.borrow_raw().await?)
+    }
+
+    /// Borrow an item, returning the freelist member exactly as stored
+    pub async fn borrow_raw(&self) -> RedisResult<String> {
         // Connect to Redis
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+        let mut con = self.connection().await?;
 
         // Try to pop a value from the freelist
-        let raw: Option<String> = con.spop(FREELIST_KEY)?;
-
-        // Return the JSON value or an error if none available
-        match raw {
-            Some(s) => serde_json::from_str::<Value>(&s).map_err(|e| {
-                redis::RedisError::from((
-                    redis::ErrorKind::TypeError,
-                    "Stored value is not valid JSON",
-                    format!("{}", e),
-                ))
-            }),
-            None => Err(redis::RedisError::from((
+        let raw: Option<String> = if self.deterministic_borrow {
+            redis::Script::new(BORROW_SMALLEST_SCRIPT).key(&self.keys.freelist).invoke_async(&mut con).await?
+        } else if self.scripts_only {
+            redis::Script::new(BORROW_SCRIPT).key(&self.keys.freelist).invoke_async(&mut con).await?
+        } else {
+            con.spop(&self.keys.freelist).await?
+        };
+
+        // A failed unindex only leaves stale entries, which filtered borrows skip
+        if let Some(member) = &raw {
+            let _ = self.update_index(&mut con, "rem", member).await;
+        }
+
+        // Return the stored member or an error if none available
+        raw.ok_or_else(|| {
+            redis::RedisError::from((
                 redis::ErrorKind::ResponseError,
                 "No items available in the freelist",
-            ))),
+            ))
+        })
+    }
+
+    /// Pop a free member like [`Store::borrow_raw`] and, atomically with it, count the
+    /// members left and pick up to `sample_size` of them at random.
+    /// Returns `(member, remaining, sample)`.
+    pub async fn borrow_with_context(&self, sample_size: usize) -> RedisResult<(String, usize, Vec<String>)> {
+        let mut con = self.connection().await?;
+
+        let reply: Option<Vec<redis::Value>> = redis::Script::new(BORROW_WITH_CONTEXT_SCRIPT)
+            .key(&self.keys.freelist)
+            .arg(sample_size)
+            .arg(if self.deterministic_borrow { "1" } else { "0" })
+            .invoke_async(&mut con).await?;
+        let Some(reply) = reply else {
+            return Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "No items available in the freelist",
+            )));
+        };
+        let mut values = reply.iter();
+        let (Some(member), Some(remaining)) = (values.next(), values.next()) else {
+            return Err(redis::RedisError::from((redis::ErrorKind::TypeError, "Unexpected borrow reply")));
+        };
+        let member: String = redis::from_redis_value(member)?;
+        let remaining: usize = redis::from_redis_value(remaining)?;
+        let sample = values.map(redis::from_redis_value).collect::<RedisResult<Vec<String>>>()?;
+
+        // A failed unindex only leaves stale entries, which filtered borrows skip
+        let _ = self.update_index(&mut con, "rem", &member).await;
+        Ok((member, remaining, sample))
+    }
+
+    /// Pop a free member whose indexed `field` equals `value`
+    pub async fn borrow_indexed(&self, field: &str, value: &str) -> RedisResult<String> {
+        let mut con = self.connection().await?;
+
+        let script = redis::Script::new(BORROW_INDEXED_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(&self.keys.freelist)
+            .key(self.index_key(field, value))
+            .arg(self.key(FREELIST_INDEX_PREFIX));
+        for field in self.indexed_fields.iter() {
+            invocation.arg(field);
+        }
+        let raw: Option<String> = invocation.invoke_async(&mut con).await?;
+
+        raw.ok_or_else(|| {
+            redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "No items available in the freelist",
+            ))
+        })
+    }
+
+    /// Recreate every per-field index from the freelist; returns the number of entries indexed
+    pub async fn rebuild_indexes(&self) -> RedisResult<usize> {
+        let mut con = self.connection().await?;
+
+        let script = redis::Script::new(REBUILD_INDEXES_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        invocation
+            .key(&self.keys.freelist)
+            .key(self.key(FREELIST_INDEXES_KEY))
+            .arg(self.key(FREELIST_INDEX_PREFIX));
+        for field in self.indexed_fields.iter() {
+            invocation.arg(field);
+        }
+        invocation.invoke_async(&mut con).await
+    }
+
+    /// Whether this pool keeps per-field indexes: only the default pool does, and only
+    /// with indexed fields configured
+    fn is_indexed(&self) -> bool {
+        !self.indexed_fields.is_empty() && self.keys.name == DEFAULT_POOL
+    }
+
+    /// Add a freelist member to, or remove it from, the index sets of its indexed fields.
+    /// Only the default pool is indexed.
+    async fn update_index(&self, con: &mut PooledConnection, op: &str, member: &str) -> RedisResult<()> {
+        if !self.is_indexed() {
+            return Ok(());
         }
+        let Ok(item) = serde_json::from_str::<Value>(member) else {
+            return Ok(());
+        };
+        let keys: Vec<String> = self
+            .indexed_fields
+            .iter()
+            .filter_map(|field| item.get(field).and_then(Value::as_str).map(|value| self.index_key(field, value)))
+            .collect();
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let script = redis::Script::new(INDEX_ITEM_SCRIPT);
+        let mut invocation = script.prepare_invoke();
+        invocation.key(self.key(FREELIST_INDEXES_KEY));
+        for key in &keys {
+            invocation.key(key);
+        }
+        invocation.arg(op).arg(member).invoke_async::<_, i32>(con).await.map(|_| ())
     }
 
     /// Borrow with blocking wait - will wait up to timeout_secs for an item to become available
     /// Uses Redis Pub/Sub to be notified when items are returned to the freelist
-    pub fn borrow_blocking(&self, timeout: Duration) -> RedisResult<Value> {
-        let client = self.get_redis_client()?;
+    pub async fn borrow_blocking(&self, timeout: Duration) -> RedisResult<Value> {
+        parse_member(&self.borrow_blocking_raw(timeout).await?)
+    }
 
+    /// Blocking borrow returning the freelist member exactly as stored
+    pub async fn borrow_blocking_raw(&self, timeout: Duration) -> RedisResult<String> {
         // First, try a non-blocking borrow
-        match self.borrow() {
+        match self.borrow_raw().await {
             Ok(item) => return Ok(item),
             Err(e) => {
                 // If error is not "no items available", return it immediately
@@ -74,35 +1359,28 @@ impl Store {
             }
         }
 
-        // Set up pub/sub connection to listen for notifications
-        let mut pubsub_conn = client.get_connection()?;
-        let mut pubsub = pubsub_conn.as_pubsub();
-        pubsub.subscribe(FREELIST_NOTIFY_CHANNEL)?;
+        // Set up a dedicated pub/sub connection to listen for notifications; it is
+        // closed when the wait ends
+        let mut pubsub = self.get_redis_client().await?.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(&self.keys.notify).await?;
+        let mut messages = pubsub.on_message();
 
-        // Set timeout for pub/sub
-        let start = std::time::Instant::now();
+        let timed_out = || {
+            redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "No items available in the freelist (timeout)",
+            ))
+        };
+        let deadline = tokio::time::Instant::now() + timeout;
 
         loop {
-            // Calculate remaining timeout
-            let elapsed = start.elapsed();
-            if elapsed >= timeout {
-                // Timeout reached, return error
-                return Err(redis::RedisError::from((
-                    redis::ErrorKind::ResponseError,
-                    "No items available in the freelist (timeout)",
-                )));
-            }
-
-            let remaining = timeout - elapsed;
-
-            // Wait for notification with remaining timeout
-            pubsub.set_read_timeout(Some(remaining))?;
-
-            // Try to receive a message (this blocks until message or timeout)
-            match pubsub.get_message() {
-                Ok(_msg) => {
+            // Wait for a notification until the deadline, without holding a worker thread
+            match tokio::time::timeout_at(deadline, messages.next()).await {
+                // Borrows and deletions never free an item
+                Ok(Some(msg)) if !may_free_item(&msg.get_payload::<String>().unwrap_or_default()) => {}
+                Ok(Some(_msg)) => {
                     // Notification received, try to borrow again
-                    match self.borrow() {
+                    match self.borrow_raw().await {
                         Ok(item) => return Ok(item),
                         Err(e) => {
                             // If still no items, another client may have grabbed it
@@ -114,50 +1392,266 @@ impl Store {
                         }
                     }
                 }
-                Err(e) => {
-                    // Check if this is a timeout error
-                    if e.is_timeout() {
-                        // Timeout - return no items available error
-                        return Err(redis::RedisError::from((
-                            redis::ErrorKind::ResponseError,
-                            "No items available in the freelist (timeout)",
-                        )));
-                    }
-                    // Other error - return it
-                    return Err(e);
+                Ok(None) => {
+                    return Err(redis::RedisError::from((
+                        redis::ErrorKind::IoError,
+                        "Pub/sub connection closed while waiting for an item",
+                    )));
                 }
+                // Timeout reached, return no items available error
+                Err(_elapsed) => return Err(timed_out()),
             }
         }
     }
 
-    pub fn return_item(&self, value: &Value) -> RedisResult<()> {
+    /// Publish a freelist mutation (one of `FREELIST_EVENTS`) on this pool's notify channel,
+    /// or the event's own channel if it has one, with the item and the pool's free and
+    /// borrowed counts after it. Returns the message.
+    pub async fn publish_freelist_event(&self, event: &str, item: &Value) -> RedisResult<String> {
+        let mut con = self.connection().await?;
+        let channel = self.event_channels.get(event).unwrap_or(&self.keys.notify);
+        redis::Script::new(FREELIST_EVENT_SCRIPT)
+            .key(&self.keys.freelist)
+            .key(&self.keys.borrowed)
+            .arg(channel)
+            .arg(event)
+            .arg(item_key(item)?)
+            .arg(&self.keys.name)
+            .invoke_async(&mut con).await
+    }
+
+    /// A dedicated pub/sub connection subscribed to this pool's notify channel
+    pub async fn subscribe_notify(&self) -> RedisResult<redis::aio::PubSub> {
+        let mut pubsub = self.get_redis_client().await?.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(&self.keys.notify).await?;
+        Ok(pubsub)
+    }
+
+    /// Like [`Store::subscribe_notify`], also subscribed to the events' own channels. Those
+    /// are shared by every pool; see [`Store::is_own_freelist_event`].
+    pub async fn subscribe_freelist_events(&self) -> RedisResult<redis::aio::PubSub> {
+        let mut pubsub = self.subscribe_notify().await?;
+        let mut channels: Vec<&String> = self.event_channels.values().collect();
+        channels.sort();
+        channels.dedup();
+        for channel in channels.into_iter().filter(|channel| **channel != self.keys.notify) {
+            pubsub.subscribe(channel).await?;
+        }
+        Ok(pubsub)
+    }
+
+    /// Whether a message is one of `FREELIST_EVENTS` about this pool
+    pub fn is_own_freelist_event(&self, message: &str) -> bool {
+        is_freelist_event(message)
+            && serde_json::from_str::<Value>(message)
+                .is_ok_and(|message| message["pool"].as_str() == Some(self.keys.name.as_str()))
+    }
+
+    /// Add an item to the freelist; returns whether it was not already there
+    pub async fn return_item(&self, value: &Value) -> RedisResult<bool> {
+        self.return_raw(&item_key(value)?).await
+    }
+
+    /// Add a member to the freelist verbatim; callers ensure it is valid JSON
+    pub async fn return_raw(&self, member: &str) -> RedisResult<bool> {
         // Connect to Redis
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+        let mut con = self.connection().await?;
+        self.register_pool(&mut con).await?;
 
-        // Add the item (as serialized JSON) to the freelist
-        let payload = serde_json::to_string(value).map_err(|e| {
+        if self.scripts_only {
+            let added: bool = redis::Script::new(RETURN_ITEM_SCRIPT)
+                .key(&self.keys.freelist)
+                .arg(member)
+                .arg(&self.keys.notify)
+                .invoke_async(&mut con).await?;
+            self.update_index(&mut con, "add", member).await?;
+            return Ok(added);
+        }
+
+        let added: i32 = con.sadd(&self.keys.freelist, member).await?;
+        self.update_index(&mut con, "add", member).await?;
+
+        // Notify any waiting clients via Pub/Sub
+        let _: () = redis::cmd("PUBLISH")
+            .arg(&self.keys.notify)
+            .arg("item_returned")
+            .query_async(&mut con).await?;
+
+        Ok(added > 0)
+    }
+
+    /// Record that an item has been borrowed with a specific token.
+    /// Fails with "Item already borrowed" instead of overwriting if the item is already
+    /// recorded under another token, e.g. a stale freelist entry popped while it was out.
+    /// With a `lease` or a `token_max_lifetime` the borrow is reclaimed by
+    /// [`Store::take_expired_leases`] callers once the first runs out; without either it
+    /// lasts until returned.
+    pub async fn record_borrowed(
+        &self,
+        item: &Value,
+        borrow_token: &str,
+        owner: Option<Owner<'_>>,
+        lease: Option<Duration>,
+    ) -> RedisResult<BorrowRecord> {
+        let mut con = self.connection().await?;
+
+        let item_key = serde_json::to_string(item).map_err(|e| {
             redis::RedisError::from((
                 redis::ErrorKind::TypeError,
                 "Failed to serialize JSON",
                 format!("{}", e),
             ))
         })?;
-        let _added: i32 = con.sadd(FREELIST_KEY, payload)?;
+        let (record, record_json) = new_borrow_record(owner, lease, self.token_max_lifetime)?;
 
-        // Notify any waiting clients via Pub/Sub
-        let _: () = redis::cmd("PUBLISH")
-            .arg(FREELIST_NOTIFY_CHANNEL)
-            .arg("item_returned")
-            .query(&mut con)?;
+        if self.scripts_only {
+            let recorded: bool = redis::Script::new(RECORD_BORROW_SCRIPT)
+                .key(&self.keys.borrowed)
+                .key(&self.keys.records)
+                .key(&self.keys.tokens)
+                .key(&self.keys.leases)
+                .arg(&item_key)
+                .arg(borrow_token)
+                .arg(record_json)
+                .arg(record.reclaim_at().map(|at| at.to_string()).unwrap_or_default())
+                .invoke_async(&mut con).await?;
+            return if recorded { Ok(record) } else { Err(already_borrowed()) };
+        }
 
-        Ok(())
+        // Claim the item under the borrow_token; never overwrite another holder's claim
+        let claimed: bool = con.hset_nx(&self.keys.borrowed, &item_key, borrow_token).await?;
+        if !claimed {
+            return Err(already_borrowed());
+        }
+        // Store the borrow metadata under the same key and index the token back to the item
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .hset(&self.keys.records, &item_key, record_json)
+            .hset(&self.keys.tokens, borrow_token, &item_key);
+        if let Some(deadline) = record.reclaim_at() {
+            pipe.zadd(&self.keys.leases, borrow_token, deadline);
+        }
+        let _: () = pipe.query_async(&mut con).await?;
+        Ok(record)
     }
 
-    /// Record that an item has been borrowed with a specific token
-    pub fn record_borrowed(&self, item: &Value, borrow_token: &str) -> RedisResult<()> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    /// Pop a free member and record it as borrowed under `borrow_token` in one atomic step,
+    /// so the item cannot be lost between the two. Returns the member as stored, the item
+    /// and its record. Fails like [`Store::borrow_raw`] if the freelist is empty, and with
+    /// "Item already borrowed", leaving the member free, like [`Store::record_borrowed`].
+    pub async fn borrow_and_record(
+        &self,
+        borrow_token: &str,
+        owner: Option<Owner<'_>>,
+        lease: Option<Duration>,
+    ) -> RedisResult<(String, Value, BorrowRecord)> {
+        let mut con = self.connection().await?;
+
+        let (record, record_json) = new_borrow_record(owner, lease, self.token_max_lifetime)?;
+        let reply: Option<(String, bool)> = redis::Script::new(BORROW_AND_RECORD_SCRIPT)
+            .key(&self.keys.freelist)
+            .key(&self.keys.borrowed)
+            .key(&self.keys.records)
+            .key(&self.keys.tokens)
+            .key(&self.keys.leases)
+            .arg(borrow_token)
+            .arg(&record_json)
+            .arg(record.reclaim_at().map(|at| at.to_string()).unwrap_or_default())
+            .arg(if self.deterministic_borrow { "1" } else { "0" })
+            .invoke_async(&mut con).await?;
+        let Some((member, recorded)) = reply else {
+            return Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "No items available in the freelist",
+            )));
+        };
+        if !recorded {
+            return Err(already_borrowed());
+        }
+        // A failed unindex only leaves stale entries, which filtered borrows skip
+        let _ = self.update_index(&mut con, "rem", &member).await;
+
+        // Borrows are keyed by the item's canonical JSON; members stored in another form
+        // (raw items, or invalid JSON) are moved or put back. The connection goes back to
+        // the pool first: those calls take their own, and with every pooled connection held
+        // this way they would wait forever.
+        let item = match parse_member(&member) {
+            Ok(item) => item,
+            Err(e) => {
+                clear_borrow(&mut con, &self.keys, &member).await?;
+                drop(con);
+                self.return_raw(&member).await?;
+                return Err(e);
+            }
+        };
+        if item_key(&item)? != member {
+            clear_borrow(&mut con, &self.keys, &member).await?;
+            drop(con);
+            let record = self.record_borrowed(&item, borrow_token, owner, lease).await?;
+            return Ok((member, item, record));
+        }
+        Ok((member, item, record))
+    }
+
+    /// Borrow `item` itself, if it is free, recording it under `borrow_token` in the same
+    /// atomic step, so two clients can never both claim it. `raw_item`, the item's text as
+    /// the client sent it, is tried first in case the item is stored verbatim. Returns the
+    /// member as stored and the record, `None` if the item is not in the freelist, and fails
+    /// with "Item already borrowed" if it is free but recorded under another token.
+    pub async fn borrow_specific(
+        &self,
+        item: &Value,
+        raw_item: Option<&str>,
+        borrow_token: &str,
+        owner: Option<Owner<'_>>,
+        lease: Option<Duration>,
+    ) -> RedisResult<Option<(String, BorrowRecord)>> {
+        let mut con = self.connection().await?;
+        let key = item_key(item)?;
+        let (record, record_json) = new_borrow_record(owner, lease, self.token_max_lifetime)?;
+        let deadline = record.reclaim_at().map(|at| at.to_string()).unwrap_or_default();
+        let mut members = raw_item.filter(|raw| *raw != key).map(str::to_string).into_iter().collect::<Vec<_>>();
+        members.push(key.clone());
+        for member in members {
+            let taken: i32 = redis::Script::new(BORROW_SPECIFIC_SCRIPT)
+                .key(&self.keys.freelist)
+                .key(&self.keys.borrowed)
+                .key(&self.keys.records)
+                .key(&self.keys.tokens)
+                .key(&self.keys.leases)
+                .arg(&member)
+                .arg(&key)
+                .arg(borrow_token)
+                .arg(&record_json)
+                .arg(&deadline)
+                .invoke_async(&mut con).await?;
+            match taken {
+                1 => {
+                    // A failed unindex only leaves stale entries, which filtered borrows skip
+                    let _ = self.update_index(&mut con, "rem", &member).await;
+                    return Ok(Some((member, record)));
+                }
+                -1 => return Err(already_borrowed()),
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    /// Take the borrow tokens whose lease ran out by `now` (unix seconds). Tokens of items
+    /// returned in the meantime are included; force returning them is a no-op.
+    pub async fn take_expired_leases(&self, now: u64) -> RedisResult<Vec<String>> {
+        let mut con = self.connection().await?;
+
+        redis::Script::new(TAKE_EXPIRED_LEASES_SCRIPT)
+            .key(&self.keys.leases)
+            .arg(now)
+            .invoke_async(&mut con).await
+    }
+
+    /// Get the metadata recorded when the item was borrowed, if any
+    pub async fn get_borrow_record(&self, item: &Value) -> RedisResult<Option<BorrowRecord>> {
+        let mut con = self.connection().await?;
 
         let item_key = serde_json::to_string(item).map_err(|e| {
             redis::RedisError::from((
@@ -167,16 +1661,14 @@ impl Store {
             ))
         })?;
 
-        // Store the borrow_token in a hash map with the item as the key
-        let _: () = con.hset(BORROWED_ITEMS_KEY, item_key, borrow_token)?;
-        Ok(())
+        let raw: Option<String> = con.hget(&self.keys.records, &item_key).await?;
+        Ok(raw.and_then(|r| serde_json::from_str(&r).ok()))
     }
 
     /// Verify that the borrow_token matches the one issued when the item was borrowed
     /// Returns Ok(()) if valid, Err if token doesn't match or item not found
-    pub fn verify_borrow_token(&self, item: &Value, borrow_token: &str) -> RedisResult<()> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    pub async fn verify_borrow_token(&self, item: &Value, borrow_token: &str) -> RedisResult<()> {
+        let mut con = self.connection().await?;
 
         let item_key = serde_json::to_string(item).map_err(|e| {
             redis::RedisError::from((
@@ -187,7 +1679,7 @@ impl Store {
         })?;
 
         // Get the stored borrow_token for this item
-        let stored_token: Option<String> = con.hget(BORROWED_ITEMS_KEY, &item_key)?;
+        let stored_token: Option<String> = con.hget(&self.keys.borrowed, &item_key).await?;
 
         match stored_token {
             Some(stored) if stored == borrow_token => Ok(()),
@@ -202,10 +1694,34 @@ impl Store {
         }
     }
 
+    /// Verify that `owner` is the owner the item was borrowed by and that the borrow token
+    /// has not outlived `token_max_lifetime`. Borrows recorded without an explicit owner
+    /// (none, or only the client IP) accept any; otherwise a different or missing owner
+    /// is rejected.
+    pub async fn verify_owner(&self, item: &Value, owner: Option<&str>) -> RedisResult<()> {
+        let Some(record) = self.get_borrow_record(item).await? else {
+            return Ok(());
+        };
+        if let Some(expected) = record.owner.as_deref().filter(|_| record.owner_explicit) {
+            if Some(expected) != owner {
+                return Err(redis::RedisError::from((
+                    redis::ErrorKind::ResponseError,
+                    "Owner mismatch: This item is borrowed by another owner",
+                )));
+            }
+        }
+        if record.token_expires_at.is_some_and(|at| at <= now_secs()) {
+            return Err(redis::RedisError::from((
+                redis::ErrorKind::ResponseError,
+                "Borrow token expired: Borrow the item again",
+            )));
+        }
+        Ok(())
+    }
+
     /// Remove the borrowed item record after successful return
-    pub fn remove_borrowed_record(&self, item: &Value) -> RedisResult<()> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    pub async fn remove_borrowed_record(&self, item: &Value) -> RedisResult<()> {
+        let mut con = self.connection().await?;
 
         let item_key = serde_json::to_string(item).map_err(|e| {
             redis::RedisError::from((
@@ -215,17 +1731,28 @@ impl Store {
             ))
         })?;
 
-        // Remove the item from the borrowed_items hash
-        let _: () = con.hdel(BORROWED_ITEMS_KEY, item_key)?;
+        // Remove the item from the borrowed_items hash along with its metadata
+        let _: i32 = clear_borrow(&mut con, &self.keys, &item_key).await?;
         Ok(())
     }
 
+    /// Number of items currently in the freelist
+    pub async fn free_count(&self) -> RedisResult<usize> {
+        let mut con = self.connection().await?;
+        con.scard(&self.keys.freelist).await
+    }
+
+    /// Number of items currently borrowed
+    pub async fn borrowed_count(&self) -> RedisResult<usize> {
+        let mut con = self.connection().await?;
+        con.hlen(&self.keys.borrowed).await
+    }
+
     /// Get all items in the freelist (for admin UI)
-    pub fn list_all_items(&self) -> RedisResult<Vec<Value>> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    pub async fn list_all_items(&self) -> RedisResult<Vec<Value>> {
+        let mut con = self.connection().await?;
 
-        let raw_items: Vec<String> = con.smembers(FREELIST_KEY)?;
+        let raw_items: Vec<String> = con.smembers(&self.keys.freelist).await?;
 
         let mut items = Vec::new();
         for raw in raw_items {
@@ -237,17 +1764,102 @@ impl Store {
         Ok(items)
     }
 
-    /// Get all borrowed items with their tokens (for admin UI)
-    pub fn list_borrowed_items(&self) -> RedisResult<Vec<(Value, String)>> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    /// One page of the freelist: skip `offset` items, then keep at most `limit` (all when
+    /// unset). The set is walked with `SSCAN`, which stops once the page is full, so a large
+    /// freelist is never loaded whole. Pages follow `SSCAN` order, which is stable while
+    /// the set is unchanged. Members that are not valid JSON are skipped and not counted.
+    pub async fn scan_items(&self, offset: usize, limit: Option<usize>) -> RedisResult<Vec<Value>> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut items = Vec::new();
+        if limit == 0 {
+            return Ok(items);
+        }
+        let mut con = self.connection().await?;
+        let mut iter = con.sscan::<_, String>(&self.keys.freelist).await?;
+        let mut skipped = 0;
+        while let Some(raw) = iter.next_item().await {
+            let Ok(item) = serde_json::from_str::<Value>(&raw) else {
+                continue; // Skip invalid JSON
+            };
+            if skipped < offset {
+                skipped += 1;
+                continue;
+            }
+            items.push(item);
+            if items.len() == limit {
+                break;
+            }
+        }
+        Ok(items)
+    }
 
-        let raw_map: std::collections::HashMap<String, String> = con.hgetall(BORROWED_ITEMS_KEY)?;
+    /// Up to `count` distinct free items picked at random (`SRANDMEMBER`), left in the
+    /// freelist. Members that are not valid JSON are skipped.
+    pub async fn peek_items(&self, count: usize) -> RedisResult<Vec<Value>> {
+        let mut con = self.connection().await?;
+        let raw_items: Vec<String> = con.srandmember_multiple(&self.keys.freelist, count).await?;
+        Ok(raw_items.iter().filter_map(|raw| serde_json::from_str(raw).ok()).collect())
+    }
+
+    /// Keep an item in the freelist only until `until` (unix seconds); `raw_item` is its
+    /// member text when items are stored raw
+    pub async fn set_available_until(&self, item: &Value, raw_item: Option<&str>, until: u64) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        let member = match raw_item {
+            Some(raw) => raw.to_string(),
+            None => item_key(item)?,
+        };
+        con.zadd(self.key(AVAILABLE_UNTIL_KEY), member, until).await
+    }
+
+    /// Make an item available without a deadline again, e.g. when it is returned or
+    /// submitted anew; `raw_item` is its member text when items are stored raw
+    pub async fn clear_available_until(&self, item: &Value, raw_item: Option<&str>) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        let mut members = vec![item_key(item)?];
+        members.extend(raw_item.map(str::to_string));
+        con.zrem(self.key(AVAILABLE_UNTIL_KEY), members).await
+    }
+
+    /// Deadlines of time-boxed items, keyed by the item's canonical JSON
+    pub async fn available_until(&self) -> RedisResult<HashMap<String, u64>> {
+        let mut con = self.connection().await?;
+        let entries: Vec<(String, u64)> = con.zrange_withscores(self.key(AVAILABLE_UNTIL_KEY), 0, -1).await?;
+        Ok(entries
+            .into_iter()
+            .filter_map(|(member, until)| {
+                let item = serde_json::from_str::<Value>(&member).ok()?;
+                Some((item.to_string(), until))
+            })
+            .collect())
+    }
+
+    /// Remove items whose availability ended by `now` (unix seconds) from the freelist;
+    /// returns how many were removed
+    pub async fn expire_available_items(&self, now: u64) -> RedisResult<usize> {
+        let mut con = self.connection().await?;
+
+        redis::Script::new(EXPIRE_AVAILABLE_SCRIPT)
+            .key(self.key(AVAILABLE_UNTIL_KEY))
+            .key(&self.keys.freelist)
+            .arg(now)
+            .invoke_async(&mut con).await
+    }
+
+    /// Get all borrowed items with their tokens and borrow metadata (for admin UI)
+    pub async fn list_borrowed_items(&self) -> RedisResult<Vec<(Value, String, Option<BorrowRecord>)>> {
+        let mut con = self.connection().await?;
+
+        let raw_map: std::collections::HashMap<String, String> = con.hgetall(&self.keys.borrowed).await?;
+        let mut records: std::collections::HashMap<String, String> = con.hgetall(&self.keys.records).await?;
 
         let mut borrowed = Vec::new();
         for (item_key, token) in raw_map {
+            let record = records
+                .remove(&item_key)
+                .and_then(|r| serde_json::from_str::<BorrowRecord>(&r).ok());
             match serde_json::from_str::<Value>(&item_key) {
-                Ok(item) => borrowed.push((item, token)),
+                Ok(item) => borrowed.push((item, token, record)),
                 Err(_) => continue, // Skip invalid JSON
             }
         }
@@ -255,9 +1867,8 @@ impl Store {
     }
 
     /// Remove an item from the freelist (for admin deletion)
-    pub fn delete_item(&self, value: &Value) -> RedisResult<bool> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    pub async fn delete_item(&self, value: &Value) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
 
         let payload = serde_json::to_string(value).map_err(|e| {
             redis::RedisError::from((
@@ -267,22 +1878,393 @@ impl Store {
             ))
         })?;
 
-        let removed: i32 = con.srem(FREELIST_KEY, payload)?;
+        let removed: i32 = con.srem(&self.keys.freelist, payload).await?;
         Ok(removed > 0)
     }
 
+    /// Make the freelist hold exactly `items` in one atomic step, without touching
+    /// borrowed items, then index it again from scratch. Returns the (added, removed,
+    /// unchanged) counts.
+    pub async fn set_freelist(&self, items: &[Value]) -> RedisResult<(usize, usize, usize)> {
+        let mut con = self.connection().await?;
+
+        let set = redis::Script::new(SET_FREELIST_SCRIPT);
+        let mut script = set.prepare_invoke();
+        script.key(&self.keys.freelist).key(&self.keys.borrowed).arg(&self.keys.notify);
+        for item in items {
+            script.arg(item_key(item)?);
+        }
+        let counts = script.invoke_async(&mut con).await?;
+        drop(con);
+        if self.is_indexed() {
+            self.rebuild_indexes().await?;
+        }
+        Ok(counts)
+    }
+
+    /// Add items to the freelist, skipping those already free or currently borrowed;
+    /// returns how many were added. Items go in batches of `SEED_BATCH`, each checked and
+    /// added in one step, and are indexed like returned items.
+    pub async fn seed_items(&self, items: &[Value]) -> RedisResult<usize> {
+        let mut con = self.connection().await?;
+        self.register_pool(&mut con).await?;
+
+        let seed = redis::Script::new(SEED_ITEMS_SCRIPT);
+        let mut count = 0;
+        for batch in items.chunks(SEED_BATCH) {
+            let mut script = seed.prepare_invoke();
+            script.key(&self.keys.freelist).key(&self.keys.borrowed).arg(&self.keys.notify);
+            for item in batch {
+                script.arg(item_key(item)?);
+            }
+            let added: Vec<String> = script.invoke_async(&mut con).await?;
+            for member in &added {
+                self.update_index(&mut con, "add", member).await?;
+            }
+            count += added.len();
+        }
+        Ok(count)
+    }
+
+    /// Atomically move a free item from pool `from` to pool `to`. Returns false if the
+    /// item is not free in `from`. Borrowers waiting on `to` are woken.
+    pub async fn transfer_item(&self, item: &Value, from: &str, to: &str) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
+
+        let member = item_key(item)?;
+        let (from_keys, to_keys) = (PoolKeys::new(&self.key_prefix, from), PoolKeys::new(&self.key_prefix, to));
+        let moved: i32 = redis::Script::new(TRANSFER_ITEM_SCRIPT)
+            .key(&from_keys.freelist)
+            .key(&to_keys.freelist)
+            .key(self.key(POOLS_KEY))
+            .arg(&member)
+            .arg(to)
+            .arg(&to_keys.notify)
+            .invoke_async(&mut con).await?;
+        if moved != 1 {
+            return Ok(false);
+        }
+        // A failed unindex only leaves stale entries, which filtered borrows skip
+        let _ = self.in_pool(from).update_index(&mut con, "rem", &member).await;
+        self.in_pool(to).update_index(&mut con, "add", &member).await?;
+        Ok(true)
+    }
+
+    /// The default pool and every pool items were submitted, returned or moved into,
+    /// the default pool first and the rest by name
+    pub async fn pool_names(&self) -> RedisResult<Vec<String>> {
+        let mut con = self.connection().await?;
+
+        let mut pools: Vec<String> = con.smembers(self.key(POOLS_KEY)).await?;
+        pools.retain(|pool| pool != DEFAULT_POOL);
+        pools.sort();
+        pools.insert(0, DEFAULT_POOL.to_string());
+        Ok(pools)
+    }
+
+    /// Remember a named pool so it shows up in [`Store::pool_names`]
+    async fn register_pool(&self, con: &mut PooledConnection) -> RedisResult<()> {
+        if self.keys.name != DEFAULT_POOL {
+            let _: () = con.sadd(self.key(POOLS_KEY), &self.keys.name).await?;
+        }
+        Ok(())
+    }
+
+    /// Free item count of every pool, ordered like [`Store::pool_names`]
+    pub async fn pool_sizes(&self) -> RedisResult<Vec<(String, usize)>> {
+        let pools = self.pool_names().await?;
+        let mut con = self.connection().await?;
+        let mut sizes = Vec::with_capacity(pools.len());
+        for pool in pools {
+            let size: usize = con.scard(PoolKeys::new(&self.key_prefix, &pool).freelist).await?;
+            sizes.push((pool, size));
+        }
+        Ok(sizes)
+    }
+
+    /// Append an admin audit entry (JSON), keeping the newest `ADMIN_AUDIT_MAX_ENTRIES`
+    pub async fn append_audit(&self, entry: &str) -> RedisResult<()> {
+        self.push_capped(ADMIN_AUDIT_KEY, entry, ADMIN_AUDIT_MAX_ENTRIES).await
+    }
+
+    /// A page of admin audit entries, newest first, and the number kept
+    pub async fn audit_entries(&self, offset: usize, limit: usize) -> RedisResult<(Vec<String>, usize)> {
+        self.list_page(ADMIN_AUDIT_KEY, offset, limit).await
+    }
+
+    /// Append a borrow, return or submit audit entry (JSON), keeping the newest
+    /// `ITEM_AUDIT_MAX_ENTRIES`
+    pub async fn append_item_audit(&self, entry: &str) -> RedisResult<()> {
+        self.push_capped(ITEM_AUDIT_KEY, entry, ITEM_AUDIT_MAX_ENTRIES).await
+    }
+
+    /// A page of borrow, return and submit audit entries, newest first, and the number kept
+    pub async fn item_audit_entries(&self, offset: usize, limit: usize) -> RedisResult<(Vec<String>, usize)> {
+        self.list_page(ITEM_AUDIT_KEY, offset, limit).await
+    }
+
+    /// Push `entry` onto the head of the list `key` and trim it to its newest `max` entries
+    async fn push_capped(&self, key: &str, entry: &str, max: isize) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        redis::pipe()
+            .atomic()
+            .lpush(self.key(key), entry)
+            .ignore()
+            .ltrim(self.key(key), 0, max - 1)
+            .ignore()
+            .query_async(&mut con)
+            .await
+    }
+
+    /// A page of the list `key`, head first, and its length
+    async fn list_page(&self, key: &str, offset: usize, limit: usize) -> RedisResult<(Vec<String>, usize)> {
+        let mut con = self.connection().await?;
+        let total: usize = con.llen(self.key(key)).await?;
+        if limit == 0 || offset >= total {
+            return Ok((Vec::new(), total));
+        }
+        let last = offset.saturating_add(limit - 1).min(total - 1);
+        let entries: Vec<String> = con.lrange(self.key(key), offset as isize, last as isize).await?;
+        Ok((entries, total))
+    }
+
+    /// Write fields of a persisted operation and set when its hash expires (`None` keeps
+    /// it). With `replace`, fields not written are dropped first.
+    pub async fn save_operation(
+        &self,
+        id: &str,
+        fields: &[(String, String)],
+        ttl: Option<Duration>,
+        replace: bool,
+    ) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        let key = format!("{}{}", self.key(OPERATION_KEY_PREFIX), id);
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        if replace {
+            pipe.del(&key).ignore();
+        }
+        pipe.hset_multiple(&key, fields).ignore();
+        match ttl {
+            Some(ttl) => pipe.pexpire(&key, (ttl.as_millis() as usize).max(1)).ignore(),
+            None => pipe.persist(&key).ignore(),
+        };
+        pipe.query_async(&mut con).await
+    }
+
+    /// Fields of a persisted operation; empty if it is not persisted
+    pub async fn load_operation(&self, id: &str) -> RedisResult<HashMap<String, String>> {
+        let mut con = self.connection().await?;
+        con.hgetall(format!("{}{}", self.key(OPERATION_KEY_PREFIX), id)).await
+    }
+
+    /// Fields of every persisted operation
+    pub async fn load_operations(&self) -> RedisResult<Vec<HashMap<String, String>>> {
+        let mut con = self.connection().await?;
+        let keys: Vec<String> = {
+            let mut iter = con.scan_match::<_, String>(format!("{}*", self.key(OPERATION_KEY_PREFIX))).await?;
+            let mut keys = Vec::new();
+            while let Some(key) = iter.next_item().await {
+                keys.push(key);
+            }
+            keys
+        };
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let mut pipe = redis::pipe();
+        for key in &keys {
+            pipe.hgetall(key);
+        }
+        pipe.query_async(&mut con).await
+    }
+
+    /// Drop a persisted operation; returns whether it was persisted
+    pub async fn delete_operation(&self, id: &str) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
+        let removed: i32 = con.del(format!("{}{}", self.key(OPERATION_KEY_PREFIX), id)).await?;
+        Ok(removed > 0)
+    }
+
+    /// Claim idempotency `key` for `operation_id` for `ttl` (at least a second). Returns
+    /// the operation an earlier, unexpired claim is for instead; shared by every replica.
+    pub async fn claim_idempotency_key(&self, key: &str, operation_id: &str, ttl: Duration) -> RedisResult<Option<String>> {
+        let mut con = self.connection().await?;
+        redis::Script::new(CLAIM_IDEMPOTENCY_KEY_SCRIPT)
+            .key(format!("{}{}", self.key(IDEMPOTENCY_KEY_PREFIX), key))
+            .arg(operation_id)
+            .arg(ttl.as_secs().max(1))
+            .invoke_async(&mut con).await
+    }
+
+    /// Drop the claim on idempotency `key` if it is still for `operation_id`
+    pub async fn release_idempotency_key(&self, key: &str, operation_id: &str) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        let _: i32 = redis::Script::new(RELEASE_LOCK_SCRIPT)
+            .key(format!("{}{}", self.key(IDEMPOTENCY_KEY_PREFIX), key))
+            .arg(operation_id)
+            .invoke_async(&mut con).await?;
+        Ok(())
+    }
+
+    pub fn redis_url(&self) -> &str {
+        &self.redis_url
+    }
+
+    /// Queue a subscriber retry entry for `due` (unix seconds); false if `capacity`
+    /// entries are already queued
+    pub async fn enqueue_retry(&self, entry: &str, due: u64, capacity: usize) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
+        let queued: i32 = redis::Script::new(ENQUEUE_RETRY_SCRIPT)
+            .key(self.key(RETRY_QUEUE_KEY))
+            .arg(entry)
+            .arg(due)
+            .arg(capacity)
+            .invoke_async(&mut con)
+            .await?;
+        Ok(queued == 1)
+    }
+
+    /// Claim the retry entries due by `now` for `claim`; unless rescheduled or finished
+    /// within that time, they are due again, e.g. after a crash
+    pub async fn claim_retries(&self, now: u64, claim: Duration) -> RedisResult<Vec<String>> {
+        let mut con = self.connection().await?;
+        redis::Script::new(CLAIM_RETRIES_SCRIPT)
+            .key(self.key(RETRY_QUEUE_KEY))
+            .arg(now)
+            .arg(now + claim.as_secs())
+            .arg(RETRY_CLAIM_BATCH)
+            .invoke_async(&mut con)
+            .await
+    }
+
+    /// Replace a claimed retry entry with its next attempt, due at `due`
+    pub async fn reschedule_retry(&self, claimed: &str, entry: &str, due: u64) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        redis::pipe()
+            .atomic()
+            .zrem(self.key(RETRY_QUEUE_KEY), claimed)
+            .ignore()
+            .zadd(self.key(RETRY_QUEUE_KEY), entry, due)
+            .ignore()
+            .query_async(&mut con)
+            .await
+    }
+
+    /// Drop a claimed retry entry once it succeeded or gave up
+    pub async fn finish_retry(&self, claimed: &str) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        con.zrem(self.key(RETRY_QUEUE_KEY), claimed).await
+    }
+
+    /// Number of queued subscriber retries, claimed ones included
+    pub async fn retry_queue_depth(&self) -> RedisResult<usize> {
+        let mut con = self.connection().await?;
+        con.zcard(self.key(RETRY_QUEUE_KEY)).await
+    }
+
+    /// Keep a two-phase return (JSON) awaiting confirmation under its operation id
+    pub async fn save_pending_release(&self, op_id: &str, release: &str) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        con.hset(self.key(PENDING_RELEASES_KEY), op_id, release).await
+    }
+
+    /// The two-phase return awaiting confirmation under an operation id, if any
+    pub async fn pending_release(&self, op_id: &str) -> RedisResult<Option<String>> {
+        let mut con = self.connection().await?;
+        con.hget(self.key(PENDING_RELEASES_KEY), op_id).await
+    }
+
+    /// Every two-phase return awaiting confirmation, by operation id
+    pub async fn pending_releases(&self) -> RedisResult<HashMap<String, String>> {
+        let mut con = self.connection().await?;
+        con.hgetall(self.key(PENDING_RELEASES_KEY)).await
+    }
+
+    /// Claim a two-phase return awaiting confirmation; only one caller, on any instance,
+    /// gets it
+    pub async fn take_pending_release(&self, op_id: &str) -> RedisResult<Option<String>> {
+        let mut con = self.connection().await?;
+        redis::Script::new(TAKE_FIELD_SCRIPT)
+            .key(self.key(PENDING_RELEASES_KEY))
+            .arg(op_id)
+            .invoke_async(&mut con)
+            .await
+    }
+
+    /// Copy the durable state of every pool to the Redis at `target_url`: freelists,
+    /// borrowed items with their records, tokens and leases, plus known items, item
+    /// metadata, time-boxed deadlines and the pool list. Members are added to what the
+    /// target already holds. Returns the number of members per key that were copied, or
+    /// with `dry_run` would be, leaving the target untouched. Reservations, freelist
+    /// indexes, operations and queued subscriber retries are not copied.
+    pub async fn migrate_to(&self, target_url: &str, dry_run: bool) -> RedisResult<BTreeMap<String, usize>> {
+        let mut keys = vec![
+            (self.key(POOLS_KEY), MigrateKind::Set),
+            (self.key(ITEM_METADATA_KEY), MigrateKind::Hash),
+            (self.key(AVAILABLE_UNTIL_KEY), MigrateKind::SortedSet),
+        ];
+        for pool in self.pool_names().await? {
+            let pool = PoolKeys::new(&self.key_prefix, &pool);
+            keys.push((pool.freelist, MigrateKind::Set));
+            keys.push((pool.borrowed, MigrateKind::Hash));
+            keys.push((pool.records, MigrateKind::Hash));
+            keys.push((pool.tokens, MigrateKind::Hash));
+            keys.push((pool.leases, MigrateKind::SortedSet));
+            keys.push((pool.known, MigrateKind::Set));
+        }
+
+        let mut con = self.connection().await?;
+        let mut target = match dry_run {
+            true => None,
+            false => Some(Client::open(target_url)?.get_async_connection().await?),
+        };
+        let mut copied = BTreeMap::new();
+        for (key, kind) in keys {
+            let count = copy_key(&mut con, target.as_mut(), &key, kind).await?;
+            if count > 0 {
+                copied.insert(key, count);
+            }
+        }
+        Ok(copied)
+    }
+
+    /// Try to take the admin bulk-operation lock under `token`; it expires after `ttl`
+    /// so a crashed holder cannot keep it. Returns false if someone else holds it.
+    pub async fn try_admin_lock(&self, token: &str, ttl: Duration) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
+        let set: Option<String> = redis::cmd("SET")
+            .arg(self.key(ADMIN_LOCK_KEY))
+            .arg(token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl.as_millis() as u64)
+            .query_async(&mut con).await?;
+        Ok(set.is_some())
+    }
+
+    /// Release the admin lock if it is still held under `token`
+    pub async fn release_admin_lock(&self, token: &str) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        let _: i32 = redis::Script::new(RELEASE_LOCK_SCRIPT)
+            .key(self.key(ADMIN_LOCK_KEY))
+            .arg(token)
+            .invoke_async(&mut con).await?;
+        Ok(())
+    }
+
     /// Force return an item without token validation (for admin use)
-    pub fn force_return(&self, item: &Value) -> RedisResult<()> {
+    pub async fn force_return(&self, item: &Value) -> RedisResult<()> {
         // Remove from borrowed items if present
-        let _ = self.remove_borrowed_record(item);
-        // Add back to freelist
-        self.return_item(item)
+        let _ = self.remove_borrowed_record(item).await;
+        // Add back to freelist, no longer time-boxed
+        self.clear_available_until(item, None).await?;
+        self.return_item(item).await.map(|_| ())
     }
 
     /// Delete a borrowed item without returning it to the freelist (for admin deletion)
-    pub fn delete_borrowed_item(&self, item: &Value) -> RedisResult<bool> {
-        let client = self.get_redis_client()?;
-        let mut con = client.get_connection()?;
+    pub async fn delete_borrowed_item(&self, item: &Value) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
 
         let item_key = serde_json::to_string(item).map_err(|e| {
             redis::RedisError::from((
@@ -292,8 +2274,207 @@ impl Store {
             ))
         })?;
 
-        // Remove the item from the borrowed_items hash
-        let removed: i32 = con.hdel(BORROWED_ITEMS_KEY, item_key)?;
+        // Remove the item from the borrowed_items hash along with its metadata
+        let removed = clear_borrow(&mut con, &self.keys, &item_key).await?;
         Ok(removed > 0)
     }
-}
\ No newline at end of file
+
+    /// Force return the item held under a borrow token, without knowing the item.
+    /// Returns the item, or None if the token is not an active borrow.
+    pub async fn force_return_by_token(&self, borrow_token: &str) -> RedisResult<Option<Value>> {
+        let mut con = self.connection().await?;
+
+        let item_key: Option<String> = redis::Script::new(FORCE_RETURN_BY_TOKEN_SCRIPT)
+            .key(&self.keys.tokens)
+            .key(&self.keys.borrowed)
+            .key(&self.keys.records)
+            .key(&self.keys.freelist)
+            .arg(borrow_token)
+            .arg(&self.keys.notify)
+            .invoke_async(&mut con).await?;
+        let Some(member) = item_key else {
+            return Ok(None);
+        };
+        self.update_index(&mut con, "add", &member).await?;
+        // Returned items are no longer time-boxed
+        let _: () = con.zrem(self.key(AVAILABLE_UNTIL_KEY), &member).await?;
+
+        Ok(serde_json::from_str(&member).ok())
+    }
+
+    /// The item currently borrowed under a token, if the token is active
+    pub async fn item_for_token(&self, borrow_token: &str) -> RedisResult<Option<Value>> {
+        let mut con = self.connection().await?;
+
+        let key: Option<String> = con.hget(&self.keys.tokens, borrow_token).await?;
+        key.map(|k| parse_member(&k)).transpose()
+    }
+
+    /// Whether the item is currently in the freelist or borrowed, checked in one round trip
+    pub async fn is_free_or_borrowed(&self, item: &Value) -> RedisResult<bool> {
+        let (free, borrowed) = self.item_location(item).await?;
+        Ok(free || borrowed)
+    }
+
+    /// Whether the item is in this pool's freelist, and whether it is borrowed from it
+    pub async fn item_location(&self, item: &Value) -> RedisResult<(bool, bool)> {
+        let mut con = self.connection().await?;
+
+        let key = item_key(item)?;
+        redis::pipe()
+            .sismember(&self.keys.freelist, &key)
+            .hexists(&self.keys.borrowed, &key)
+            .query_async(&mut con).await
+    }
+
+    /// Replace the operator metadata of an item
+    pub async fn set_item_metadata(&self, item: &Value, metadata: &Value) -> RedisResult<()> {
+        let mut con = self.connection().await?;
+        con.hset(self.key(ITEM_METADATA_KEY), item_key(item)?, metadata.to_string()).await
+    }
+
+    /// The operator metadata of an item, if any was set
+    pub async fn item_metadata(&self, item: &Value) -> RedisResult<Option<Value>> {
+        let mut con = self.connection().await?;
+        let metadata: Option<String> = con.hget(self.key(ITEM_METADATA_KEY), item_key(item)?).await?;
+        Ok(metadata.and_then(|m| serde_json::from_str(&m).ok()))
+    }
+
+    /// Drop the operator metadata of an item; returns whether it had any
+    pub async fn delete_item_metadata(&self, item: &Value) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
+        let removed: i32 = con.hdel(self.key(ITEM_METADATA_KEY), item_key(item)?).await?;
+        Ok(removed > 0)
+    }
+
+    /// Register items as members of this pool; returns how many were new. Each pool has
+    /// its own known items.
+    pub async fn add_known_items(&self, items: &[Value]) -> RedisResult<usize> {
+        if items.is_empty() {
+            return Ok(0);
+        }
+        let mut con = self.connection().await?;
+
+        let keys = items.iter().map(item_key).collect::<RedisResult<Vec<_>>>()?;
+        self.register_pool(&mut con).await?;
+        con.sadd(&self.keys.known, keys).await
+    }
+
+    /// Whether the item has been registered as a member of this pool
+    pub async fn is_known_item(&self, item: &Value) -> RedisResult<bool> {
+        let mut con = self.connection().await?;
+        con.sismember(&self.keys.known, item_key(item)?).await
+    }
+
+    /// Atomically move `count` items from the freelist into a new reservation that
+    /// expires after `ttl`. Returns None if fewer than `count` items are free.
+    pub async fn reserve_batch(&self, count: usize, ttl: Duration) -> RedisResult<Option<Reservation>> {
+        let mut con = self.connection().await?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let expires_at = now_secs() + ttl.as_secs();
+        let keys: Option<Vec<String>> = redis::Script::new(RESERVE_BATCH_SCRIPT)
+            .key(&self.keys.freelist)
+            .key(format!("{}{}", self.key(RESERVATION_KEY_PREFIX), id))
+            .key(self.key(RESERVATION_DEADLINES_KEY))
+            .arg(count)
+            .arg(&id)
+            .arg(expires_at)
+            .invoke_async(&mut con).await?;
+
+        match keys {
+            Some(keys) => Ok(Some(Reservation { id, items: parse_items(keys)?, expires_at })),
+            None => Ok(None),
+        }
+    }
+
+    /// Take items out of a reservation so they can be recorded as borrowed.
+    /// With `items` None the whole reservation is taken. Returns None if the
+    /// reservation does not exist (never created, already finished, or expired).
+    pub async fn take_reserved(&self, reservation_id: &str, items: Option<&[Value]>) -> RedisResult<Option<Vec<Value>>> {
+        let mut con = self.connection().await?;
+
+        let take = redis::Script::new(TAKE_RESERVED_SCRIPT);
+        let mut script = take.prepare_invoke();
+        script
+            .key(format!("{}{}", self.key(RESERVATION_KEY_PREFIX), reservation_id))
+            .key(self.key(RESERVATION_DEADLINES_KEY))
+            .arg(reservation_id);
+        for item in items.unwrap_or_default() {
+            script.arg(item_key(item)?);
+        }
+        let keys: Option<Vec<String>> = script.invoke_async(&mut con).await?;
+
+        keys.map(parse_items).transpose()
+    }
+
+    /// Return every item of a reservation to the freelist.
+    /// Returns how many were released, or None if the reservation does not exist.
+    pub async fn abort_reservation(&self, reservation_id: &str) -> RedisResult<Option<usize>> {
+        let mut con = self.connection().await?;
+
+        let released: Option<Vec<String>> = redis::Script::new(ABORT_RESERVATION_SCRIPT)
+            .key(format!("{}{}", self.key(RESERVATION_KEY_PREFIX), reservation_id))
+            .key(self.key(RESERVATION_DEADLINES_KEY))
+            .key(&self.keys.freelist)
+            .arg(reservation_id)
+            .arg(&self.keys.notify)
+            .invoke_async(&mut con).await?;
+        let Some(released) = released else {
+            return Ok(None);
+        };
+        for member in &released {
+            self.update_index(&mut con, "add", member).await?;
+        }
+        Ok(Some(released.len()))
+    }
+
+    /// Release expired reservations back to the freelist; returns the number of items released
+    pub async fn expire_reservations(&self) -> RedisResult<usize> {
+        let mut con = self.connection().await?;
+
+        let released: Vec<String> = redis::Script::new(EXPIRE_RESERVATIONS_SCRIPT)
+            .key(self.key(RESERVATION_DEADLINES_KEY))
+            .key(&self.keys.freelist)
+            .arg(now_secs())
+            .arg(self.key(RESERVATION_KEY_PREFIX))
+            .arg(&self.keys.notify)
+            .invoke_async(&mut con).await?;
+        for member in &released {
+            self.update_index(&mut con, "add", member).await?;
+        }
+        Ok(released.len())
+    }
+
+    /// Publish a `lease_expiring` message on the notify channel for each item of a
+    /// reservation that expires within `window` of `now` (unix seconds). Each
+    /// reservation is announced once. Returns the published messages.
+    pub async fn warn_expiring_reservations(&self, now: u64, window: Duration) -> RedisResult<Vec<String>> {
+        let mut con = self.connection().await?;
+
+        redis::Script::new(WARN_EXPIRING_SCRIPT)
+            .key(self.key(RESERVATION_DEADLINES_KEY))
+            .key(self.key(RESERVATION_WARNED_KEY))
+            .arg(now)
+            .arg(now + window.as_secs())
+            .arg(self.key(RESERVATION_KEY_PREFIX))
+            .arg(&self.keys.notify)
+            .invoke_async(&mut con).await
+    }
+
+    /// Publish a `lease_expiring` message on the notify channel for each item borrowed from
+    /// this pool whose lease (or borrow token) runs out within `window` of `now`. Each lease
+    /// is announced once, and again after a renewal moves its deadline.
+    pub async fn warn_expiring_leases(&self, now: u64, window: Duration) -> RedisResult<Vec<String>> {
+        let mut con = self.connection().await?;
+
+        redis::Script::new(WARN_EXPIRING_LEASES_SCRIPT)
+            .key(&self.keys.leases)
+            .key(&self.keys.lease_warned)
+            .key(&self.keys.tokens)
+            .arg(now)
+            .arg(now + window.as_secs())
+            .arg(&self.keys.notify)
+            .invoke_async(&mut con).await
+    }
+}
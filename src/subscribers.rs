@@ -1,11 +1,20 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
 
-use crate::config::{AppConfig, SubscriberDef};
-use reqwest::Client;
+use futures::stream::{self, StreamExt};
+use crate::config::{AppConfig, OperationSubscribers, SubscriberDef};
+use crate::metrics::Metrics;
+use crate::ops::{Broadcasters, OperationKind};
+use hmac::{Hmac, Mac};
+use reqwest::redirect::Policy;
+use reqwest::{Client, RequestBuilder};
+use sha2::Sha256;
 use serde::Serialize;
 use serde_json::Value;
 use serde::Deserialize;
-use tokio::time::{sleep, Duration};
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::AbortHandle;
+use tokio::time::{sleep, sleep_until, Duration, Instant};
 use reqwest::Url;
 
 #[derive(Debug, Serialize)]
@@ -27,25 +36,422 @@ pub struct SubmitEventPayload<'a> {
     pub item: &'a Value,
 }
 
+/// An event payload in the section's `payload_version`: version 1 is the payload as is,
+/// version 2 adds `version`, `event` and, for returns and submits, `operation_id`
+fn versioned_payload<T: Serialize>(section: &OperationSubscribers, ctx: &TemplateContext, payload: &T) -> Value {
+    let mut body = serde_json::to_value(payload).unwrap_or(Value::Null);
+    let version = section.payload_version();
+    if let (true, Value::Object(fields)) = (version >= 2, &mut body) {
+        fields.insert("version".to_string(), Value::from(version));
+        fields.insert("event".to_string(), Value::from(ctx.event));
+        if let Some(operation_id) = ctx.operation_id {
+            fields.insert("operation_id".to_string(), Value::from(operation_id));
+        }
+    }
+    body
+}
+
+/// Must-succeed subscribers that did not accept a notification, with their reasons
+#[derive(Debug, Clone, PartialEq)]
+pub struct Undelivered(pub Vec<(String, String)>);
+
+impl Undelivered {
+    /// Every reason, as reported on the operation
+    pub fn message(&self) -> String {
+        self.0.iter().map(|(_, reason)| reason.as_str()).collect::<Vec<_>>().join("; ")
+    }
+
+    /// Names of the subscribers that did not accept the notification
+    pub fn subscribers(&self) -> Vec<String> {
+        self.0.iter().map(|(name, _)| name.clone()).collect()
+    }
+}
+
+/// Values available to a subscriber's `body_template`
+pub struct TemplateContext<'a> {
+    pub event: &'a str,
+    pub item: &'a Value,
+    /// The return or submit operation being notified; none for borrows
+    pub operation_id: Option<&'a str>,
+}
+
+/// Render a `body_template`: placeholders in string values are replaced, and a string
+/// that is exactly one placeholder takes that value's JSON type (e.g. `"{{item}}"`)
+pub fn render_template(template: &Value, ctx: &TemplateContext) -> Value {
+    match template {
+        Value::String(text) => render_string(text, ctx),
+        Value::Array(values) => Value::Array(values.iter().map(|v| render_template(v, ctx)).collect()),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(k, v)| (k.clone(), render_template(v, ctx)))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+fn placeholder_value(name: &str, ctx: &TemplateContext) -> Option<Value> {
+    match name {
+        "item" => Some(ctx.item.clone()),
+        "ip" => Some(ctx.item.get("ip").cloned().unwrap_or(Value::Null)),
+        "event" => Some(Value::String(ctx.event.to_string())),
+        "operation_id" => Some(ctx.operation_id.map_or(Value::Null, |id| Value::String(id.to_string()))),
+        _ => None,
+    }
+}
+
+fn render_string(text: &str, ctx: &TemplateContext) -> Value {
+    let whole = text.strip_prefix("{{").and_then(|t| t.strip_suffix("}}")).map(str::trim);
+    if let Some(value) = whole.filter(|name| !name.contains("{{")).and_then(|name| placeholder_value(name, ctx)) {
+        return value;
+    }
+
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start..].find("}}") else { break };
+        out.push_str(&rest[..start]);
+        let name = rest[start + 2..start + len].trim();
+        match placeholder_value(name, ctx) {
+            Some(Value::String(s)) => out.push_str(&s),
+            Some(Value::Null) => {}
+            Some(value) => out.push_str(&value.to_string()),
+            // Unknown placeholders are left as written
+            None => out.push_str(&rest[start..start + len + 2]),
+        }
+        rest = &rest[start + len + 2..];
+    }
+    out.push_str(rest);
+    Value::String(out)
+}
+
+/// Most failed notifications kept for replay; the oldest are dropped beyond this
+const DEAD_LETTER_CAPACITY: usize = 1000;
+
+/// A notification an optional subscriber failed to accept, kept for replay
+#[derive(Debug, Clone)]
+pub struct DeadLetter {
+    pub kind: String,
+    pub subscriber: String,
+    /// The exact body that was posted
+    pub body: Value,
+}
+
+/// Outcome of one dead-letter replay pass
+#[derive(Debug, Default)]
+pub struct ReplaySummary {
+    pub replayed: usize,
+    pub failed: usize,
+    /// Left in place because the subscriber is disabled
+    pub skipped: usize,
+    /// Discarded because the subscriber is no longer configured
+    pub dropped: usize,
+    pub remaining: usize,
+}
+
+/// Deferred borrow notifications still waiting out their `notify_after_secs`
+struct DeferredBorrow {
+    item: Value,
+    task: AbortHandle,
+}
+
 #[derive(Clone)]
 pub struct Subscribers {
+    // Never follows redirects; a 3xx usually means a misconfigured webhook URL
     http: Client,
+    // For subscribers with `follow_redirects`
+    http_following: Client,
+    // Runtime overrides keyed by (kind, name); not persisted across restarts
+    disabled: Arc<RwLock<HashSet<(String, String)>>>,
+    // Failed optional notifications, oldest first; in memory only
+    dead_letters: Arc<Mutex<VecDeque<DeadLetter>>>,
+    // Pending deferred borrow notifications keyed by borrow token; in memory only
+    deferred_borrows: Arc<Mutex<HashMap<String, DeferredBorrow>>>,
+    // Most subscriber requests one dispatch has in flight at once
+    max_concurrency: usize,
+    // Request timeout of subscribers without their own `timeout_secs`
+    default_timeout_secs: u64,
+    // Operation event streams that retries are reported on
+    events: Option<Broadcasters>,
+    // Where failures and dispatch latencies are counted
+    metrics: Option<Arc<Metrics>>,
 }
 
 impl Subscribers {
-    pub fn new() -> Self {
+    /// Build the dispatcher, tuning its HTTP connection pool from the config
+    pub fn from_config(cfg: &AppConfig) -> Self {
+        let build = |redirects: Policy| {
+            let mut builder = Client::builder().redirect(redirects);
+            if let Some(max_idle) = cfg.subscriber_pool_max_idle_per_host {
+                builder = builder.pool_max_idle_per_host(max_idle);
+            }
+            if let Some(secs) = cfg.subscriber_pool_idle_timeout_secs {
+                builder = builder.pool_idle_timeout(Duration::from_secs(secs));
+            }
+            if cfg.http2 {
+                builder = builder.http2_prior_knowledge();
+            }
+            builder.build().expect("subscriber HTTP client")
+        };
         Self {
-            http: Client::new(),
+            http: build(Policy::none()),
+            http_following: build(Policy::limited(10)),
+            disabled: Arc::new(RwLock::new(HashSet::new())),
+            dead_letters: Arc::new(Mutex::new(VecDeque::new())),
+            deferred_borrows: Arc::new(Mutex::new(HashMap::new())),
+            max_concurrency: cfg.max_concurrency(),
+            default_timeout_secs: cfg.subscriber_timeout_secs(),
+            events: None,
+            metrics: None,
         }
     }
 
+    /// Report subscriber retries as `subscriber_retry` events on the operation's stream
+    pub fn with_events(mut self, events: Broadcasters) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    /// Count failures and dispatch latencies per subscriber on `/metrics`
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// The HTTP client matching a subscriber's redirect policy
+    fn client_for(&self, def: &SubscriberDef) -> &Client {
+        if def.follow_redirects {
+            &self.http_following
+        } else {
+            &self.http
+        }
+    }
+
+    /// A POST of `payload` to a subscriber with its client, timeout and, with an
+    /// `hmac_secret`, the signature of the exact body bytes sent
+    fn post_request(&self, def: &SubscriberDef, payload: &Value) -> Result<RequestBuilder, String> {
+        let body = serde_json::to_vec(payload).map_err(|e| format!("cannot serialize payload: {}", e))?;
+        let mut request = self
+            .client_for(def)
+            .post(&def.post)
+            .timeout(Duration::from_secs(self.timeout_of(def)))
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(secret) = def.hmac_secret()? {
+            request = request.header(SIGNATURE_HEADER, sign(&secret, &body));
+        }
+        Ok(request.body(body))
+    }
+
+    /// Enabled must-succeed subscribers that are unreachable, as `<kind>/<name>: <reason>`.
+    /// Each is sent a GET to its `health_path` (or `post` URL); only connection errors,
+    /// timeouts and 5xx answers count as down, since any other answer means it is up.
+    pub async fn unreachable_must_succeed(&self, cfg: &AppConfig) -> Vec<String> {
+        let mut probes = Vec::new();
+        for kind in ["borrow", "return", "submit"] {
+            let section = cfg.subscribers_for(kind).expect("known operation kind");
+            for (name, def) in &section.subscribers {
+                if def.mustSuceed && self.is_enabled(kind, name).await {
+                    probes.push(async move {
+                        self.probe(def).await.err().map(|reason| format!("{}/{}: {}", kind, name, reason))
+                    });
+                }
+            }
+        }
+
+        let mut down: Vec<String> = stream::iter(probes)
+            .buffer_unordered(self.max_concurrency)
+            .filter_map(|down| async move { down })
+            .collect()
+            .await;
+        down.sort();
+        down
+    }
+
+    async fn probe(&self, def: &SubscriberDef) -> Result<(), String> {
+        let url = match &def.health_path {
+            Some(path) => Url::parse(&def.post)
+                .and_then(|post| post.join(path))
+                .map_err(|e| format!("invalid health URL: {}", e))?,
+            None => Url::parse(&def.post).map_err(|e| format!("invalid post URL: {}", e))?,
+        };
+        let response = self
+            .client_for(def)
+            .get(url)
+            .timeout(Duration::from_secs(self.timeout_of(def)))
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+        if response.status().is_server_error() {
+            return Err(format!("http {}", response.status()));
+        }
+        Ok(())
+    }
+
+    /// Request timeout of a subscriber, in seconds
+    fn timeout_of(&self, def: &SubscriberDef) -> u64 {
+        def.timeout_secs.unwrap_or(self.default_timeout_secs)
+    }
+
+    async fn dead_letter(&self, kind: &str, subscriber: &str, body: Value) {
+        if let Some(metrics) = &self.metrics {
+            metrics.inc_subscriber_failure(kind, subscriber);
+        }
+        let mut queue = self.dead_letters.lock().await;
+        if queue.len() >= DEAD_LETTER_CAPACITY {
+            queue.pop_front();
+        }
+        queue.push_back(DeadLetter { kind: kind.to_string(), subscriber: subscriber.to_string(), body });
+    }
+
+    /// Re-post up to `limit` dead-lettered notifications, oldest first. Delivered
+    /// entries are removed; failed and skipped ones keep their place in the queue.
+    pub async fn replay_dead_letters(&self, cfg: &AppConfig, limit: usize) -> ReplaySummary {
+        let batch: Vec<DeadLetter> = {
+            let mut queue = self.dead_letters.lock().await;
+            let n = limit.min(queue.len());
+            queue.drain(..n).collect()
+        };
+
+        let mut summary = ReplaySummary::default();
+        let mut kept = Vec::new();
+        for letter in batch {
+            let def = cfg
+                .subscribers_for(&letter.kind)
+                .and_then(|section| section.subscribers.get(&letter.subscriber));
+            let Some(def) = def else {
+                summary.dropped += 1;
+                continue;
+            };
+            if !self.is_enabled(&letter.kind, &letter.subscriber).await {
+                summary.skipped += 1;
+                kept.push(letter);
+                continue;
+            }
+            let sent = match self.post_request(def, &letter.body) {
+                Ok(request) => request.send().await.ok(),
+                Err(_) => None,
+            };
+            match sent {
+                Some(resp) if resp.status().is_success() => summary.replayed += 1,
+                _ => {
+                    summary.failed += 1;
+                    kept.push(letter);
+                }
+            }
+        }
+
+        let mut queue = self.dead_letters.lock().await;
+        for letter in kept.into_iter().rev() {
+            queue.push_front(letter);
+        }
+        // Failures dead-lettered during the replay are newer than the kept ones; like
+        // `dead_letter`, drop the oldest beyond capacity
+        let excess = queue.len().saturating_sub(DEAD_LETTER_CAPACITY);
+        queue.drain(..excess);
+        summary.remaining = queue.len();
+        summary
+    }
+
+    /// Skip (or stop skipping) a subscriber during dispatch
+    pub async fn set_enabled(&self, kind: &str, name: &str, enabled: bool) {
+        let mut guard = self.disabled.write().await;
+        let key = (kind.to_string(), name.to_string());
+        if enabled {
+            guard.remove(&key);
+        } else {
+            guard.insert(key);
+        }
+    }
+
+    pub async fn is_enabled(&self, kind: &str, name: &str) -> bool {
+        let guard = self.disabled.read().await;
+        !guard.contains(&(kind.to_string(), name.to_string()))
+    }
+
     pub async fn notify_borrow(
         &self,
         cfg: &AppConfig,
         item: &Value,
         params: Option<&Value>,
     ) -> Result<(), (String, bool)> {
-        self.dispatch_and_wait(&cfg.borrow.subscribers, &BorrowEventPayload { item, params }).await
+        let ctx = TemplateContext { event: "borrow", item, operation_id: None };
+        let body = versioned_payload(&cfg.borrow, &ctx, &BorrowEventPayload { item, params });
+        self.dispatch_and_wait("borrow", &cfg.borrow.subscribers, &body, &ctx).await
+    }
+
+    /// Schedule the borrow subscribers with `notify_after_secs` for a recorded borrow.
+    /// Each is notified once its grace period has passed, unless the borrow is cancelled
+    /// first; failures are dead-lettered like those of any optional subscriber.
+    pub async fn defer_borrow(&self, cfg: &AppConfig, item: &Value, params: Option<&Value>, borrow_token: &str) {
+        let ctx = TemplateContext { event: "borrow", item, operation_id: None };
+        let body = versioned_payload(&cfg.borrow, &ctx, &BorrowEventPayload { item, params });
+        let mut due: Vec<(u64, String, SubscriberDef, Value)> = cfg
+            .borrow
+            .subscribers
+            .iter()
+            .filter(|(_, def)| def.applies_to(item))
+            .filter_map(|(name, def)| {
+                let after = def.notify_after_secs?;
+                let payload = match &def.body_template {
+                    Some(template) => render_template(template, &ctx),
+                    None => body.clone(),
+                };
+                Some((after, name.clone(), def.clone(), payload))
+            })
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+        due.sort_by_key(|(after, ..)| *after);
+
+        // Hold the lock while spawning so the task cannot finish before it is registered
+        let mut pending = self.deferred_borrows.lock().await;
+        let this = self.clone();
+        let token = borrow_token.to_string();
+        let task = tokio::spawn(async move {
+            let start = Instant::now();
+            for (after, name, def, payload) in due {
+                sleep_until(start + Duration::from_secs(after)).await;
+                this.post_optional("borrow", &name, &def, payload).await;
+            }
+            this.deferred_borrows.lock().await.remove(&token);
+        });
+        pending.insert(borrow_token.to_string(), DeferredBorrow { item: item.clone(), task: task.abort_handle() });
+    }
+
+    /// Drop the deferred notifications still pending for a borrow, e.g. because the item
+    /// came back within the grace period
+    pub async fn cancel_deferred_borrow(&self, borrow_token: &str) {
+        if let Some(deferred) = self.deferred_borrows.lock().await.remove(borrow_token) {
+            deferred.task.abort();
+        }
+    }
+
+    /// Like `cancel_deferred_borrow`, for callers that only know the item
+    pub async fn cancel_deferred_borrows_of(&self, item: &Value) {
+        self.deferred_borrows.lock().await.retain(|_, deferred| {
+            let keep = deferred.item != *item;
+            if !keep {
+                deferred.task.abort();
+            }
+            keep
+        });
+    }
+
+    /// Post a notification nothing waits on, dead-lettering it if it is not accepted
+    async fn post_optional(&self, kind: &str, name: &str, def: &SubscriberDef, payload: Value) {
+        if !self.is_enabled(kind, name).await {
+            return;
+        }
+        let sent = match self.post_request(def, &payload) {
+            Ok(request) => request.send().await.ok(),
+            Err(_) => None,
+        };
+        match sent {
+            Some(resp) if resp.status().is_success() => {}
+            _ => self.dead_letter(kind, name, payload).await,
+        }
     }
 
     pub async fn notify_return(
@@ -53,100 +459,268 @@ impl Subscribers {
         cfg: &AppConfig,
         item: &Value,
         params: Option<&Value>,
-    ) -> Result<(), (String, bool)> {
-        self.dispatch_and_wait(&cfg.r#return.subscribers, &ReturnEventPayload { item, params }).await
+        operation_id: &str,
+    ) -> Result<(), Undelivered> {
+        self.notify_operation(cfg, OperationKind::Return, item, params, operation_id, None).await
     }
 
-    pub async fn notify_submit(
+    pub async fn notify_submit(&self, cfg: &AppConfig, item: &Value, operation_id: &str) -> Result<(), Undelivered> {
+        self.notify_operation(cfg, OperationKind::Submit, item, None, operation_id, None).await
+    }
+
+    /// Notify the return or submit subscribers of an operation, or with `only` just the
+    /// named ones, as the retry queue does for those that have not accepted it yet
+    pub async fn notify_operation(
         &self,
         cfg: &AppConfig,
+        kind: OperationKind,
         item: &Value,
-    ) -> Result<(), (String, bool)> {
-        self.dispatch_and_wait(&cfg.submit.subscribers, &SubmitEventPayload { item }).await
+        params: Option<&Value>,
+        operation_id: &str,
+        only: Option<&[String]>,
+    ) -> Result<(), Undelivered> {
+        let ctx = TemplateContext { event: kind.as_str(), item, operation_id: Some(operation_id) };
+        let (section, body) = match kind {
+            OperationKind::Return => {
+                (&cfg.r#return, versioned_payload(&cfg.r#return, &ctx, &ReturnEventPayload { item, params }))
+            }
+            OperationKind::Submit => (&cfg.submit, versioned_payload(&cfg.submit, &ctx, &SubmitEventPayload { item })),
+        };
+        self.dispatch_all(kind.as_str(), &section.subscribers, &body, &ctx, only).await
     }
 
 }
 
+/// Header carrying the HMAC-SHA256 of a request body, GitHub webhook style
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// `sha256=<hex HMAC-SHA256 of body under secret>`, the value of `SIGNATURE_HEADER`
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    let digest: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256={}", digest)
+}
+
+/// Backoff before retry number `attempt` (from 1): `base_ms` doubled per earlier retry,
+/// plus up to half of that again as jitter so retries of many operations spread out
+fn retry_delay(base_ms: u64, attempt: u32) -> Duration {
+    let backoff = base_ms.saturating_mul(1 << (attempt - 1).min(16));
+    let jitter = (uuid::Uuid::new_v4().as_u128() % (backoff as u128 / 2 + 1)) as u64;
+    Duration::from_millis(backoff + jitter)
+}
+
 #[derive(Debug, Deserialize)]
 struct OperationAck {
     #[serde(default)]
     operation_id: String,
     #[serde(default)]
+    #[allow(dead_code)]
     status: String,
 }
 
 impl Subscribers {
 
+    /// Notify every applicable subscriber of `kind`, at most `max_concurrency` at a time.
+    /// Optional failures are dead-lettered; the failures of must-succeed subscribers are
+    /// reported together once all of them have finished.
     async fn dispatch_and_wait<T: Serialize + ?Sized>(
         &self,
+        kind: &str,
         subs: &HashMap<String, SubscriberDef>,
         body: &T,
+        ctx: &TemplateContext<'_>,
     ) -> Result<(), (String, bool)> {
+        self.dispatch_all(kind, subs, body, ctx, None).await.map_err(|undelivered| (undelivered.message(), true))
+    }
+
+    /// `dispatch_and_wait`, limited to the subscribers in `only` if given, reporting which
+    /// must-succeed subscribers failed
+    async fn dispatch_all<T: Serialize + ?Sized>(
+        &self,
+        kind: &str,
+        subs: &HashMap<String, SubscriberDef>,
+        body: &T,
+        ctx: &TemplateContext<'_>,
+        only: Option<&[String]>,
+    ) -> Result<(), Undelivered> {
+        let mut due = Vec::new();
         for (name, def) in subs {
-            let resp = match self.http.post(&def.post).json(&body).send().await {
-                Ok(r) => r,
-                Err(e) => {
-                    if def.mustSuceed { return Err((format!("subscriber `{}` request error: {}", name, e), true)); }
-                    else { continue; }
+            // Deferred subscribers are notified later by `defer_borrow`
+            if def.notify_after_secs.is_some() || !self.is_enabled(kind, name).await || !def.applies_to(ctx.item) {
+                continue;
+            }
+            if only.is_some_and(|names| !names.contains(name)) {
+                continue;
+            }
+            let payload = match &def.body_template {
+                Some(template) => render_template(template, ctx),
+                None => serde_json::to_value(body).unwrap_or(Value::Null),
+            };
+            due.push(async move {
+                let outcome = self.dispatch_timed(kind, name, def, payload, ctx.operation_id).await;
+                outcome.map_err(|reason| (name.clone(), reason))
+            });
+        }
+
+        let mut failures: Vec<(String, String)> = stream::iter(due)
+            .buffer_unordered(self.max_concurrency)
+            .filter_map(|outcome| async move { outcome.err() })
+            .collect()
+            .await;
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            failures.sort();
+            Err(Undelivered(failures))
+        }
+    }
+
+    /// Post to a subscriber until it answers 2xx, retrying connection errors, timeouts and
+    /// 5xx answers up to `max_retries` times with exponential backoff. Any other answer is
+    /// final. Each retry is announced on the operation's event stream, if there is one.
+    async fn post_with_retries(
+        &self,
+        name: &str,
+        def: &SubscriberDef,
+        payload: &Value,
+        operation_id: Option<&str>,
+    ) -> Result<reqwest::Response, String> {
+        let timeout_secs = self.timeout_of(def);
+        let mut attempt = 0;
+        loop {
+            let request = self.post_request(def, payload).map_err(|e| format!("subscriber `{}`: {}", name, e))?;
+            let failure = match request.send().await {
+                Ok(resp) if resp.status().is_success() => return Ok(resp),
+                Ok(resp) if resp.status().is_redirection() => {
+                    let location = resp.headers().get(reqwest::header::LOCATION).and_then(|l| l.to_str().ok());
+                    return Err(format!(
+                        "subscriber `{}` redirected with http {} to {}; fix its post URL or set follow_redirects",
+                        name,
+                        resp.status(),
+                        location.unwrap_or("an unknown location")
+                    ));
+                }
+                Ok(resp) if !resp.status().is_server_error() => {
+                    return Err(format!("subscriber `{}` http {}", name, resp.status()));
                 }
+                Ok(resp) => format!("subscriber `{}` http {}", name, resp.status()),
+                Err(e) if e.is_timeout() => format!("subscriber `{}` timed out after {} seconds", name, timeout_secs),
+                Err(e) => format!("subscriber `{}` request error: {}", name, e),
             };
+            if attempt >= def.max_retries {
+                return Err(failure);
+            }
+            attempt += 1;
+            let delay = retry_delay(def.base_delay_ms(), attempt);
+            if let (Some(events), Some(op_id)) = (&self.events, operation_id) {
+                let event = serde_json::json!({
+                    "event": "subscriber_retry",
+                    "subscriber": name,
+                    "attempt": attempt,
+                    "delay_ms": delay.as_millis() as u64,
+                    "reason": failure,
+                });
+                events.notify(op_id, event.to_string()).await;
+            }
+            sleep(delay).await;
+        }
+    }
+
+    /// `dispatch_one`, counting its latency and, for must-succeed subscribers, its failure
+    /// (dead-lettered optional ones are counted by `dead_letter`)
+    async fn dispatch_timed(
+        &self,
+        kind: &str,
+        name: &str,
+        def: &SubscriberDef,
+        payload: Value,
+        operation_id: Option<&str>,
+    ) -> Result<(), String> {
+        let started = Instant::now();
+        let outcome = self.dispatch_one(kind, name, def, payload, operation_id).await;
+        if let Some(metrics) = &self.metrics {
+            metrics.observe_subscriber_dispatch(kind, name, started.elapsed());
+            if outcome.is_err() {
+                metrics.inc_subscriber_failure(kind, name);
+            }
+        }
+        outcome
+    }
 
-            if !resp.status().is_success() {
-                if def.mustSuceed { return Err((format!("subscriber `{}` http {}", name, resp.status()), true)); }
-                else { continue; }
+    /// Post to one subscriber and, for async must-succeed ones, poll until it finishes.
+    /// Errors only for must-succeed subscribers; optional ones are dead-lettered instead.
+    async fn dispatch_one(
+        &self,
+        kind: &str,
+        name: &str,
+        def: &SubscriberDef,
+        payload: Value,
+        operation_id: Option<&str>,
+    ) -> Result<(), String> {
+        let timeout_secs = self.timeout_of(def);
+        let timeout = Duration::from_secs(timeout_secs);
+        let resp = match self.post_with_retries(name, def, &payload, operation_id).await {
+            Ok(r) => r,
+            Err(e) => {
+                if def.mustSuceed { return Err(e); }
+                else { self.dead_letter(kind, name, payload).await; return Ok(()); }
             }
+        };
 
-            if def.mustSuceed && def.r#async {
-                // Try to read operation_id and poll until completion
-                let ack: OperationAck = match resp.json().await {
-                    Ok(a) => a,
-                    Err(e) => { return Err((format!("subscriber `{}`: invalid JSON ack: {}", name, e), true)); }
-                };
-                if ack.operation_id.is_empty() {
-                    return Err((format!("subscriber `{}` did not return operation_id" , name), true));
-                }
+        if def.mustSuceed && def.r#async {
+            // Try to read operation_id and poll until completion
+            let ack: OperationAck = match resp.json().await {
+                Ok(a) => a,
+                Err(e) => { return Err(format!("subscriber `{}`: invalid JSON ack: {}", name, e)); }
+            };
+            if ack.operation_id.is_empty() {
+                return Err(format!("subscriber `{}` did not return operation_id" , name));
+            }
 
-                // Derive status URL from base of post URL
-                let post_url = Url::parse(&def.post).map_err(|e| (format!("bad post url for `{}`: {}", name, e), true))?;
-                let mut base = post_url;
-                let _ = base.path(); // ensure parse
-                base.set_path("/operations/status");
-                base.set_query(Some(&format!("id={}", ack.operation_id)));
-
-                // Poll until succeeded/failed
-                #[derive(Deserialize)]
-                struct StatusResp { status: String, message: Option<String> }
-                let mut attempts = 0u32;
-                let max_attempts = 1800u32; // ~1 hour at 2s interval
-                loop {
-                    let res = self.http.get(base.as_str()).send().await;
-                    match res {
-                        Ok(r) if r.status().is_success() => {
-                            match r.json::<StatusResp>().await {
-                                Ok(sr) => {
-                                    let s = sr.status.to_lowercase();
-                                    if s == "succeeded" || s == "success" || s == "ok" {
-                                        break; // done
-                                    } else if s == "failed" || s == "error" {
-                                        return Err((format!("subscriber `{}` op failed: {}", name, sr.message.unwrap_or_default()), true));
-                                    }
-                                }
-                                Err(e) => {
-                                    return Err((format!("subscriber `{}` status parse error: {}", name, e), true));
+            // Derive status URL from base of post URL
+            let post_url = Url::parse(&def.post).map_err(|e| format!("bad post url for `{}`: {}", name, e))?;
+            let mut base = post_url;
+            let _ = base.path(); // ensure parse
+            base.set_path("/operations/status");
+            base.set_query(Some(&format!("id={}", ack.operation_id)));
+
+            // Poll until succeeded/failed
+            #[derive(Deserialize)]
+            struct StatusResp { status: String, message: Option<String> }
+            let mut attempts = 0u32;
+            let max_attempts = 1800u32; // ~1 hour at 2s interval
+            loop {
+                let res = self.client_for(def).get(base.as_str()).timeout(timeout).send().await;
+                match res {
+                    Ok(r) if r.status().is_success() => {
+                        match r.json::<StatusResp>().await {
+                            Ok(sr) => {
+                                let s = sr.status.to_lowercase();
+                                if s == "succeeded" || s == "success" || s == "ok" {
+                                    break; // done
+                                } else if s == "failed" || s == "error" {
+                                    return Err(format!("subscriber `{}` op failed: {}", name, sr.message.unwrap_or_default()));
                                 }
                             }
+                            Err(e) => {
+                                return Err(format!("subscriber `{}` status parse error: {}", name, e));
+                            }
                         }
-                        Ok(r) => {
-                            return Err((format!("subscriber `{}` status http {}", name, r.status()), true));
-                        }
-                        Err(e) => {
-                            return Err((format!("subscriber `{}` status request error: {}", name, e), true));
-                        }
                     }
-                    attempts += 1;
-                    if attempts >= max_attempts { return Err((format!("subscriber `{}` op timeout", name), true)); }
-                    sleep(Duration::from_secs(2)).await;
+                    Ok(r) => {
+                        return Err(format!("subscriber `{}` status http {}", name, r.status()));
+                    }
+                    Err(e) if e.is_timeout() => {
+                        return Err(format!("subscriber `{}` status check timed out after {} seconds", name, timeout_secs));
+                    }
+                    Err(e) => {
+                        return Err(format!("subscriber `{}` status request error: {}", name, e));
+                    }
                 }
+                attempts += 1;
+                if attempts >= max_attempts { return Err(format!("subscriber `{}` op timeout", name)); }
+                sleep(Duration::from_secs(2)).await;
             }
         }
         Ok(())
@@ -154,3 +728,199 @@ impl Subscribers {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn template_placeholders_render_against_item() {
+        let item = json!({"ip": "10.0.0.7", "port": 22});
+        let ctx = TemplateContext { event: "return", item: &item, operation_id: Some("op-1") };
+
+        let template = json!({"address": "{{ip}}", "data": {"item": "{{item}}", "kind": "{{ event }}"}});
+        assert_eq!(
+            render_template(&template, &ctx),
+            json!({"address": "10.0.0.7", "data": {"item": {"ip": "10.0.0.7", "port": 22}, "kind": "return"}})
+        );
+
+        let template = json!(["{{operation_id}}", "host {{ip}} via {{event}}", "{{unknown}}", 5]);
+        assert_eq!(
+            render_template(&template, &ctx),
+            json!(["op-1", "host 10.0.0.7 via return", "{{unknown}}", 5])
+        );
+    }
+
+    #[test]
+    fn missing_values_render_as_null_or_empty() {
+        let item = json!("plain-item");
+        let ctx = TemplateContext { event: "borrow", item: &item, operation_id: None };
+
+        let template = json!({"op": "{{operation_id}}", "ip": "{{ip}}", "note": "op={{operation_id}}"});
+        assert_eq!(render_template(&template, &ctx), json!({"op": null, "ip": null, "note": "op="}));
+    }
+
+    #[test]
+    fn payloads_follow_the_section_version() {
+        let item = json!({"ip": "10.0.0.7"});
+        let ctx = TemplateContext { event: "return", item: &item, operation_id: Some("op-1") };
+        let payload = ReturnEventPayload { item: &item, params: None };
+
+        let latest = OperationSubscribers::default();
+        assert_eq!(
+            versioned_payload(&latest, &ctx, &payload),
+            json!({"version": 2, "event": "return", "item": {"ip": "10.0.0.7"}, "operation_id": "op-1"})
+        );
+        let pinned = OperationSubscribers { payload_version: Some(1), ..Default::default() };
+        assert_eq!(versioned_payload(&pinned, &ctx, &payload), json!({"item": {"ip": "10.0.0.7"}}));
+    }
+
+    /// Start a subscriber that answers every POST with 200 after `delay`, one at a time
+    fn slow_subscriber(delay: Duration) -> String {
+        use std::io::{BufRead, BufReader, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind subscriber");
+        let url = format!("http://{}/hook", listener.local_addr().expect("local addr"));
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else { continue };
+                let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                std::thread::sleep(delay);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}");
+            }
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn subscribers_are_notified_concurrently() {
+        let subs: HashMap<String, SubscriberDef> = (1..=3)
+            .map(|n| {
+                let post = slow_subscriber(Duration::from_millis(500));
+                (format!("slow-{}", n), SubscriberDef { post, mustSuceed: true, ..Default::default() })
+            })
+            .collect();
+        let dispatcher = Subscribers::from_config(&AppConfig::default());
+        let item = json!({"ip": "10.0.0.9"});
+        let ctx = TemplateContext { event: "return", item: &item, operation_id: Some("op-1") };
+
+        let started = Instant::now();
+        let outcome = dispatcher
+            .dispatch_and_wait("return", &subs, &ReturnEventPayload { item: &item, params: None }, &ctx)
+            .await;
+        assert_eq!(outcome, Ok(()));
+        assert!(started.elapsed() < Duration::from_millis(1200), "took {:?}", started.elapsed());
+    }
+
+    #[tokio::test]
+    async fn readiness_probes_only_enabled_must_succeed_subscribers() {
+        let down = {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind");
+            format!("http://{}/hook", listener.local_addr().expect("local addr"))
+        };
+        let mut cfg = AppConfig::default();
+        let subs = &mut cfg.r#return.subscribers;
+        subs.insert("up".into(), SubscriberDef { post: slow_subscriber(Duration::ZERO), mustSuceed: true, ..Default::default() });
+        subs.insert("optional".into(), SubscriberDef { post: down.clone(), ..Default::default() });
+        subs.insert("down".into(), SubscriberDef { post: down, mustSuceed: true, ..Default::default() });
+        let dispatcher = Subscribers::from_config(&cfg);
+
+        let unreachable = dispatcher.unreachable_must_succeed(&cfg).await;
+        assert_eq!(unreachable.len(), 1, "{:?}", unreachable);
+        assert!(unreachable[0].starts_with("return/down: "), "{:?}", unreachable);
+
+        dispatcher.set_enabled("return", "down", false).await;
+        assert!(dispatcher.unreachable_must_succeed(&cfg).await.is_empty());
+    }
+
+    /// Headers (lowercased names) and body of a captured request
+    type CapturedRequest = (HashMap<String, String>, Vec<u8>);
+
+    /// Start a subscriber that answers one POST with 200 and hands over the request
+    fn capturing_subscriber() -> (String, std::sync::mpsc::Receiver<CapturedRequest>) {
+        use std::io::{BufRead, BufReader, Read, Write};
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind subscriber");
+        let url = format!("http://{}/hook", listener.local_addr().expect("local addr"));
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let Ok((mut stream, _)) = listener.accept() else { return };
+            let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+            let mut headers = HashMap::new();
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+                }
+            }
+            let mut body = vec![0; headers.get("content-length").and_then(|l| l.parse().ok()).unwrap_or(0)];
+            let _ = reader.read_exact(&mut body);
+            let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}");
+            let _ = tx.send((headers, body));
+        });
+        (url, rx)
+    }
+
+    #[test]
+    fn signature_matches_rfc_4231_vector() {
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn requests_are_signed_over_the_exact_body() {
+        std::env::set_var("SUBSCRIBERS_TEST_HMAC_SECRET", "Jefe");
+        let (post, captured) = capturing_subscriber();
+        let def = SubscriberDef {
+            post,
+            mustSuceed: true,
+            hmac_secret: Some("${SUBSCRIBERS_TEST_HMAC_SECRET}".to_string()),
+            ..Default::default()
+        };
+        let subs = HashMap::from([("signed".to_string(), def)]);
+        let dispatcher = Subscribers::from_config(&AppConfig::default());
+        let item = json!({"ip": "10.0.0.11"});
+        let ctx = TemplateContext { event: "submit", item: &item, operation_id: Some("op-3") };
+
+        let outcome = dispatcher.dispatch_and_wait("submit", &subs, &SubmitEventPayload { item: &item }, &ctx).await;
+        assert_eq!(outcome, Ok(()));
+        let (headers, body) = captured.recv().expect("captured request");
+        assert_eq!(body, br#"{"item":{"ip":"10.0.0.11"}}"#);
+        assert_eq!(headers.get("content-type").map(String::as_str), Some("application/json"));
+        let signature = headers.get("x-signature-256").expect("signature header");
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"Jefe").expect("HMAC key");
+        mac.update(&body);
+        let expected: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+        assert_eq!(signature, &format!("sha256={}", expected));
+    }
+
+    #[tokio::test]
+    async fn subscriber_timeouts_fail_only_must_succeed() {
+        let post = slow_subscriber(Duration::from_secs(3));
+        let dispatcher = Subscribers::from_config(&AppConfig::default());
+        let item = json!({"ip": "10.0.0.10"});
+        let ctx = TemplateContext { event: "submit", item: &item, operation_id: Some("op-2") };
+        let notify = |must_succeed: bool| {
+            let def = SubscriberDef { post: post.clone(), mustSuceed: must_succeed, timeout_secs: Some(1), ..Default::default() };
+            HashMap::from([("slow".to_string(), def)])
+        };
+
+        let started = Instant::now();
+        let outcome = dispatcher.dispatch_and_wait("submit", &notify(true), &SubmitEventPayload { item: &item }, &ctx).await;
+        assert_eq!(outcome, Err(("subscriber `slow` timed out after 1 seconds".to_string(), true)));
+        assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+
+        let outcome = dispatcher.dispatch_and_wait("submit", &notify(false), &SubmitEventPayload { item: &item }, &ctx).await;
+        assert_eq!(outcome, Ok(()));
+    }
+}
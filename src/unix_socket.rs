@@ -0,0 +1,91 @@
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::os::unix::fs::FileTypeExt;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rocket::fairing::{Fairing, Info, Kind};
+use rocket::{Orbit, Rocket};
+use tokio::net::{TcpStream, UnixListener};
+
+/// Pause after a failed accept, so a persistent error (e.g. out of file descriptors) does
+/// not spin the accept loop
+const ACCEPT_BACKOFF: Duration = Duration::from_millis(100);
+
+/// Also serves the API on a unix domain socket.
+///
+/// Rocket 0.5 only binds TCP, so once it is listening each socket connection is
+/// relayed to the TCP listener on loopback. Requests thus come from `127.0.0.1`, and
+/// socket clients without `X-Owner-Id` all get that address as their owner id.
+pub struct UnixSocketListener {
+    pub path: PathBuf,
+}
+
+#[rocket::async_trait]
+impl Fairing for UnixSocketListener {
+    fn info(&self) -> Info {
+        Info {
+            name: "Unix socket listener",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let config = rocket.config();
+        let address = if config.address.is_unspecified() {
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        } else {
+            config.address
+        };
+        let upstream = SocketAddr::new(address, config.port);
+
+        // A socket file left over from a previous run would make bind fail; anything else
+        // at the path is not ours to delete
+        match std::fs::symlink_metadata(&self.path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                if let Err(e) = std::fs::remove_file(&self.path) {
+                    log::warn!("failed to remove stale unix socket {}: {}", self.path.display(), e);
+                    return;
+                }
+            }
+            Ok(_) => {
+                log::warn!("not listening on unix socket {}: the path exists and is not a socket", self.path.display());
+                return;
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                log::warn!("failed to inspect unix socket path {}: {}", self.path.display(), e);
+                return;
+            }
+        }
+        let listener = match UnixListener::bind(&self.path) {
+            Ok(listener) => listener,
+            Err(e) => {
+                log::warn!("failed to bind unix socket {}: {}", self.path.display(), e);
+                return;
+            }
+        };
+        log::info!("listening on unix socket {}", self.path.display());
+
+        tokio::spawn(async move {
+            loop {
+                let mut inbound = match listener.accept().await {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        log::warn!("unix socket accept failed: {}", e);
+                        tokio::time::sleep(ACCEPT_BACKOFF).await;
+                        continue;
+                    }
+                };
+                tokio::spawn(async move {
+                    match TcpStream::connect(upstream).await {
+                        Ok(mut outbound) => {
+                            let _ = tokio::io::copy_bidirectional(&mut inbound, &mut outbound).await;
+                        }
+                        Err(e) => log::warn!("unix socket relay to {} failed: {}", upstream, e),
+                    }
+                });
+            }
+        });
+    }
+}
@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::store::now_secs;
+
+/// A client parked in `/borrow?wait`
+#[derive(Debug, Clone)]
+pub struct Waiter {
+    /// Owner id (or client IP) of the waiting request
+    pub owner: Option<String>,
+    /// Unix timestamp (seconds) at which the request started waiting
+    pub since: u64,
+}
+
+#[derive(Default)]
+struct Inner {
+    next_id: u64,
+    waiters: HashMap<u64, Waiter>,
+}
+
+/// The requests currently parked in `/borrow?wait`, bounded by `max_waiters`
+#[derive(Default)]
+pub struct WaitQueue {
+    inner: Mutex<Inner>,
+}
+
+impl WaitQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Join the queue unless it already holds `max` waiters; returns the waiter's id
+    pub fn try_enter(&self, owner: Option<String>, max: usize) -> Option<u64> {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        if inner.waiters.len() >= max {
+            return None;
+        }
+        inner.next_id += 1;
+        let id = inner.next_id;
+        inner.waiters.insert(id, Waiter { owner, since: now_secs() });
+        Some(id)
+    }
+
+    pub fn leave(&self, id: u64) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.waiters.remove(&id);
+    }
+
+    /// Current waiters, longest waiting first
+    pub fn snapshot(&self) -> Vec<Waiter> {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let mut waiters: Vec<Waiter> = inner.waiters.values().cloned().collect();
+        waiters.sort_by_key(|w| w.since);
+        waiters
+    }
+}
@@ -0,0 +1,277 @@
+//! Shared helpers for the integration tests.
+#![allow(dead_code)]
+
+use rocket::local::blocking::Client;
+use testcontainers::{clients, Container};
+use testcontainers_modules::redis::Redis;
+
+/// Start a Redis container and return it together with its connection URL.
+/// The container is stopped when the returned handle is dropped.
+pub fn start_redis(docker: &clients::Cli) -> (Container<'_, Redis>, String) {
+    let container = docker.run(Redis);
+    let port = container.get_host_port_ipv4(6379);
+    let url = format!("redis://127.0.0.1:{}", port);
+    (container, url)
+}
+
+/// Open a raw Redis connection for seeding and inspecting state
+pub fn redis_connection(redis_url: &str) -> redis::Connection {
+    let client = redis::Client::open(redis_url).expect("Failed to connect to Redis");
+    client.get_connection().expect("Failed to get Redis connection")
+}
+
+/// Add raw JSON items to the freelist
+pub fn seed_freelist(redis_url: &str, items: &[&str]) {
+    let mut con = redis_connection(redis_url);
+    for item in items {
+        let _: () = redis::cmd("SADD")
+            .arg("freelist")
+            .arg(*item)
+            .query(&mut con)
+            .expect("Failed to add item to freelist");
+    }
+}
+
+/// Number of items currently in the freelist
+pub fn freelist_size(redis_url: &str) -> usize {
+    let mut con = redis_connection(redis_url);
+    redis::cmd("SCARD")
+        .arg("freelist")
+        .query(&mut con)
+        .expect("Failed to read freelist size")
+}
+
+/// Borrow an item and return the parsed response body
+pub fn borrow(client: &Client) -> serde_json::Value {
+    let response = client.get("/borrow").dispatch();
+    assert_eq!(response.status(), rocket::http::Status::Ok);
+    serde_json::from_str(&response.into_string().expect("Response body")).expect("Valid JSON")
+}
+
+/// Poll `/operations/<id>` until the operation reaches a terminal state
+pub fn wait_for_operation(client: &Client, operation_id: &str) -> serde_json::Value {
+    for _ in 0..50 {
+        let response = client.get(format!("/operations/{}", operation_id)).dispatch();
+        let body: serde_json::Value =
+            serde_json::from_str(&response.into_string().expect("Response body")).expect("Valid JSON");
+        let status = body["status"].as_str().unwrap_or_default();
+        if status == "succeeded" || status == "failed" {
+            return body;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    panic!("operation {} did not reach a terminal state", operation_id);
+}
+
+/// Start a subscriber endpoint that answers every POST with 200 `{}`.
+/// Returns its URL and a counter of the requests it received.
+pub fn spawn_subscriber() -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    spawn_subscriber_at("127.0.0.1:0")
+}
+
+/// Like [`spawn_subscriber`], listening on a fixed address (e.g. to bring a
+/// subscriber back up where it was before)
+pub fn spawn_subscriber_at(addr: &str) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    serve_subscriber(addr, 0, "")
+}
+
+/// Like [`spawn_subscriber`], answering the first `failures` requests with `status`
+/// (e.g. `"503 Service Unavailable"`)
+pub fn spawn_flaky_subscriber(
+    failures: usize,
+    status: &'static str,
+) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    serve_subscriber("127.0.0.1:0", failures, status)
+}
+
+fn serve_subscriber(
+    addr: &str,
+    failures: usize,
+    failure_status: &'static str,
+) -> (String, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let listener = std::net::TcpListener::bind(addr).expect("bind subscriber");
+    let url = format!("http://{}/hook", listener.local_addr().expect("local addr"));
+    let hits = std::sync::Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap_or(0);
+                }
+            }
+            let mut body = vec![0; content_length];
+            let _ = reader.read_exact(&mut body);
+            let status = if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                failure_status
+            } else {
+                "200 OK"
+            };
+            let _ = stream.write_all(
+                format!(
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                    status
+                )
+                .as_bytes(),
+            );
+        }
+    });
+    (url, hits)
+}
+
+/// Start an endpoint that answers every request with `307 Temporary Redirect` to `to`.
+/// Returns its URL.
+pub fn spawn_redirect(to: &str) -> String {
+    use std::io::{BufRead, BufReader, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind redirect");
+    let url = format!("http://{}/old-hook", listener.local_addr().expect("local addr"));
+    let to = to.to_string();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let mut reader = BufReader::new(stream.try_clone().expect("clone stream"));
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                line.clear();
+            }
+            let _ = stream.write_all(
+                format!(
+                    "HTTP/1.1 307 Temporary Redirect\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    to
+                )
+                .as_bytes(),
+            );
+        }
+    });
+    url
+}
+
+/// Start a stand-in for a TLS Redis (`rediss://`) with a freshly generated self-signed
+/// certificate for `localhost`. It answers every command with `+PONG`, enough for
+/// `PING`. Returns its `rediss://` URL.
+pub fn spawn_tls_redis() -> String {
+    use openssl::asn1::Asn1Time;
+    use openssl::hash::MessageDigest;
+    use openssl::pkey::PKey;
+    use openssl::rsa::Rsa;
+    use openssl::ssl::{SslAcceptor, SslMethod};
+    use openssl::x509::{X509NameBuilder, X509};
+    use std::io::{Read, Write};
+
+    let key = PKey::from_rsa(Rsa::generate(2048).expect("RSA key")).expect("private key");
+    let mut name = X509NameBuilder::new().expect("name builder");
+    name.append_entry_by_text("CN", "localhost").expect("common name");
+    let name = name.build();
+    let mut cert = X509::builder().expect("certificate builder");
+    cert.set_version(2).expect("version");
+    cert.set_subject_name(&name).expect("subject");
+    cert.set_issuer_name(&name).expect("issuer");
+    cert.set_pubkey(&key).expect("public key");
+    cert.set_not_before(&Asn1Time::days_from_now(0).expect("not before")).expect("not before");
+    cert.set_not_after(&Asn1Time::days_from_now(1).expect("not after")).expect("not after");
+    cert.sign(&key, MessageDigest::sha256()).expect("self-signed");
+    let cert = cert.build();
+
+    let mut acceptor = SslAcceptor::mozilla_intermediate(SslMethod::tls()).expect("TLS acceptor");
+    acceptor.set_private_key(&key).expect("acceptor key");
+    acceptor.set_certificate(&cert).expect("acceptor certificate");
+    let acceptor = std::sync::Arc::new(acceptor.build());
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind TLS redis");
+    let url = format!("rediss://localhost:{}/", listener.local_addr().expect("local addr").port());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else { continue };
+            let acceptor = acceptor.clone();
+            std::thread::spawn(move || {
+                let Ok(mut stream) = acceptor.accept(stream) else { return };
+                let mut buf = [0; 1024];
+                while stream.read(&mut buf).unwrap_or(0) > 0 {
+                    if stream.write_all(b"+PONG\r\n").is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    url
+}
+
+/// Start a stand-in Redis that answers `ROLE` with `master`, or with `slave` once
+/// `demoted` is set, and every other command with `+PONG`. Returns its port and a count
+/// of the connections it accepted.
+pub fn spawn_fake_redis(
+    demoted: std::sync::Arc<std::sync::atomic::AtomicBool>,
+) -> (u16, std::sync::Arc<std::sync::atomic::AtomicUsize>) {
+    use std::io::{Read, Write};
+    use std::sync::atomic::Ordering;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind fake redis");
+    let port = listener.local_addr().expect("local addr").port();
+    let accepted = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = accepted.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            counter.fetch_add(1, Ordering::SeqCst);
+            let demoted = demoted.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0; 1024];
+                loop {
+                    let read = stream.read(&mut buf).unwrap_or(0);
+                    if read == 0 {
+                        return;
+                    }
+                    let is_role = String::from_utf8_lossy(&buf[..read]).to_uppercase().contains("ROLE");
+                    let reply: &[u8] = match (is_role, demoted.load(Ordering::SeqCst)) {
+                        (true, false) => b"*1\r\n$6\r\nmaster\r\n",
+                        (true, true) => b"*1\r\n$5\r\nslave\r\n",
+                        (false, _) => b"+PONG\r\n",
+                    };
+                    if stream.write_all(reply).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    (port, accepted)
+}
+
+/// Start a stand-in Redis Sentinel that names `127.0.0.1` and the port in `master` as
+/// the master, whatever it is asked. Returns its `host:port` address.
+pub fn spawn_fake_sentinel(master: std::sync::Arc<std::sync::atomic::AtomicU16>) -> String {
+    use std::io::{Read, Write};
+    use std::sync::atomic::Ordering;
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("bind fake sentinel");
+    let address = listener.local_addr().expect("local addr").to_string();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let Ok(mut stream) = stream else { continue };
+            let master = master.clone();
+            std::thread::spawn(move || {
+                let mut buf = [0; 1024];
+                while stream.read(&mut buf).unwrap_or(0) > 0 {
+                    let port = master.load(Ordering::SeqCst).to_string();
+                    let reply = format!("*2\r\n$9\r\n127.0.0.1\r\n${}\r\n{}\r\n", port.len(), port);
+                    if stream.write_all(reply.as_bytes()).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+    });
+    address
+}